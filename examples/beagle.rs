@@ -16,11 +16,11 @@ use std::io::Write;
 
 use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::spi::{Transfer as SpiTransfer, Write as SpiWrite};
-use embedded_hal::digital::v2::OutputPin;
 use hal::spidev::{SpiModeFlags, SpidevOptions};
 use hal::sysfs_gpio::Direction;
 use hal::{Delay, Pin, Spidev};
-use mfrc522::{Mfrc522, Initialized, WithNssDelay};
+use mfrc522::chip::Chip;
+use mfrc522::{AuthenticatedSector, Card, Initialized, Mfrc522, WithNssDelay};
 
 // NOTE this requires tweaking permissions and configuring LED0
 //
@@ -72,11 +72,12 @@ fn main() {
     // add a call to `with_nss(pin)`.
     let mut mfrc522 = Mfrc522::new(spi).init().unwrap();
 
-    let vers = mfrc522.version().unwrap();
+    let chip = mfrc522.chip_info().unwrap();
 
-    println!("VERSION: 0x{:x}", vers);
+    println!("CHIP: {:?} (VERSION: 0x{:x})", chip, chip.version());
 
-    assert!(vers == 0x91 || vers == 0x92);
+    assert!(matches!(chip, Chip::Mfrc522V1 | Chip::Mfrc522V2));
+    assert!(mfrc522.self_test().unwrap());
 
     loop {
         const CARD_UID: [u8; 4] = [34, 246, 178, 171];
@@ -84,19 +85,20 @@ fn main() {
 
         match mfrc522.reqa() {
             Ok(atqa) => {
-                if let Ok(uid) = mfrc522.select(&atqa) {
+                if let Ok(card) = mfrc522.select(&atqa) {
+                    let uid = card.uid();
                     println!("UID: {:?}", uid.as_bytes());
 
-                    if uid.as_bytes() == &CARD_UID {
+                    if uid.as_bytes() == CARD_UID {
                         led.off();
                         println!("CARD");
-                    } else if uid.as_bytes() == &TAG_UID {
+                    } else if uid.as_bytes() == TAG_UID {
                         led.on();
                         println!("TAG");
                     }
 
-                    handle_authenticate(&mut mfrc522, &uid, |m| {
-                        match m.mf_read(1, true) {
+                    handle_authenticate(card, |sector| {
+                        match sector.read(1) {
                             Ok(data) => {
                                 println!("read {:?}", data);
                             }
@@ -111,7 +113,7 @@ fn main() {
                         //    0x07, 0x06, 0x05, 0x04,
                         //    0x03, 0x02, 0x01, 0x00,
                         //];
-                        //match m.mf_write(1, buffer) {
+                        //match sector.write(1, buffer) {
                         //    Ok(_) => {
                         //        println!("write success");
                         //    }
@@ -130,26 +132,23 @@ fn main() {
     }
 }
 
-fn handle_authenticate<E, SPI, NSS, D, F>(
-    mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>,
-    uid: &mfrc522::Uid,
-    action: F,
-) where
+fn handle_authenticate<E, SPI, NSS, D, F>(card: Card<SPI, NSS, D>, action: F)
+where
     SPI: SpiTransfer<u8, Error = E> + SpiWrite<u8, Error = E>,
     Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
-    F: FnOnce(&mut Mfrc522<SPI, NSS, D, Initialized>) -> (),
+    F: FnOnce(&mut AuthenticatedSector<SPI, NSS, D>),
 {
     let key = [0xFF; 6];
-    if mfrc522.mf_authenticate(uid, 1, &key).is_ok() {
-        action(mfrc522);
-    } else {
-        println!("Could not authenticate");
-    }
-
-    if mfrc522.hlta().is_err() {
-        println!("Could not halt");
-    }
-    if mfrc522.stop_crypto1().is_err() {
-        println!("Could not disable crypto1");
+    // the PICC is halted when the card or sector is finished (or dropped)
+    match card.authenticate(1, &key) {
+        Ok(mut sector) => {
+            action(&mut sector);
+            if sector.finish().is_err() {
+                println!("Could not halt");
+            }
+        }
+        Err(_) => {
+            println!("Could not authenticate");
+        }
     }
 }
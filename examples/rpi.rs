@@ -19,11 +19,11 @@ use std::io::Write;
 use anyhow::Result;
 use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::spi::{Transfer as SpiTransfer, Write as SpiWrite};
-use embedded_hal::digital::v2::OutputPin;
 use hal::spidev::{SpiModeFlags, SpidevOptions};
 use hal::sysfs_gpio::Direction;
 use hal::{Delay, Pin, Spidev};
-use mfrc522::{Mfrc522, Initialized, WithNssDelay};
+use mfrc522::chip::Chip;
+use mfrc522::{AuthenticatedSector, Card, Initialized, Mfrc522, WithNssDelay};
 
 // NOTE this requires tweaking permissions and configuring LED0
 //
@@ -74,30 +74,32 @@ fn main() -> Result<()> {
     // The `with_nss` method provides a GPIO pin to the driver for software controlled chip select.
     let mut mfrc522 = Mfrc522::new(spi).with_nss(pin).init()?;
 
-    let vers = mfrc522.version()?;
+    let chip = mfrc522.chip_info()?;
 
-    println!("VERSION: 0x{:x}", vers);
+    println!("CHIP: {:?} (VERSION: 0x{:x})", chip, chip.version());
 
-    assert!(vers == 0x91 || vers == 0x92);
+    assert!(matches!(chip, Chip::Mfrc522V1 | Chip::Mfrc522V2));
+    assert!(mfrc522.self_test()?);
 
     loop {
         const CARD_UID: [u8; 4] = [34, 246, 178, 171];
         const TAG_UID: [u8; 4] = [128, 170, 179, 76];
 
         if let Ok(atqa) = mfrc522.reqa() {
-            if let Ok(uid) = mfrc522.select(&atqa) {
+            if let Ok(card) = mfrc522.select(&atqa) {
+                let uid = card.uid();
                 println!("UID: {:?}", uid.as_bytes());
 
-                if uid.as_bytes() == &CARD_UID {
+                if uid.as_bytes() == CARD_UID {
                     led.off();
                     println!("CARD");
-                } else if uid.as_bytes() == &TAG_UID {
+                } else if uid.as_bytes() == TAG_UID {
                     led.on();
                     println!("TAG");
                 }
 
-                handle_authenticate(&mut mfrc522, &uid, |m| {
-                    let data = m.mf_read(1, true)?;
+                handle_authenticate(card, |sector| {
+                    let data = sector.read(1)?;
                     println!("read {:?}", data);
                     Ok(())
                 })
@@ -109,26 +111,24 @@ fn main() -> Result<()> {
     }
 }
 
-fn handle_authenticate<E, SPI, NSS, D, F>(
-    mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>,
-    uid: &mfrc522::Uid,
-    action: F,
-) -> Result<()>
+fn handle_authenticate<E, SPI, NSS, D, F>(card: Card<SPI, NSS, D>, action: F) -> Result<()>
 where
     SPI: SpiTransfer<u8, Error = E> + SpiWrite<u8, Error = E>,
     Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
-    F: FnOnce(&mut Mfrc522<SPI, NSS, D, Initialized>) -> Result<()>,
+    F: FnOnce(&mut AuthenticatedSector<SPI, NSS, D>) -> Result<()>,
     E: std::fmt::Debug + std::marker::Sync + std::marker::Send + 'static,
 {
     // Use *default* key, this should work on new/empty cards
     let key = [0xFF; 6];
-    if mfrc522.mf_authenticate(uid, 1, &key).is_ok() {
-        action(mfrc522)?;
-    } else {
-        println!("Could not authenticate");
+    match card.authenticate(1, &key) {
+        Ok(mut sector) => {
+            action(&mut sector)?;
+            // dropping the sector also halts the PICC, but ignores any errors
+            sector.finish()?;
+        }
+        Err(_) => {
+            println!("Could not authenticate");
+        }
     }
-
-    mfrc522.hlta()?;
-    mfrc522.stop_crypto1()?;
     Ok(())
 }
@@ -0,0 +1,205 @@
+//! Handles to a selected PICC.
+//!
+//! [select](Mfrc522::select) returns a [Card], which borrows the driver for as long as the
+//! PICC is being used. Authenticating a MIFARE Classic sector turns the card into an
+//! [AuthenticatedSector], which only allows access to the blocks of that sector.
+//!
+//! When a handle is dropped, the PICC is sent to the HALT state and the MIFARE Crypto1 unit
+//! is switched off, also on error paths. Use `finish` to get notified of errors
+//! while doing so.
+//!
+//! ```ignore
+//! let atqa = mfrc522.reqa()?;
+//! let card = mfrc522.select(&atqa)?;
+//! let mut sector = card.authenticate(1, &[0xFF; 6])?;
+//! let data = sector.read(1)?;
+//! sector.finish()?;
+//! ```
+
+use crate::error::{Error, Nak};
+use crate::iso_dep::IsoDep;
+use crate::{identify::CardType, spi, Initialized, Mfrc522, MifareKey, Uid, WithNssDelay};
+
+/// A selected PICC
+///
+/// When dropped, the PICC is halted, see [finish](Card::finish).
+pub struct Card<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8> + spi::Write<u8, Error = <SPI as spi::Transfer<u8>>::Error>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    pub(crate) mfrc522: &'a mut Mfrc522<SPI, NSS, D, Initialized>,
+    pub(crate) uid: Uid,
+    /// Is the PICC still to be halted
+    active: bool,
+}
+
+/// A MIFARE Classic sector that has been authenticated
+///
+/// When dropped, the PICC is halted and the MIFARE Crypto1 unit is switched off,
+/// see [finish](AuthenticatedSector::finish).
+pub struct AuthenticatedSector<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8> + spi::Write<u8, Error = <SPI as spi::Transfer<u8>>::Error>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    card: Card<'a, SPI, NSS, D>,
+    /// First block of the sector
+    first_block: u8,
+    /// Number of blocks in the sector
+    blocks: u8,
+    /// Key the sector was authenticated with, to authenticate again when retrying
+    key: MifareKey,
+}
+
+impl<'a, E, SPI, NSS, D> Card<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    pub(crate) fn new(mfrc522: &'a mut Mfrc522<SPI, NSS, D, Initialized>, uid: Uid) -> Self {
+        Card {
+            mfrc522,
+            uid,
+            active: true,
+        }
+    }
+
+    pub fn uid(&self) -> &Uid {
+        &self.uid
+    }
+
+    /// Identify the type of the PICC, see [CardType]
+    ///
+    /// If the PICC had to be sent additional commands, it is woken up and selected again
+    /// afterwards, so it can be used as before (ISO/IEC 14443-4 PICCs need to be sent RATS again).
+    /// Returns [Error::Collision] if a different PICC was selected this way.
+    pub fn identify(&mut self) -> Result<CardType, Error<E>> {
+        self.mfrc522.identify(&self.uid)
+    }
+
+    /// Sends a Request for Answer To Select, to activate the ISO/IEC 14443-4 protocol.
+    ///
+    /// The PICC should indicate compliance with ISO/IEC 14443-4 in its SAK,
+    /// see [Uid::is_compliant].
+    pub fn iso_dep(&mut self) -> Result<IsoDep<'_, SPI, NSS, D>, Error<E>> {
+        self.mfrc522.iso_dep()
+    }
+
+    /// Authenticate the MIFARE Classic sector containing `block` with key A.
+    ///
+    /// If authentication fails, the PICC is halted.
+    pub fn authenticate(
+        self,
+        block: u8,
+        key: &MifareKey,
+    ) -> Result<AuthenticatedSector<'a, SPI, NSS, D>, Error<E>> {
+        self.mfrc522.mf_authenticate(&self.uid, block, key)?;
+
+        // the first 32 sectors contain 4 blocks, the last 8 sectors (MIFARE Classic 4K) 16 blocks
+        let blocks = if block < 128 { 4 } else { 16 };
+        Ok(AuthenticatedSector {
+            card: self,
+            first_block: block & !(blocks - 1),
+            blocks,
+            key: *key,
+        })
+    }
+
+    /// Halt the PICC, returning any error that occurs while doing so
+    pub fn finish(mut self) -> Result<(), Error<E>> {
+        self.active = false;
+        let halted = self.mfrc522.hlta();
+        self.mfrc522.stop_crypto1()?;
+        halted
+    }
+
+    /// Stop using the PICC without halting it, it remains selected
+    pub fn detach(mut self) -> Uid {
+        self.active = false;
+        self.uid.clone()
+    }
+}
+
+impl<'a, E, SPI, NSS, D> AuthenticatedSector<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    pub fn uid(&self) -> &Uid {
+        self.card.uid()
+    }
+
+    /// The blocks that can be accessed
+    pub fn blocks(&self) -> core::ops::RangeInclusive<u8> {
+        self.first_block..=self.trailer_block()
+    }
+
+    /// The block containing the keys and access conditions of the sector
+    pub fn trailer_block(&self) -> u8 {
+        // the last sector of a MIFARE Classic 4K ends at block 255
+        self.first_block + (self.blocks - 1)
+    }
+
+    /// Reads one 16 byte block of the sector, the CRC_A of the response is verified.
+    ///
+    /// Returns [Error::NotAuthenticated] if the block is not part of the sector.
+    /// Transient errors are retried according to the [RetryPolicy](crate::retry::RetryPolicy).
+    pub fn read(&mut self, block: u8) -> Result<[u8; 16], Error<E>> {
+        self.check_block(block)?;
+        let auth = Some((self.first_block, &self.key));
+        self.card.mfrc522.retry(&self.card.uid, auth, |mfrc522| {
+            mfrc522.mf_read(block, true, Nak::classic)
+        })
+    }
+
+    /// Writes one 16 byte block of the sector.
+    ///
+    /// Returns [Error::NotAuthenticated] if the block is not part of the sector.
+    /// Transient errors are retried according to the [RetryPolicy](crate::retry::RetryPolicy).
+    pub fn write(&mut self, block: u8, data: [u8; 16]) -> Result<(), Error<E>> {
+        self.check_block(block)?;
+        let auth = Some((self.first_block, &self.key));
+        self.card.mfrc522.retry(&self.card.uid, auth, |mfrc522| {
+            mfrc522.mf_write(block, data)
+        })
+    }
+
+    /// Authenticate another sector of the same PICC, with key A.
+    ///
+    /// If authentication fails, the PICC is halted.
+    pub fn authenticate(
+        self,
+        block: u8,
+        key: &MifareKey,
+    ) -> Result<AuthenticatedSector<'a, SPI, NSS, D>, Error<E>> {
+        self.card.authenticate(block, key)
+    }
+
+    /// Halt the PICC and switch off the MIFARE Crypto1 unit,
+    /// returning any error that occurs while doing so
+    pub fn finish(self) -> Result<(), Error<E>> {
+        self.card.finish()
+    }
+
+    fn check_block(&self, block: u8) -> Result<(), Error<E>> {
+        if !self.blocks().contains(&block) {
+            return Err(Error::NotAuthenticated);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, SPI, NSS, D> Drop for Card<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8> + spi::Write<u8, Error = <SPI as spi::Transfer<u8>>::Error>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    fn drop(&mut self) {
+        if self.active {
+            // halt first: while Crypto1 is on, HLTA is sent encrypted
+            let _ = self.mfrc522.hlta();
+            let _ = self.mfrc522.stop_crypto1();
+        }
+    }
+}
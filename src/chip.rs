@@ -0,0 +1,152 @@
+//! Identification of the reader IC and its digital self test.
+//!
+//! Besides the two versions of the MFRC522, many cheap reader modules carry a compatible
+//! clone, the FM17522 of Fudan Semiconductor, or a counterfeit that reports an unofficial
+//! version. [chip_info](crate::Mfrc522::chip_info) tells them apart by the VersionReg,
+//! [self_test](crate::Mfrc522::self_test) runs the self test of the datasheet (section 16.1.1),
+//! which compares the output of the CRC coprocessor against the reference of the version.
+//!
+//! ```ignore
+//! match mfrc522.chip_info()? {
+//!     Chip::Mfrc522V1 | Chip::Mfrc522V2 => assert!(mfrc522.self_test()?),
+//!     other => println!("not an NXP MFRC522: {:?}", other),
+//! }
+//! ```
+
+use crate::error::Error;
+use crate::register::*;
+use crate::{spi, Initialized, Mfrc522, WithNssDelay};
+
+/// Number of zero bytes written to the internal buffer before the self test
+const SELF_TEST_ZEROS: usize = 25;
+/// Number of bytes produced by the self test
+pub const SELF_TEST_SIZE: usize = 64;
+
+/// Self test result of the MFRC522 version 1.0
+const REFERENCE_V1: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xC6, 0x37, 0xD5, 0x32, 0xB7, 0x57, 0x5C, 0xC2, 0xD8, 0x7C, 0x4D, 0xD9, 0x70, 0xC7, 0x73,
+    0x10, 0xE6, 0xD2, 0xAA, 0x5E, 0xA1, 0x3E, 0x5A, 0x14, 0xAF, 0x30, 0x61, 0xC9, 0x70, 0xDB, 0x2E,
+    0x64, 0x22, 0x72, 0xB5, 0xBD, 0x65, 0xF4, 0xEC, 0x22, 0xBC, 0xD3, 0x72, 0x35, 0xCD, 0xAA, 0x41,
+    0x1F, 0xA7, 0xF3, 0x53, 0x14, 0xDE, 0x7E, 0x02, 0xD9, 0x0F, 0xB5, 0x5E, 0x25, 0x1D, 0x29, 0x79,
+];
+/// Self test result of the MFRC522 version 2.0
+const REFERENCE_V2: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xEB, 0x66, 0xBA, 0x57, 0xBF, 0x23, 0x95, 0xD0, 0xE3, 0x0D, 0x3D, 0x27, 0x89, 0x5C, 0xDE,
+    0x9D, 0x3B, 0xA7, 0x00, 0x21, 0x5B, 0x89, 0x82, 0x51, 0x3A, 0xEB, 0x02, 0x0C, 0xA5, 0x00, 0x49,
+    0x7C, 0x84, 0x4D, 0xB3, 0xCC, 0xD2, 0x1B, 0x81, 0x5D, 0x48, 0x76, 0xD5, 0x71, 0x61, 0x21, 0xA9,
+    0x86, 0x96, 0x83, 0x38, 0xCF, 0x9D, 0x5B, 0x6D, 0xDC, 0x15, 0xBA, 0x3E, 0x7D, 0x95, 0x3B, 0x2F,
+];
+/// Self test result of the FM17522
+const REFERENCE_FM17522: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xD6, 0x78, 0x8C, 0xE2, 0xAA, 0x0C, 0x18, 0x2A, 0xB8, 0x7A, 0x7F, 0xD3, 0x6A, 0xCF, 0x0B,
+    0xB1, 0x37, 0x63, 0x4B, 0x69, 0xAE, 0x91, 0xC7, 0xC3, 0x97, 0xAE, 0x77, 0xF4, 0x37, 0xD7, 0x9B,
+    0x7C, 0xF5, 0x3C, 0x11, 0x8F, 0x15, 0xC3, 0xD7, 0xC1, 0x5B, 0x00, 0x2A, 0xD0, 0x75, 0xDE, 0x9E,
+    0x51, 0x64, 0xAB, 0x3E, 0xE9, 0x15, 0xB5, 0xAB, 0x56, 0x9A, 0x98, 0x82, 0x26, 0xEA, 0x2A, 0x62,
+];
+
+/// The reader IC, as reported by the VersionReg
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Chip {
+    /// NXP MFRC522 version 1.0 (0x91)
+    Mfrc522V1,
+    /// NXP MFRC522 version 2.0 (0x92)
+    Mfrc522V2,
+    /// Fudan Semiconductor FM17522 (0x88), a compatible clone
+    Fm17522,
+    /// A counterfeit reporting 0x12 or 0xB2, mostly compatible, but without a known self test
+    /// result
+    Counterfeit(u8),
+    /// Another version
+    Unknown(u8),
+}
+
+impl Chip {
+    pub fn from_version(version: u8) -> Self {
+        match version {
+            0x91 => Chip::Mfrc522V1,
+            0x92 => Chip::Mfrc522V2,
+            0x88 => Chip::Fm17522,
+            0x12 | 0xB2 => Chip::Counterfeit(version),
+            _ => Chip::Unknown(version),
+        }
+    }
+
+    /// The value of the VersionReg
+    pub fn version(&self) -> u8 {
+        match *self {
+            Chip::Mfrc522V1 => 0x91,
+            Chip::Mfrc522V2 => 0x92,
+            Chip::Fm17522 => 0x88,
+            Chip::Counterfeit(version) | Chip::Unknown(version) => version,
+        }
+    }
+
+    /// The expected result of the self test, if known
+    pub fn self_test_reference(&self) -> Option<&'static [u8; SELF_TEST_SIZE]> {
+        match self {
+            Chip::Mfrc522V1 => Some(&REFERENCE_V1),
+            Chip::Mfrc522V2 => Some(&REFERENCE_V2),
+            Chip::Fm17522 => Some(&REFERENCE_FM17522),
+            _ => None,
+        }
+    }
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Identify the reader IC by its version
+    pub fn chip_info(&mut self) -> Result<Chip, Error<E>> {
+        self.version().map(Chip::from_version)
+    }
+
+    /// Run the digital self test, returns if the result matches the reference of the version.
+    ///
+    /// The MFRC522 is reset before and after the test and configured again as by
+    /// [init](Mfrc522::init). The test overwrites the internal buffer of the Mem command.
+    /// Returns [Error::Unsupported] if there is no reference for the version, see [Chip].
+    pub fn self_test(&mut self) -> Result<bool, Error<E>> {
+        let chip = self.chip_info()?;
+        let reference = chip.self_test_reference().ok_or(Error::Unsupported)?;
+
+        // 1. soft reset
+        self.reset().map_err(Error::Spi)?;
+        // 2. clear the internal buffer, with 25 zero bytes and the Mem command
+        self.fifo_flush().map_err(Error::Spi)?;
+        self.write_many(Register::FIFODataReg, &[0; SELF_TEST_ZEROS])?;
+        self.command(Command::Mem).map_err(Error::Spi)?;
+        // 3. enable the self test
+        self.write(Register::AutoTestReg, SELF_TEST)
+            .map_err(Error::Spi)?;
+        // 4. a zero byte in the FIFO buffer
+        self.write(Register::FIFODataReg, 0x00)
+            .map_err(Error::Spi)?;
+        // 5. start the self test with the CalcCRC command
+        self.command(Command::CalcCRC).map_err(Error::Spi)?;
+
+        // 6. the result is complete when the FIFO buffer holds 64 bytes
+        let mut result = [0u8; SELF_TEST_SIZE];
+        let mut complete = false;
+        for _ in 0..5000 {
+            if self.read(Register::FIFOLevelReg).map_err(Error::Spi)? as usize >= SELF_TEST_SIZE {
+                self.read_many(Register::FIFODataReg, &mut result)?;
+                complete = true;
+                break;
+            }
+        }
+
+        // leave the self test and restore the configuration
+        self.command(Command::Idle).map_err(Error::Spi)?;
+        self.write(Register::AutoTestReg, 0x00)
+            .map_err(Error::Spi)?;
+        self.reset().map_err(Error::Spi)?;
+        self.configure().map_err(Error::Spi)?;
+
+        let passed = complete && result == *reference;
+        debug!("self test of {:?}: {}", chip, passed);
+        Ok(passed)
+    }
+}
@@ -0,0 +1,161 @@
+//! CRC_A, CRC_B and BCC of ISO/IEC 14443-3, calculated in software.
+//!
+//! CRC_A and CRC_B both use the CRC-16/CCITT polynomial (x^16 + x^12 + x^5 + 1),
+//! processed LSB first. The CRC is transmitted least significant byte first,
+//! which is the order of the returned bytes.
+//!
+//! The table driven functions are faster, the bitwise functions avoid the 512 byte table.
+//!
+//! ```
+//! use mfrc522::crc;
+//!
+//! // HLTA
+//! assert_eq!(crc::crc_a(&[0x50, 0x00]), [0x57, 0xCD]);
+//! assert!(crc::check_crc_a(&[0x50, 0x00, 0x57, 0xCD]));
+//! assert_eq!(crc::bcc(&[0x88, 0x04, 0x2A, 0x1B]), 0xBD);
+//! ```
+
+/// The CCITT polynomial, bit-reversed as the data is processed LSB first
+const POLYNOMIAL: u16 = 0x8408;
+/// Initial value of CRC_A
+const CRC_A_PRESET: u16 = 0x6363;
+/// Initial value of CRC_B
+const CRC_B_PRESET: u16 = 0xFFFF;
+
+/// CRC of each byte value, with an initial value of zero
+const TABLE: [u16; 256] = table();
+
+const fn table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+pub(crate) fn update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc = (crc >> 8) ^ TABLE[((crc ^ byte as u16) & 0xFF) as usize];
+    }
+    crc
+}
+
+fn update_bitwise(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC_A, as used by ISO/IEC 14443 type A
+pub fn crc_a(data: &[u8]) -> [u8; 2] {
+    update(CRC_A_PRESET, data).to_le_bytes()
+}
+
+/// CRC_A, calculated bit by bit
+pub fn crc_a_bitwise(data: &[u8]) -> [u8; 2] {
+    update_bitwise(CRC_A_PRESET, data).to_le_bytes()
+}
+
+/// CRC_B, as used by ISO/IEC 14443 type B
+pub fn crc_b(data: &[u8]) -> [u8; 2] {
+    (!update(CRC_B_PRESET, data)).to_le_bytes()
+}
+
+/// CRC_B, calculated bit by bit
+pub fn crc_b_bitwise(data: &[u8]) -> [u8; 2] {
+    (!update_bitwise(CRC_B_PRESET, data)).to_le_bytes()
+}
+
+/// Does the frame end with the correct CRC_A of the preceding bytes
+pub fn check_crc_a(frame: &[u8]) -> bool {
+    match frame.len().checked_sub(2) {
+        Some(n) => crc_a(&frame[..n]) == frame[n..],
+        None => false,
+    }
+}
+
+/// Does the frame end with the correct CRC_B of the preceding bytes
+pub fn check_crc_b(frame: &[u8]) -> bool {
+    match frame.len().checked_sub(2) {
+        Some(n) => crc_b(&frame[..n]) == frame[n..],
+        None => false,
+    }
+}
+
+/// Block Check Character: the exclusive-or of the UID bytes (or cascade tag)
+/// of one cascade level
+pub fn bcc(data: &[u8]) -> u8 {
+    data.iter().fold(0, |bcc, b| bcc ^ b)
+}
+
+/// Does the last byte contain the correct BCC of the preceding bytes
+pub fn check_bcc(data: &[u8]) -> bool {
+    match data.split_last() {
+        Some((&bcc_byte, uid)) => bcc(uid) == bcc_byte,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // examples of ISO/IEC 14443-3 annex B
+    #[test]
+    fn crc_a_of_iso_14443_3() {
+        // the CRC_A 0x1EA0, transmitted least significant byte first
+        assert_eq!(crc_a(&[0x00, 0x00]), [0xA0, 0x1E]);
+        assert_eq!(crc_a(&[0x12, 0x34]), [0x26, 0xCF]);
+        assert!(check_crc_a(&[0x00, 0x00, 0xA0, 0x1E]));
+        assert!(!check_crc_a(&[0x00, 0x00, 0x1E, 0xA0]));
+        assert!(!check_crc_a(&[0xA0]));
+    }
+
+    #[test]
+    fn crc_b_of_iso_14443_3() {
+        // the CRC_B 0xC6CC, transmitted least significant byte first
+        assert_eq!(crc_b(&[0x00, 0x00, 0x00]), [0xCC, 0xC6]);
+        assert_eq!(crc_b(&[0x0F, 0xAA, 0xFF]), [0xFC, 0xD1]);
+        assert_eq!(crc_b(&[0x0A, 0x12, 0x34, 0x56]), [0x2C, 0xF6]);
+        assert!(check_crc_b(&[0x00, 0x00, 0x00, 0xCC, 0xC6]));
+        // check value of the CRC-16/X-25 catalogue entry
+        assert_eq!(crc_b(b"123456789"), 0x906Eu16.to_le_bytes());
+    }
+
+    #[test]
+    fn table_and_bitwise_agree() {
+        let data: [u8; 256] = core::array::from_fn(|i| i as u8);
+        for n in [0, 1, 2, 17, 256] {
+            assert_eq!(crc_a(&data[..n]), crc_a_bitwise(&data[..n]));
+            assert_eq!(crc_b(&data[..n]), crc_b_bitwise(&data[..n]));
+        }
+    }
+
+    #[test]
+    fn bcc_of_cascade_level() {
+        assert_eq!(bcc(&[0xDE, 0xAD, 0xBE, 0xEF]), 0x22);
+        assert!(check_bcc(&[0xDE, 0xAD, 0xBE, 0xEF, 0x22]));
+        assert!(!check_bcc(&[0xDE, 0xAD, 0xBE, 0xEF, 0x23]));
+        assert!(!check_bcc(&[]));
+    }
+}
@@ -0,0 +1,501 @@
+//! Software implementation of the block ciphers used by contactless cards.
+//!
+//! The MFRC522 only has a hardware implementation of the MIFARE Crypto1 cipher,
+//! other ciphers (DES, 3DES and AES) need to be handled by the host.
+
+use aes::Aes128;
+use cipher::generic_array::GenericArray;
+use cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use des::{Des, TdesEde2, TdesEde3};
+
+/// Largest block size of the supported ciphers (AES)
+pub(crate) const MAX_BLOCK_SIZE: usize = 16;
+
+/// Secret key for one of the supported block ciphers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// Single DES key, 8 bytes (including parity/version bits)
+    Des([u8; 8]),
+    /// Two-key triple DES (2K3DES) key, 16 bytes
+    TDes2([u8; 16]),
+    /// Three-key triple DES (3K3DES) key, 24 bytes
+    TDes3([u8; 24]),
+    /// AES-128 key, 16 bytes
+    Aes([u8; 16]),
+}
+
+impl Key {
+    /// The raw key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::Des(k) => k,
+            Key::TDes2(k) => k,
+            Key::TDes3(k) => k,
+            Key::Aes(k) => k,
+        }
+    }
+
+    /// The block size of the cipher this key is used with
+    pub fn block_size(&self) -> usize {
+        match self {
+            Key::Aes(_) => 16,
+            _ => 8,
+        }
+    }
+
+    /// Build a session key from the random numbers exchanged during a mutual
+    /// authentication, following the scheme used by MIFARE DESFire and Ultralight C.
+    ///
+    /// `rnd_a` and `rnd_b` must be as long as the random numbers used with this key type:
+    /// 8 bytes for (2K3)DES, 16 bytes for 3K3DES and AES.
+    pub(crate) fn session_key(&self, rnd_a: &[u8], rnd_b: &[u8]) -> Key {
+        let mut buf = [0u8; 24];
+        buf[0..4].copy_from_slice(&rnd_a[0..4]);
+        buf[4..8].copy_from_slice(&rnd_b[0..4]);
+        match self {
+            Key::Des(_) => Key::Des(buf[..8].try_into().unwrap()),
+            Key::TDes2(k) if k[..8] == k[8..] => Key::Des(buf[..8].try_into().unwrap()),
+            Key::TDes2(_) => {
+                buf[8..12].copy_from_slice(&rnd_a[4..8]);
+                buf[12..16].copy_from_slice(&rnd_b[4..8]);
+                Key::TDes2(buf[..16].try_into().unwrap())
+            }
+            Key::TDes3(_) => {
+                buf[8..12].copy_from_slice(&rnd_a[6..10]);
+                buf[12..16].copy_from_slice(&rnd_b[6..10]);
+                buf[16..20].copy_from_slice(&rnd_a[12..16]);
+                buf[20..24].copy_from_slice(&rnd_b[12..16]);
+                Key::TDes3(buf)
+            }
+            Key::Aes(_) => {
+                buf[8..12].copy_from_slice(&rnd_a[12..16]);
+                buf[12..16].copy_from_slice(&rnd_b[12..16]);
+                Key::Aes(buf[..16].try_into().unwrap())
+            }
+        }
+    }
+}
+
+// There is no allocator to box the key schedules, the size is accepted
+#[allow(clippy::large_enum_variant)]
+enum Inner {
+    Des(Des),
+    TDes2(TdesEde2),
+    TDes3(TdesEde3),
+    Aes(Aes128),
+}
+
+/// Block cipher instance with the modes of operation needed by the card protocols
+pub(crate) struct Cipher {
+    inner: Inner,
+}
+
+impl Cipher {
+    pub fn new(key: &Key) -> Self {
+        let inner = match key {
+            Key::Des(k) => Inner::Des(Des::new(GenericArray::from_slice(k))),
+            Key::TDes2(k) => Inner::TDes2(TdesEde2::new(GenericArray::from_slice(k))),
+            Key::TDes3(k) => Inner::TDes3(TdesEde3::new(GenericArray::from_slice(k))),
+            Key::Aes(k) => Inner::Aes(Aes128::new(GenericArray::from_slice(k))),
+        };
+        Cipher { inner }
+    }
+
+    pub fn block_size(&self) -> usize {
+        match self.inner {
+            Inner::Aes(_) => 16,
+            _ => 8,
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        match &self.inner {
+            Inner::Des(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::TDes2(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::TDes3(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::Aes(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+        }
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8]) {
+        match &self.inner {
+            Inner::Des(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::TDes2(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::TDes3(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Inner::Aes(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+        }
+    }
+
+    /// Encrypt `data` in CBC mode.
+    ///
+    /// The length of `data` must be a multiple of the block size.
+    /// On return, `iv` contains the last ciphertext block, so it can be used to chain
+    /// the next operation.
+    pub fn encrypt_cbc(&self, iv: &mut [u8], data: &mut [u8]) {
+        let bs = self.block_size();
+        for block in data.chunks_exact_mut(bs) {
+            xor(block, &iv[..bs]);
+            self.encrypt_block(block);
+            iv[..bs].copy_from_slice(block);
+        }
+    }
+
+    /// Decrypt `data` in CBC mode.
+    ///
+    /// The length of `data` must be a multiple of the block size.
+    /// On return, `iv` contains the last ciphertext block, so it can be used to chain
+    /// the next operation.
+    pub fn decrypt_cbc(&self, iv: &mut [u8], data: &mut [u8]) {
+        let bs = self.block_size();
+        let mut ciphertext = [0u8; MAX_BLOCK_SIZE];
+        for block in data.chunks_exact_mut(bs) {
+            ciphertext[..bs].copy_from_slice(block);
+            self.decrypt_block(block);
+            xor(block, &iv[..bs]);
+            iv[..bs].copy_from_slice(&ciphertext[..bs]);
+        }
+    }
+
+    /// *Send mode* used by the legacy MIFARE DESFire (D40) protocol:
+    /// the PCD always deciphers, also for the data it sends to the PICC,
+    /// so the PICC only needs to implement the encryption direction.
+    pub fn decipher_send(&self, data: &mut [u8]) {
+        let bs = self.block_size();
+        let mut previous = [0u8; MAX_BLOCK_SIZE];
+        for block in data.chunks_exact_mut(bs) {
+            xor(block, &previous[..bs]);
+            self.decrypt_block(block);
+            previous[..bs].copy_from_slice(block);
+        }
+    }
+
+    /// Calculate the CMAC (NIST SP 800-38B) of `data`, starting from the given `iv`.
+    ///
+    /// Only the first [block_size](Cipher::block_size) bytes of the result are valid.
+    pub fn cmac(&self, iv: &[u8], data: &[u8]) -> [u8; MAX_BLOCK_SIZE] {
+        let bs = self.block_size();
+        let (k1, k2) = self.cmac_subkeys();
+
+        let mut mac = [0u8; MAX_BLOCK_SIZE];
+        mac[..bs].copy_from_slice(&iv[..bs]);
+
+        let full_blocks = if data.is_empty() {
+            0
+        } else {
+            (data.len() - 1) / bs
+        };
+        for block in data[..full_blocks * bs].chunks_exact(bs) {
+            xor(&mut mac[..bs], block);
+            self.encrypt_block(&mut mac[..bs]);
+        }
+
+        let rest = &data[full_blocks * bs..];
+        let mut last = [0u8; MAX_BLOCK_SIZE];
+        last[..rest.len()].copy_from_slice(rest);
+        if rest.len() == bs {
+            xor(&mut last[..bs], &k1[..bs]);
+        } else {
+            last[rest.len()] = 0x80;
+            xor(&mut last[..bs], &k2[..bs]);
+        }
+        xor(&mut mac[..bs], &last[..bs]);
+        self.encrypt_block(&mut mac[..bs]);
+
+        mac
+    }
+
+    pub fn cmac_subkeys(&self) -> ([u8; MAX_BLOCK_SIZE], [u8; MAX_BLOCK_SIZE]) {
+        let bs = self.block_size();
+        let rb = if bs == 16 { 0x87 } else { 0x1B };
+
+        let mut l = [0u8; MAX_BLOCK_SIZE];
+        self.encrypt_block(&mut l[..bs]);
+        let k1 = shift_left(&l[..bs], rb);
+        let k2 = shift_left(&k1[..bs], rb);
+        (k1, k2)
+    }
+}
+
+/// Shift a block one bit to the left, applying the CMAC reduction constant `rb`
+/// if the most significant bit was set.
+fn shift_left(block: &[u8], rb: u8) -> [u8; MAX_BLOCK_SIZE] {
+    let mut out = [0u8; MAX_BLOCK_SIZE];
+    let bs = block.len();
+    for i in 0..bs {
+        let carry = if i + 1 < bs { block[i + 1] >> 7 } else { 0 };
+        out[i] = (block[i] << 1) | carry;
+    }
+    if block[0] & 0x80 != 0 {
+        out[bs - 1] ^= rb;
+    }
+    out
+}
+
+pub(crate) fn xor(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+/// Rotate a random number one byte to the left, as done during mutual authentication
+pub(crate) fn rotate_left(dst: &mut [u8], src: &[u8]) {
+    let n = src.len();
+    dst[..n - 1].copy_from_slice(&src[1..]);
+    dst[n - 1] = src[0];
+}
+
+/// CRC32 as used by MIFARE DESFire EV1 secure messaging
+/// (IEEE 802.3 polynomial, without the final inversion)
+pub(crate) fn crc32(data: &[u8]) -> [u8; 4] {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message of the examples in NIST SP 800-38A and SP 800-38B
+    const MESSAGE: [u8; 64] = [
+        0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93, 0x17,
+        0x2A, 0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC, 0x45, 0xAF,
+        0x8E, 0x51, 0x30, 0xC8, 0x1C, 0x46, 0xA3, 0x5C, 0xE4, 0x11, 0xE5, 0xFB, 0xC1, 0x19, 0x1A,
+        0x0A, 0x52, 0xEF, 0xF6, 0x9F, 0x24, 0x45, 0xDF, 0x4F, 0x9B, 0x17, 0xAD, 0x2B, 0x41, 0x7B,
+        0xE6, 0x6C, 0x37, 0x10,
+    ];
+    /// AES-128 key of NIST SP 800-38A and SP 800-38B
+    const AES_KEY: [u8; 16] = [
+        0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F,
+        0x3C,
+    ];
+    /// 2K3DES key and data of FIPS 81 (extended to two keys)
+    const TDES2_KEY: [u8; 16] = [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0xFE, 0xDC, 0xBA, 0x98, 0x76, 0x54, 0x32,
+        0x10,
+    ];
+    const TDES_DATA: &[u8; 24] = b"Now is the time for all ";
+
+    #[test]
+    fn des() {
+        let cipher = Cipher::new(&Key::Des([0x13, 0x34, 0x57, 0x79, 0x9B, 0xBC, 0xDF, 0xF1]));
+        let mut block = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        cipher.encrypt_block(&mut block);
+        assert_eq!(block, [0x85, 0xE8, 0x13, 0x54, 0x0F, 0x0A, 0xB4, 0x05]);
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block, [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]);
+    }
+
+    // NIST SP 800-67, appendix B
+    #[test]
+    fn tdes3() {
+        let key = [
+            0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD,
+            0xEF, 0x01, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x01, 0x23,
+        ];
+        let cipher = Cipher::new(&Key::TDes3(key));
+        let mut data = *b"The qufck brown fox jump";
+        for block in data.chunks_exact_mut(8) {
+            cipher.encrypt_block(block);
+        }
+        assert_eq!(
+            data,
+            [
+                0xA8, 0x26, 0xFD, 0x8C, 0xE5, 0x3B, 0x85, 0x5F, 0xCC, 0xE2, 0x1C, 0x81, 0x12, 0x25,
+                0x6F, 0xE6, 0x68, 0xD5, 0xC0, 0x5D, 0xD9, 0xB6, 0xB9, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn tdes2_cbc() {
+        let cipher = Cipher::new(&Key::TDes2(TDES2_KEY));
+        let mut iv = [0x12, 0x34, 0x56, 0x78, 0x90, 0xAB, 0xCD, 0xEF];
+        let mut data = *TDES_DATA;
+        cipher.encrypt_cbc(&mut iv, &mut data);
+        let ciphertext = [
+            0xF8, 0x5D, 0x4A, 0xB9, 0x20, 0x66, 0x78, 0x9E, 0x1D, 0x04, 0x30, 0x67, 0x1F, 0x28,
+            0xAE, 0x7A, 0xB9, 0x62, 0x7D, 0x35, 0x38, 0x5D, 0x2E, 0x24,
+        ];
+        assert_eq!(data, ciphertext);
+        assert_eq!(iv, ciphertext[16..]);
+
+        let mut iv = [0x12, 0x34, 0x56, 0x78, 0x90, 0xAB, 0xCD, 0xEF];
+        cipher.decrypt_cbc(&mut iv, &mut data);
+        assert_eq!(data, *TDES_DATA);
+        assert_eq!(iv, ciphertext[16..]);
+    }
+
+    #[test]
+    fn tdes2_decipher_send() {
+        let cipher = Cipher::new(&Key::TDes2(TDES2_KEY));
+        let mut data = *TDES_DATA;
+        cipher.decipher_send(&mut data);
+        assert_eq!(
+            data,
+            [
+                0x0D, 0x61, 0xE1, 0x72, 0x0C, 0xD5, 0x64, 0x68, 0x11, 0xC3, 0xC2, 0x3A, 0x36, 0x13,
+                0x05, 0x28, 0x2A, 0xA5, 0xCB, 0x9A, 0xD4, 0x19, 0x77, 0x3F
+            ]
+        );
+    }
+
+    // NIST SP 800-38A, F.2.1 and F.2.2
+    #[test]
+    fn aes_cbc() {
+        let cipher = Cipher::new(&Key::Aes(AES_KEY));
+        let mut iv = [0u8; MAX_BLOCK_SIZE];
+        iv.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        let mut data: [u8; 32] = MESSAGE[..32].try_into().unwrap();
+        cipher.encrypt_cbc(&mut iv, &mut data);
+        assert_eq!(
+            data,
+            [
+                0x76, 0x49, 0xAB, 0xAC, 0x81, 0x19, 0xB2, 0x46, 0xCE, 0xE9, 0x8E, 0x9B, 0x12, 0xE9,
+                0x19, 0x7D, 0x50, 0x86, 0xCB, 0x9B, 0x50, 0x72, 0x19, 0xEE, 0x95, 0xDB, 0x11, 0x3A,
+                0x91, 0x76, 0x78, 0xB2
+            ]
+        );
+
+        iv.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        cipher.decrypt_cbc(&mut iv, &mut data);
+        assert_eq!(data, MESSAGE[..32]);
+    }
+
+    // NIST SP 800-38B, D.1
+    #[test]
+    fn aes_cmac() {
+        let cipher = Cipher::new(&Key::Aes(AES_KEY));
+        let (k1, k2) = cipher.cmac_subkeys();
+        assert_eq!(
+            k1,
+            [
+                0xFB, 0xEE, 0xD6, 0x18, 0x35, 0x71, 0x33, 0x66, 0x7C, 0x85, 0xE0, 0x8F, 0x72, 0x36,
+                0xA8, 0xDE
+            ]
+        );
+        assert_eq!(
+            k2,
+            [
+                0xF7, 0xDD, 0xAC, 0x30, 0x6A, 0xE2, 0x66, 0xCC, 0xF9, 0x0B, 0xC1, 0x1E, 0xE4, 0x6D,
+                0x51, 0x3B
+            ]
+        );
+
+        let iv = [0u8; MAX_BLOCK_SIZE];
+        for (len, mac) in [
+            (
+                0,
+                [
+                    0xBB, 0x1D, 0x69, 0x29, 0xE9, 0x59, 0x37, 0x28, 0x7F, 0xA3, 0x7D, 0x12, 0x9B,
+                    0x75, 0x67, 0x46,
+                ],
+            ),
+            (
+                16,
+                [
+                    0x07, 0x0A, 0x16, 0xB4, 0x6B, 0x4D, 0x41, 0x44, 0xF7, 0x9B, 0xDD, 0x9D, 0xD0,
+                    0x4A, 0x28, 0x7C,
+                ],
+            ),
+            (
+                40,
+                [
+                    0xDF, 0xA6, 0x67, 0x47, 0xDE, 0x9A, 0xE6, 0x30, 0x30, 0xCA, 0x32, 0x61, 0x14,
+                    0x97, 0xC8, 0x27,
+                ],
+            ),
+            (
+                64,
+                [
+                    0x51, 0xF0, 0xBE, 0xBF, 0x7E, 0x3B, 0x9D, 0x92, 0xFC, 0x49, 0x74, 0x17, 0x79,
+                    0x36, 0x3C, 0xFE,
+                ],
+            ),
+        ] {
+            assert_eq!(cipher.cmac(&iv, &MESSAGE[..len]), mac);
+        }
+    }
+
+    // NIST SP 800-38B, D.2
+    #[test]
+    fn tdes3_cmac() {
+        let key = [
+            0x8A, 0xA8, 0x3B, 0xF8, 0xCB, 0xDA, 0x10, 0x62, 0x0B, 0xC1, 0xBF, 0x19, 0xFB, 0xB6,
+            0xCD, 0x58, 0xBC, 0x31, 0x3D, 0x4A, 0x37, 0x1C, 0xA8, 0xB5,
+        ];
+        let cipher = Cipher::new(&Key::TDes3(key));
+        let iv = [0u8; MAX_BLOCK_SIZE];
+        assert_eq!(
+            cipher.cmac(&iv, &[])[..8],
+            [0xB7, 0xA6, 0x88, 0xE1, 0x22, 0xFF, 0xAF, 0x95]
+        );
+        assert_eq!(
+            cipher.cmac(&iv, &MESSAGE[..20])[..8],
+            [0x74, 0x3D, 0xDB, 0xE0, 0xCE, 0x2D, 0xC2, 0xED]
+        );
+    }
+
+    #[test]
+    fn crc32_without_final_inversion() {
+        // the CRC-32 check value is 0xCBF43926
+        assert_eq!(crc32(b"123456789"), (!0xCBF4_3926u32).to_le_bytes());
+        assert_eq!(crc32(&[]), [0xFF; 4]);
+    }
+
+    #[test]
+    fn session_keys() {
+        let rnd_a: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let rnd_b: [u8; 16] = core::array::from_fn(|i| 0x10 + i as u8);
+
+        let des = Key::Des([0; 8]).session_key(&rnd_a[..8], &rnd_b[..8]);
+        assert_eq!(
+            des,
+            Key::Des([0x00, 0x01, 0x02, 0x03, 0x10, 0x11, 0x12, 0x13])
+        );
+        // 2K3DES with both halves equal is single DES
+        let des = Key::TDes2([0; 16]).session_key(&rnd_a[..8], &rnd_b[..8]);
+        assert_eq!(
+            des,
+            Key::Des([0x00, 0x01, 0x02, 0x03, 0x10, 0x11, 0x12, 0x13])
+        );
+
+        let mut key = [0u8; 16];
+        key[8] = 1;
+        let tdes2 = Key::TDes2(key).session_key(&rnd_a[..8], &rnd_b[..8]);
+        assert_eq!(
+            tdes2,
+            Key::TDes2([
+                0x00, 0x01, 0x02, 0x03, 0x10, 0x11, 0x12, 0x13, 0x04, 0x05, 0x06, 0x07, 0x14, 0x15,
+                0x16, 0x17
+            ])
+        );
+
+        let tdes3 = Key::TDes3([0; 24]).session_key(&rnd_a, &rnd_b);
+        assert_eq!(
+            tdes3,
+            Key::TDes3([
+                0x00, 0x01, 0x02, 0x03, 0x10, 0x11, 0x12, 0x13, 0x06, 0x07, 0x08, 0x09, 0x16, 0x17,
+                0x18, 0x19, 0x0C, 0x0D, 0x0E, 0x0F, 0x1C, 0x1D, 0x1E, 0x1F
+            ])
+        );
+
+        let aes = Key::Aes([0; 16]).session_key(&rnd_a, &rnd_b);
+        assert_eq!(
+            aes,
+            Key::Aes([
+                0x00, 0x01, 0x02, 0x03, 0x10, 0x11, 0x12, 0x13, 0x0C, 0x0D, 0x0E, 0x0F, 0x1C, 0x1D,
+                0x1E, 0x1F
+            ])
+        );
+    }
+}
@@ -0,0 +1,716 @@
+//! MIFARE DESFire EV1/EV2 authentication and secure messaging.
+//!
+//! The native DESFire commands are wrapped in ISO/IEC 7816-4 APDUs and exchanged
+//! over [ISO-DEP](crate::iso_dep).
+//!
+//! Three authentication schemes are supported:
+//! - *legacy* (`0x0A`): DES and 2K3DES keys, compatible with MIFARE DESFire (D40)
+//! - *ISO* (`0x1A`): DES, 2K3DES and 3K3DES keys
+//! - *AES* (`0xAA`): AES-128 keys
+//!
+//! After a successful authentication, data is exchanged in one of the communication
+//! modes defined by [CommMode], using the session key derived during authentication.
+//!
+//! ```ignore
+//! let mut desfire = Desfire::new(card.iso_dep()?);
+//! desfire.select_application([0x01, 0x00, 0x00])?;
+//! desfire.authenticate(0, &Key::Aes([0u8; 16]), &mut rng)?;
+//! let n = desfire.read_data(1, 0, &mut buffer, CommMode::Enciphered)?;
+//! ```
+
+// `usize::is_multiple_of` needs Rust 1.87
+#![allow(clippy::manual_is_multiple_of)]
+
+use heapless::Vec;
+use rand_core::RngCore;
+
+use crate::crc;
+use crate::crypto::{self, Cipher, Key, MAX_BLOCK_SIZE};
+use crate::error::{Context, Error};
+use crate::iso_dep::IsoDep;
+use crate::{spi, Initialized, Mfrc522, WithNssDelay};
+
+/// Maximum size of a command or response, including the secure messaging overhead
+const BUFFER_SIZE: usize = 288;
+/// Maximum size of a single wrapped command or response APDU
+const APDU_SIZE: usize = 64;
+/// Maximum number of data bytes sent in a single frame,
+/// longer commands are continued in additional frames
+const FRAME_DATA_SIZE: usize = 52;
+
+/// CLA byte used to wrap native commands in ISO/IEC 7816-4 APDUs
+const WRAP_CLA: u8 = 0x90;
+/// SW1 of the response to a wrapped native command
+const WRAP_SW1: u8 = 0x91;
+
+const AUTHENTICATE_LEGACY: u8 = 0x0A;
+const AUTHENTICATE_ISO: u8 = 0x1A;
+const AUTHENTICATE_AES: u8 = 0xAA;
+const CHANGE_KEY: u8 = 0xC4;
+const GET_VERSION: u8 = 0x60;
+const SELECT_APPLICATION: u8 = 0x5A;
+const READ_DATA: u8 = 0xBD;
+const WRITE_DATA: u8 = 0x3D;
+
+const OPERATION_OK: u8 = 0x00;
+const ADDITIONAL_FRAME: u8 = 0xAF;
+
+/// Length of the (truncated) CMAC used by EV1 secure messaging
+const CMAC_SIZE: usize = 8;
+/// Length of the MAC used by legacy secure messaging
+const LEGACY_MAC_SIZE: usize = 4;
+
+/// Communication mode, as configured in the settings of a file or application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommMode {
+    /// Plain communication
+    Plain,
+    /// Plain communication secured by a MAC
+    Mac,
+    /// Fully enciphered communication
+    Enciphered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    /// Authenticated with `0x0A`, secure messaging as done by MIFARE DESFire (D40)
+    Legacy,
+    /// Authenticated with `0x1A` or `0xAA`, secure messaging using CMAC and CRC32
+    Ev1,
+}
+
+struct Session {
+    cipher: Cipher,
+    scheme: Scheme,
+    /// Initialization vector, chained over all commands and responses of the session
+    iv: [u8; MAX_BLOCK_SIZE],
+    /// The key number used to authenticate
+    key_no: u8,
+}
+
+/// MIFARE DESFire PICC
+pub struct Desfire<'a, SPI, NSS, D> {
+    iso_dep: IsoDep<'a, SPI, NSS, D>,
+    session: Option<Session>,
+}
+
+impl<'a, E, SPI, NSS, D> Desfire<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Use an activated ISO-DEP PICC as MIFARE DESFire
+    pub fn new(iso_dep: IsoDep<'a, SPI, NSS, D>) -> Self {
+        Desfire {
+            iso_dep,
+            session: None,
+        }
+    }
+
+    /// Release the underlying ISO-DEP PICC
+    pub fn release(self) -> IsoDep<'a, SPI, NSS, D> {
+        self.iso_dep
+    }
+
+    /// Is there an authenticated session with the PICC
+    pub fn is_authenticated(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Returns the manufacturing related data of the PICC:
+    /// hardware info (7 bytes), software info (7 bytes) and production info (14 bytes).
+    pub fn get_version(&mut self) -> Result<[u8; 28], Error<E>> {
+        let mut version = [0u8; 28];
+        let n = self.command(
+            GET_VERSION,
+            &[],
+            &[],
+            CommMode::Plain,
+            CommMode::Plain,
+            &mut version,
+        )?;
+        if n != version.len() {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+        Ok(version)
+    }
+
+    /// Select the application with the given AID (least significant byte first),
+    /// use `[0, 0, 0]` to select the PICC level.
+    ///
+    /// This ends the current authenticated session.
+    pub fn select_application(&mut self, aid: [u8; 3]) -> Result<(), Error<E>> {
+        self.session = None;
+        self.command(
+            SELECT_APPLICATION,
+            &aid,
+            &[],
+            CommMode::Plain,
+            CommMode::Plain,
+            &mut [],
+        )?;
+        Ok(())
+    }
+
+    /// Mutual authentication using the given key.
+    ///
+    /// The authentication scheme is selected based on the key type:
+    /// legacy for DES and 2K3DES keys, ISO for 3K3DES keys and AES for AES keys.
+    ///
+    /// The random number generator is used for the random challenge RndA.
+    pub fn authenticate<R: RngCore>(
+        &mut self,
+        key_no: u8,
+        key: &Key,
+        rng: &mut R,
+    ) -> Result<(), Error<E>> {
+        let cmd = match key {
+            Key::Des(_) | Key::TDes2(_) => AUTHENTICATE_LEGACY,
+            Key::TDes3(_) => AUTHENTICATE_ISO,
+            Key::Aes(_) => AUTHENTICATE_AES,
+        };
+        self.authenticate_with(cmd, key_no, key, rng)
+    }
+
+    /// Mutual authentication using the ISO scheme (`0x1A`), also for DES and 2K3DES keys.
+    ///
+    /// AES keys always use the AES scheme.
+    pub fn authenticate_iso<R: RngCore>(
+        &mut self,
+        key_no: u8,
+        key: &Key,
+        rng: &mut R,
+    ) -> Result<(), Error<E>> {
+        let cmd = match key {
+            Key::Aes(_) => AUTHENTICATE_AES,
+            _ => AUTHENTICATE_ISO,
+        };
+        self.authenticate_with(cmd, key_no, key, rng)
+    }
+
+    /// Change a key of the currently selected application (or the PICC master key).
+    ///
+    /// `old_key` is only used when changing a key other than the one used to authenticate.
+    /// The `key_version` is only used for AES keys, the version of (3)DES keys is
+    /// stored in the parity bits of the key.
+    ///
+    /// When changing the PICC master key, the key type is encoded in `key_no`
+    /// (`0x40` for 3K3DES, `0x80` for AES).
+    ///
+    /// Changing the key used to authenticate ends the authenticated session.
+    pub fn change_key(
+        &mut self,
+        key_no: u8,
+        new_key: &Key,
+        key_version: u8,
+        old_key: &Key,
+    ) -> Result<(), Error<E>> {
+        let session = self.session.as_mut().ok_or(Error::NotAuthenticated)?;
+        let same_key = key_no & 0x0F == session.key_no & 0x0F;
+
+        let new = change_key_bytes(new_key);
+        let mut cryptogram = Vec::<u8, 48>::new();
+        cryptogram.extend_from_slice(&new).unwrap();
+        if !same_key {
+            crypto::xor(&mut cryptogram, &change_key_bytes(old_key));
+        }
+
+        match session.scheme {
+            Scheme::Ev1 => {
+                if let Key::Aes(_) = new_key {
+                    cryptogram.push(key_version).unwrap();
+                }
+                let mut header = Vec::<u8, 50>::new();
+                header.extend_from_slice(&[CHANGE_KEY, key_no]).unwrap();
+                header.extend_from_slice(&cryptogram).unwrap();
+                cryptogram
+                    .extend_from_slice(&crypto::crc32(&header))
+                    .unwrap();
+                if !same_key {
+                    cryptogram.extend_from_slice(&crypto::crc32(&new)).unwrap();
+                }
+                pad(&mut cryptogram, 0, session.cipher.block_size())?;
+                session.cipher.encrypt_cbc(&mut session.iv, &mut cryptogram);
+            }
+            Scheme::Legacy => {
+                let crc = crc::crc_a(&cryptogram);
+                cryptogram.extend_from_slice(&crc).unwrap();
+                if !same_key {
+                    cryptogram.extend_from_slice(&crc::crc_a(&new)).unwrap();
+                }
+                pad(&mut cryptogram, 0, session.cipher.block_size())?;
+                session.cipher.decipher_send(&mut cryptogram);
+            }
+        }
+
+        let mut payload = Vec::<u8, BUFFER_SIZE>::new();
+        payload.push(key_no).map_err(|_| Error::NoRoom)?;
+        payload
+            .extend_from_slice(&cryptogram)
+            .map_err(|_| Error::NoRoom)?;
+
+        let mut rx = Vec::<u8, BUFFER_SIZE>::new();
+        let status = self.transceive(CHANGE_KEY, &payload, &mut rx)?;
+        if status != OPERATION_OK {
+            self.session = None;
+            return Err(Error::Status(u16::from_be_bytes([WRAP_SW1, status])));
+        }
+
+        if same_key {
+            // the PICC does not send a MAC, the session has ended
+            self.session = None;
+            Ok(())
+        } else {
+            self.unwrap_response(&mut rx, status, CommMode::Plain)?;
+            Ok(())
+        }
+    }
+
+    /// Read data from a standard or backup data file.
+    ///
+    /// Reads `data.len()` bytes starting at `offset`,
+    /// the communication mode should match the settings of the file.
+    pub fn read_data(
+        &mut self,
+        file_no: u8,
+        offset: u32,
+        data: &mut [u8],
+        mode: CommMode,
+    ) -> Result<usize, Error<E>> {
+        let header = file_header(file_no, offset, data.len())?;
+        self.command(READ_DATA, &header, &[], CommMode::Plain, mode, data)
+    }
+
+    /// Write data to a standard or backup data file.
+    ///
+    /// The communication mode should match the settings of the file.
+    pub fn write_data(
+        &mut self,
+        file_no: u8,
+        offset: u32,
+        data: &[u8],
+        mode: CommMode,
+    ) -> Result<(), Error<E>> {
+        let header = file_header(file_no, offset, data.len())?;
+        self.command(WRITE_DATA, &header, data, mode, CommMode::Plain, &mut [])?;
+        Ok(())
+    }
+
+    /// Execute a native command.
+    ///
+    /// The `header` is always sent in plain, the `data` is secured according to `tx_mode`.
+    /// The response is verified and/or deciphered according to `rx_mode`
+    /// and stored in `rx`, the number of bytes of the response is returned.
+    ///
+    /// Communication modes are only applied when there is an authenticated session,
+    /// otherwise all communication is plain.
+    pub fn command(
+        &mut self,
+        cmd: u8,
+        header: &[u8],
+        data: &[u8],
+        tx_mode: CommMode,
+        rx_mode: CommMode,
+        rx: &mut [u8],
+    ) -> Result<usize, Error<E>> {
+        let mut payload = Vec::<u8, BUFFER_SIZE>::new();
+        payload
+            .extend_from_slice(header)
+            .map_err(|_| Error::NoRoom)?;
+        payload.extend_from_slice(data).map_err(|_| Error::NoRoom)?;
+        self.wrap_command(cmd, header.len(), &mut payload, tx_mode)?;
+
+        let mut response = Vec::<u8, BUFFER_SIZE>::new();
+        let status = self.transceive(cmd, &payload, &mut response)?;
+        if status != OPERATION_OK {
+            // the PICC ends the authenticated session on errors
+            self.session = None;
+            return Err(Error::Status(u16::from_be_bytes([WRAP_SW1, status])));
+        }
+
+        let n = self.unwrap_response(&mut response, status, rx_mode)?;
+        rx.get_mut(..n)
+            .ok_or(Error::NoRoom)?
+            .copy_from_slice(&response[..n]);
+        Ok(n)
+    }
+
+    fn authenticate_with<R: RngCore>(
+        &mut self,
+        cmd: u8,
+        key_no: u8,
+        key: &Key,
+        rng: &mut R,
+    ) -> Result<(), Error<E>> {
+        self.session = None;
+
+        let cipher = Cipher::new(key);
+        let legacy = cmd == AUTHENTICATE_LEGACY;
+        let rnd_len = match key {
+            Key::TDes3(_) | Key::Aes(_) => 16,
+            Key::Des(_) | Key::TDes2(_) => 8,
+        };
+
+        // The PICC sends the encrypted random number RndB
+        let mut rx = Vec::<u8, APDU_SIZE>::new();
+        let status = self.exchange(cmd, &[key_no], &mut rx)?;
+        if status != ADDITIONAL_FRAME {
+            return Err(Error::Status(u16::from_be_bytes([WRAP_SW1, status])));
+        }
+        if rx.len() != rnd_len {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+
+        let mut iv = [0u8; MAX_BLOCK_SIZE];
+        let mut rnd_b = [0u8; 16];
+        rnd_b[..rnd_len].copy_from_slice(&rx);
+        cipher.decrypt_cbc(&mut iv, &mut rnd_b[..rnd_len]);
+
+        // Respond with RndA + RndB (rotated left by 1 byte)
+        let mut rnd_a = [0u8; 16];
+        rng.fill_bytes(&mut rnd_a[..rnd_len]);
+        let mut token = [0u8; 32];
+        token[..rnd_len].copy_from_slice(&rnd_a[..rnd_len]);
+        crypto::rotate_left(&mut token[rnd_len..2 * rnd_len], &rnd_b[..rnd_len]);
+        if legacy {
+            cipher.decipher_send(&mut token[..2 * rnd_len]);
+        } else {
+            cipher.encrypt_cbc(&mut iv, &mut token[..2 * rnd_len]);
+        }
+
+        // The PICC proves it knows the key by returning RndA (rotated left by 1 byte)
+        rx.clear();
+        let status = self.exchange(ADDITIONAL_FRAME, &token[..2 * rnd_len], &mut rx)?;
+        if status != OPERATION_OK {
+            return Err(Error::Status(u16::from_be_bytes([WRAP_SW1, status])));
+        }
+        if rx.len() != rnd_len {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+
+        let mut response = [0u8; 16];
+        response[..rnd_len].copy_from_slice(&rx);
+        if legacy {
+            iv = [0u8; MAX_BLOCK_SIZE];
+        }
+        cipher.decrypt_cbc(&mut iv, &mut response[..rnd_len]);
+
+        let mut expected = [0u8; 16];
+        crypto::rotate_left(&mut expected[..rnd_len], &rnd_a[..rnd_len]);
+        if response[..rnd_len] != expected[..rnd_len] {
+            return Err(Error::Integrity);
+        }
+
+        let session_key = key.session_key(&rnd_a[..rnd_len], &rnd_b[..rnd_len]);
+        self.session = Some(Session {
+            cipher: Cipher::new(&session_key),
+            scheme: if legacy { Scheme::Legacy } else { Scheme::Ev1 },
+            iv: [0u8; MAX_BLOCK_SIZE],
+            key_no,
+        });
+        Ok(())
+    }
+
+    /// Apply secure messaging to a command payload (header + data)
+    fn wrap_command(
+        &mut self,
+        cmd: u8,
+        header_len: usize,
+        payload: &mut Vec<u8, BUFFER_SIZE>,
+        mode: CommMode,
+    ) -> Result<(), Error<E>> {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        let bs = session.cipher.block_size();
+
+        match session.scheme {
+            Scheme::Ev1 => {
+                let mut message = Vec::<u8, BUFFER_SIZE>::new();
+                message.push(cmd).map_err(|_| Error::NoRoom)?;
+                message
+                    .extend_from_slice(payload)
+                    .map_err(|_| Error::NoRoom)?;
+
+                match mode {
+                    CommMode::Plain | CommMode::Mac => {
+                        let mac = session.cipher.cmac(&session.iv, &message);
+                        session.iv = mac;
+                        if mode == CommMode::Mac {
+                            payload
+                                .extend_from_slice(&mac[..CMAC_SIZE])
+                                .map_err(|_| Error::NoRoom)?;
+                        }
+                    }
+                    CommMode::Enciphered => {
+                        payload
+                            .extend_from_slice(&crypto::crc32(&message))
+                            .map_err(|_| Error::NoRoom)?;
+                        pad(payload, header_len, bs)?;
+                        session
+                            .cipher
+                            .encrypt_cbc(&mut session.iv, &mut payload[header_len..]);
+                    }
+                }
+            }
+            Scheme::Legacy => match mode {
+                CommMode::Plain => {}
+                CommMode::Mac => {
+                    let mac = legacy_mac(&session.cipher, &payload[header_len..])?;
+                    payload.extend_from_slice(&mac).map_err(|_| Error::NoRoom)?;
+                }
+                CommMode::Enciphered => {
+                    let crc = crc::crc_a(&payload[header_len..]);
+                    payload.extend_from_slice(&crc).map_err(|_| Error::NoRoom)?;
+                    pad(payload, header_len, bs)?;
+                    session.cipher.decipher_send(&mut payload[header_len..]);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Verify and/or decipher a response, returns the length of the response data
+    fn unwrap_response(
+        &mut self,
+        response: &mut Vec<u8, BUFFER_SIZE>,
+        status: u8,
+        mode: CommMode,
+    ) -> Result<usize, Error<E>> {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return Ok(response.len()),
+        };
+        let bs = session.cipher.block_size();
+
+        let result = match (session.scheme, mode) {
+            (Scheme::Ev1, CommMode::Plain | CommMode::Mac) => {
+                let n = response
+                    .len()
+                    .checked_sub(CMAC_SIZE)
+                    .ok_or(Error::IncompleteFrame(Context::default()))?;
+                let received: [u8; CMAC_SIZE] = response[n..].try_into().unwrap();
+                response.truncate(n);
+                response.push(status).map_err(|_| Error::NoRoom)?;
+                let mac = session.cipher.cmac(&session.iv, response);
+                session.iv = mac;
+                response.truncate(n);
+                if mac[..CMAC_SIZE] == received {
+                    Ok(n)
+                } else {
+                    Err(Error::Integrity)
+                }
+            }
+            (Scheme::Ev1, CommMode::Enciphered) => {
+                if response.len() % bs != 0 || response.is_empty() {
+                    return Err(Error::IncompleteFrame(Context::default()));
+                }
+                session.cipher.decrypt_cbc(&mut session.iv, response);
+                strip_padding(response, |data| {
+                    let mut message = Vec::<u8, BUFFER_SIZE>::new();
+                    message.extend_from_slice(data).ok();
+                    message.push(status).ok();
+                    crypto::crc32(&message)
+                })
+                .ok_or(Error::Integrity)
+            }
+            (Scheme::Legacy, CommMode::Plain) => Ok(response.len()),
+            (Scheme::Legacy, CommMode::Mac) => {
+                let n = response
+                    .len()
+                    .checked_sub(LEGACY_MAC_SIZE)
+                    .ok_or(Error::IncompleteFrame(Context::default()))?;
+                let mac = legacy_mac(&session.cipher, &response[..n])?;
+                if mac[..] == response[n..] {
+                    Ok(n)
+                } else {
+                    Err(Error::Integrity)
+                }
+            }
+            (Scheme::Legacy, CommMode::Enciphered) => {
+                if response.len() % bs != 0 || response.is_empty() {
+                    return Err(Error::IncompleteFrame(Context::default()));
+                }
+                session
+                    .cipher
+                    .decrypt_cbc(&mut [0u8; MAX_BLOCK_SIZE], response);
+                strip_padding(response, crc::crc_a).ok_or(Error::Integrity)
+            }
+        };
+
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+
+    /// Send a native command, continuing with additional frames when the command
+    /// or response does not fit in a single frame.
+    /// Returns the status of the last frame.
+    fn transceive(
+        &mut self,
+        cmd: u8,
+        payload: &[u8],
+        rx: &mut Vec<u8, BUFFER_SIZE>,
+    ) -> Result<u8, Error<E>> {
+        let mut chunks = payload.chunks(FRAME_DATA_SIZE);
+        let mut code = cmd;
+        loop {
+            let mut frame = Vec::<u8, APDU_SIZE>::new();
+            let status = self.exchange(code, chunks.next().unwrap_or(&[]), &mut frame)?;
+            rx.extend_from_slice(&frame).map_err(|_| Error::NoRoom)?;
+            if status != ADDITIONAL_FRAME {
+                return Ok(status);
+            }
+            code = ADDITIONAL_FRAME;
+        }
+    }
+
+    /// Exchange a single native frame wrapped in an ISO/IEC 7816-4 APDU,
+    /// returns the DESFire status code.
+    fn exchange(
+        &mut self,
+        cmd: u8,
+        data: &[u8],
+        rx: &mut Vec<u8, APDU_SIZE>,
+    ) -> Result<u8, Error<E>> {
+        let mut apdu = Vec::<u8, APDU_SIZE>::new();
+        apdu.extend_from_slice(&[WRAP_CLA, cmd, 0x00, 0x00])
+            .map_err(|_| Error::NoRoom)?;
+        if !data.is_empty() {
+            apdu.push(data.len() as u8).map_err(|_| Error::NoRoom)?;
+            apdu.extend_from_slice(data).map_err(|_| Error::NoRoom)?;
+        }
+        apdu.push(0x00).map_err(|_| Error::NoRoom)?;
+
+        let mut response = [0u8; APDU_SIZE];
+        let n = self.iso_dep.exchange(&apdu, &mut response)?;
+        if n < 2 {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+        let (sw1, sw2) = (response[n - 2], response[n - 1]);
+        if sw1 != WRAP_SW1 {
+            return Err(Error::Status(u16::from_be_bytes([sw1, sw2])));
+        }
+
+        rx.extend_from_slice(&response[..n - 2])
+            .map_err(|_| Error::NoRoom)?;
+        Ok(sw2)
+    }
+}
+
+/// File number + offset (3 bytes) + length (3 bytes)
+fn file_header<E>(file_no: u8, offset: u32, len: usize) -> Result<[u8; 7], Error<E>> {
+    if offset > 0xFF_FFFF || len > 0xFF_FFFF {
+        return Err(Error::NoRoom);
+    }
+    let offset = offset.to_le_bytes();
+    let len = (len as u32).to_le_bytes();
+    Ok([
+        file_no, offset[0], offset[1], offset[2], len[0], len[1], len[2],
+    ])
+}
+
+/// The key data sent in a ChangeKey command, DES keys are sent as 2K3DES keys
+fn change_key_bytes(key: &Key) -> Vec<u8, 24> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(key.as_bytes()).unwrap();
+    if let Key::Des(k) = key {
+        bytes.extend_from_slice(k).unwrap();
+    }
+    bytes
+}
+
+/// Pad with zeros, so the data starting at `start` is a multiple of the block size
+fn pad<E, const N: usize>(
+    buffer: &mut Vec<u8, N>,
+    start: usize,
+    block_size: usize,
+) -> Result<(), Error<E>> {
+    while (buffer.len() - start) % block_size != 0 {
+        buffer.push(0).map_err(|_| Error::NoRoom)?;
+    }
+    Ok(())
+}
+
+/// MAC used by legacy secure messaging: the first 4 bytes of the last block
+/// of the data enciphered in CBC mode
+fn legacy_mac<E>(cipher: &Cipher, data: &[u8]) -> Result<[u8; LEGACY_MAC_SIZE], Error<E>> {
+    let mut buffer = Vec::<u8, BUFFER_SIZE>::new();
+    buffer.extend_from_slice(data).map_err(|_| Error::NoRoom)?;
+    pad(&mut buffer, 0, cipher.block_size())?;
+
+    let mut iv = [0u8; MAX_BLOCK_SIZE];
+    cipher.encrypt_cbc(&mut iv, &mut buffer);
+    Ok(iv[..LEGACY_MAC_SIZE].try_into().unwrap())
+}
+
+/// Find the length of the deciphered data, which is followed by a CRC and zero padding.
+///
+/// The shortest length is taken: the CRC of the data followed by its CRC is zero, so with
+/// enough padding, the data including its CRC would match as well.
+fn strip_padding<const C: usize, F>(buffer: &mut Vec<u8, BUFFER_SIZE>, crc: F) -> Option<usize>
+where
+    F: Fn(&[u8]) -> [u8; C],
+{
+    let max = buffer.len().checked_sub(C)?;
+    let min = max.saturating_sub(MAX_BLOCK_SIZE - 1);
+    let n = (min..=max).find(|&n| {
+        buffer[n + C..].iter().all(|b| *b == 0) && crc(&buffer[..n])[..] == buffer[n..n + C]
+    })?;
+    buffer.truncate(n);
+    Some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: [u8; 4] = [0xCA, 0xFE, 0xBA, 0xBE];
+
+    fn deciphered<const C: usize>(crc: [u8; C], padding: usize) -> Vec<u8, BUFFER_SIZE> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&DATA).unwrap();
+        buffer.extend_from_slice(&crc).unwrap();
+        buffer.resize(buffer.len() + padding, 0).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn strip_crc_a_and_padding() {
+        // with at least 2 bytes of padding, the data including its CRC matches as well
+        let mut buffer = deciphered(crc::crc_a(&DATA), 2);
+        assert_eq!(strip_padding(&mut buffer, crc::crc_a), Some(4));
+        assert_eq!(buffer, DATA);
+
+        let mut buffer = deciphered(crc::crc_a(&DATA), 0);
+        assert_eq!(strip_padding(&mut buffer, crc::crc_a), Some(4));
+    }
+
+    #[test]
+    fn strip_crc32_and_padding() {
+        let mut buffer = deciphered(crypto::crc32(&DATA), 8);
+        assert_eq!(strip_padding(&mut buffer, crypto::crc32), Some(4));
+        assert_eq!(buffer, DATA);
+    }
+
+    #[test]
+    fn wrong_crc() {
+        let mut crc = crypto::crc32(&DATA);
+        crc[0] ^= 1;
+        let mut buffer = deciphered(crc, 8);
+        assert_eq!(strip_padding(&mut buffer, crypto::crc32), None);
+    }
+
+    #[test]
+    fn padding() {
+        let mut buffer = Vec::<u8, 32>::new();
+        buffer.extend_from_slice(&[0x90, 1, 2, 3, 4, 5]).unwrap();
+        pad::<(), 32>(&mut buffer, 1, 8).unwrap();
+        assert_eq!(buffer[1..], [1, 2, 3, 4, 5, 0, 0, 0]);
+        // nothing is added to complete blocks
+        pad::<(), 32>(&mut buffer, 1, 8).unwrap();
+        assert_eq!(buffer.len(), 9);
+    }
+}
@@ -0,0 +1,221 @@
+//! Key diversification according to NXP AN10922.
+//!
+//! Instead of storing the same key on every card, a unique card key is derived
+//! from a master key and diversification input, typically the UID of the card
+//! combined with an application and system identifier.
+//! This way, compromising a single card does not reveal the keys of other cards.
+//!
+//! ```ignore
+//! let card = mfrc522.select(&atqa)?;
+//! let input = DiversificationInput::new(card.uid())
+//!     .with_aid(&[0x30, 0x42, 0xF5])?
+//!     .with_system_identifier(b"NXP Abu")?;
+//! let card_key = master_key.diversify(&input)?;
+//! ```
+
+use heapless::Vec;
+
+use crate::crypto::{Cipher, Key, MAX_BLOCK_SIZE};
+use crate::{MifareKey, Uid};
+
+/// Maximum length of the diversification input
+pub const MAX_INPUT_SIZE: usize = 31;
+
+/// Diversification constant for AES-128 keys
+const AES128_CONSTANT: u8 = 0x01;
+/// Diversification constants for 2K3DES keys
+const TDES2_CONSTANTS: [u8; 2] = [0x21, 0x22];
+/// Diversification constants for 3K3DES keys
+const TDES3_CONSTANTS: [u8; 3] = [0x31, 0x32, 0x33];
+
+/// Diversification input, at most 31 bytes for AES keys and 15 bytes for 3DES keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiversificationInput {
+    bytes: Vec<u8, MAX_INPUT_SIZE>,
+}
+
+impl DiversificationInput {
+    /// Start the diversification input with the UID of the card
+    pub fn new(uid: &Uid) -> Self {
+        // a UID is at most 10 bytes long, so this always fits
+        DiversificationInput {
+            bytes: Vec::from_slice(uid.as_bytes()).unwrap(),
+        }
+    }
+
+    /// Use arbitrary bytes as diversification input
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(DiversificationInput {
+            bytes: Vec::from_slice(bytes).ok()?,
+        })
+    }
+
+    /// Append an application identifier (eg the 3 byte DESFire AID)
+    pub fn with_aid(self, aid: &[u8]) -> Option<Self> {
+        self.append(aid)
+    }
+
+    /// Append a system identifier
+    pub fn with_system_identifier(self, system_identifier: &[u8]) -> Option<Self> {
+        self.append(system_identifier)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn append(mut self, bytes: &[u8]) -> Option<Self> {
+        self.bytes.extend_from_slice(bytes).ok()?;
+        Some(self)
+    }
+}
+
+impl Key {
+    /// Derive a card key from this master key.
+    ///
+    /// Supported for AES-128, 2K3DES and 3K3DES master keys, the resulting key has the
+    /// same type as the master key.
+    /// Returns `None` for DES keys or if the input is too long for the key type
+    /// (31 bytes for AES-128, 15 bytes for 3DES).
+    pub fn diversify(&self, input: &DiversificationInput) -> Option<Key> {
+        let cipher = Cipher::new(self);
+        match self {
+            Key::Aes(_) => Some(Key::Aes(aes128(&cipher, input)?)),
+            Key::TDes2(master_key) => {
+                let mut key = [0u8; 16];
+                tdes(&cipher, &TDES2_CONSTANTS, input, &mut key)?;
+                restore_key_version(&mut key, master_key);
+                Some(Key::TDes2(key))
+            }
+            Key::TDes3(master_key) => {
+                let mut key = [0u8; 24];
+                tdes(&cipher, &TDES3_CONSTANTS, input, &mut key)?;
+                restore_key_version(&mut key, master_key);
+                Some(Key::TDes3(key))
+            }
+            Key::Des(_) => None,
+        }
+    }
+}
+
+/// Derive a MIFARE Classic key from an AES-128 master key.
+///
+/// The AES-128 key is diversified as described in AN10922,
+/// the first 6 bytes are used as MIFARE Classic key.
+pub fn diversify_mifare_key(
+    master_key: &[u8; 16],
+    input: &DiversificationInput,
+) -> Option<MifareKey> {
+    let key = aes128(&Cipher::new(&Key::Aes(*master_key)), input)?;
+    Some(key[..6].try_into().unwrap())
+}
+
+fn aes128(cipher: &Cipher, input: &DiversificationInput) -> Option<[u8; 16]> {
+    cmac(cipher, AES128_CONSTANT, input.as_bytes(), 32)
+}
+
+fn tdes(
+    cipher: &Cipher,
+    constants: &[u8],
+    input: &DiversificationInput,
+    key: &mut [u8],
+) -> Option<()> {
+    for (constant, part) in constants.iter().zip(key.chunks_exact_mut(8)) {
+        let mac = cmac(cipher, *constant, input.as_bytes(), 16)?;
+        part.copy_from_slice(&mac[..8]);
+    }
+    Some(())
+}
+
+/// Copy the key version of the master key into the diversified key (AN10922 2.3 and 2.4).
+///
+/// The version is stored in the least significant (parity) bits of the first 8 bytes.
+fn restore_key_version(key: &mut [u8], master_key: &[u8]) {
+    for (byte, master) in key[..8].iter_mut().zip(master_key) {
+        *byte = (*byte & 0xFE) | (master & 0x01);
+    }
+}
+
+/// CMAC of the diversification constant followed by the diversification input.
+///
+/// Unlike a regular CMAC, the message is always padded to `len` bytes.
+fn cmac(cipher: &Cipher, constant: u8, input: &[u8], len: usize) -> Option<[u8; MAX_BLOCK_SIZE]> {
+    if input.len() + 1 > len {
+        return None;
+    }
+    let bs = cipher.block_size();
+    let (k1, k2) = cipher.cmac_subkeys();
+
+    let mut message = [0u8; 32];
+    message[0] = constant;
+    message[1..=input.len()].copy_from_slice(input);
+    let subkey = if input.len() + 1 < len {
+        message[input.len() + 1] = 0x80;
+        k2
+    } else {
+        k1
+    };
+    let last = len - bs;
+    crate::crypto::xor(&mut message[last..len], &subkey[..bs]);
+
+    let mut iv = [0u8; MAX_BLOCK_SIZE];
+    cipher.encrypt_cbc(&mut iv, &mut message[..len]);
+    Some(iv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// UID, AID and system identifier of the examples in AN10922, the 3DES examples use
+    /// shorter system identifiers
+    fn input(system_identifier: &[u8]) -> DiversificationInput {
+        let uid = Uid::try_from(&[0x04, 0x78, 0x2E, 0x21, 0x80, 0x1D, 0x80][..]).unwrap();
+        DiversificationInput::new(&uid)
+            .with_aid(&[0x30, 0x42, 0xF5])
+            .unwrap()
+            .with_system_identifier(system_identifier)
+            .unwrap()
+    }
+
+    const MASTER_KEY: [u8; 24] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE,
+        0xFF, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    ];
+
+    #[test]
+    fn an10922_aes128() {
+        let key = Key::Aes(MASTER_KEY[..16].try_into().unwrap());
+        assert_eq!(
+            key.diversify(&input(b"NXP Abu")).unwrap().as_bytes(),
+            [
+                0xA8, 0xDD, 0x63, 0xA3, 0xB8, 0x9D, 0x54, 0xB3, 0x7C, 0xA8, 0x02, 0x47, 0x3F, 0xDA,
+                0x91, 0x75
+            ]
+        );
+    }
+
+    #[test]
+    fn an10922_2k3des() {
+        let key = Key::TDes2(MASTER_KEY[..16].try_into().unwrap());
+        assert_eq!(
+            key.diversify(&input(b"NXP A")).unwrap().as_bytes(),
+            [
+                0x16, 0xF9, 0x58, 0x7D, 0x9E, 0x89, 0x10, 0xC9, 0x6B, 0x96, 0x48, 0xD0, 0x06, 0x10,
+                0x7D, 0xD7
+            ]
+        );
+    }
+
+    #[test]
+    fn an10922_3k3des() {
+        let key = Key::TDes3(MASTER_KEY);
+        assert_eq!(
+            key.diversify(&input(b"NXP")).unwrap().as_bytes(),
+            [
+                0x2E, 0x0D, 0xD0, 0x37, 0x74, 0xD3, 0xFA, 0x9B, 0x57, 0x05, 0xAB, 0x0B, 0xDA, 0x91,
+                0xCA, 0x0B, 0x55, 0xB8, 0xE0, 0x7F, 0xCD, 0xBF, 0x10, 0xEC
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Reading the public data of EMV contactless payment cards.
+//!
+//! This only identifies the card and reads its records, no transaction is performed:
+//! 1. SELECT the Proximity Payment System Environment (`2PAY.SYS.DDF01`)
+//!    to list the available applications
+//! 2. SELECT one of the applications
+//! 3. GET PROCESSING OPTIONS, answering the PDOL with neutral terminal data
+//! 4. READ RECORD for all records listed in the Application File Locator (AFL)
+//!
+//! ```ignore
+//! let mut emv = Emv::new(card.iso_dep()?);
+//! for app in emv.select_ppse()? {
+//!     let mut buffer = [0u8; 1024];
+//!     let data = emv.read_application(app.aid(), &mut buffer)?;
+//!     if let Some(pan) = data.find_tag(0x5A) {
+//!         // application primary account number
+//!     }
+//! }
+//! ```
+
+use heapless::Vec;
+
+use crate::error::{Context, Error};
+use crate::iso_dep::IsoDep;
+use crate::tlv::{self, Tlvs};
+use crate::{spi, Initialized, Mfrc522, WithNssDelay};
+
+/// Maximum number of applications listed by [Emv::select_ppse]
+pub const MAX_APPLICATIONS: usize = 8;
+/// Maximum size of a response APDU: 256 data bytes and the status word
+const RESPONSE_SIZE: usize = 258;
+
+/// Name of the Proximity Payment System Environment
+const PPSE: &[u8] = b"2PAY.SYS.DDF01";
+
+const SW_OK: u16 = 0x9000;
+
+pub const TAG_FCI_TEMPLATE: u32 = 0x6F;
+pub const TAG_FCI_PROPRIETARY_TEMPLATE: u32 = 0xA5;
+pub const TAG_FCI_ISSUER_DISCRETIONARY_DATA: u32 = 0xBF0C;
+pub const TAG_APPLICATION_TEMPLATE: u32 = 0x61;
+pub const TAG_AID: u32 = 0x4F;
+pub const TAG_APPLICATION_LABEL: u32 = 0x50;
+pub const TAG_APPLICATION_PRIORITY: u32 = 0x87;
+pub const TAG_PDOL: u32 = 0x9F38;
+pub const TAG_RESPONSE_FORMAT_1: u32 = 0x80;
+pub const TAG_RESPONSE_FORMAT_2: u32 = 0x77;
+pub const TAG_AFL: u32 = 0x94;
+pub const TAG_RECORD_TEMPLATE: u32 = 0x70;
+/// Terminal Transaction Qualifiers
+const TAG_TTQ: u32 = 0x9F66;
+/// Command template for GET PROCESSING OPTIONS
+const TAG_COMMAND_TEMPLATE: u8 = 0x83;
+
+/// Terminal Transaction Qualifiers used to answer the PDOL:
+/// contactless EMV mode and online capable
+const TTQ: [u8; 4] = [0x36, 0x00, 0x40, 0x00];
+
+/// Application listed in the PPSE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Application {
+    aid: Vec<u8, 16>,
+    label: Vec<u8, 16>,
+    priority: Option<u8>,
+}
+
+impl Application {
+    /// Application Identifier
+    pub fn aid(&self) -> &[u8] {
+        &self.aid
+    }
+
+    /// Application label (ASCII), may be empty
+    pub fn label(&self) -> &[u8] {
+        &self.label
+    }
+
+    /// Application priority indicator
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+}
+
+/// EMV contactless card
+pub struct Emv<'a, SPI, NSS, D> {
+    iso_dep: IsoDep<'a, SPI, NSS, D>,
+}
+
+impl<'a, E, SPI, NSS, D> Emv<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Use an activated ISO-DEP PICC as EMV card
+    pub fn new(iso_dep: IsoDep<'a, SPI, NSS, D>) -> Self {
+        Emv { iso_dep }
+    }
+
+    /// Release the underlying ISO-DEP PICC
+    pub fn release(self) -> IsoDep<'a, SPI, NSS, D> {
+        self.iso_dep
+    }
+
+    /// SELECT the Proximity Payment System Environment and list the applications
+    pub fn select_ppse(&mut self) -> Result<Vec<Application, MAX_APPLICATIONS>, Error<E>> {
+        let mut fci = [0u8; RESPONSE_SIZE];
+        let n = self.select(PPSE, &mut fci)?;
+
+        let mut applications = Vec::new();
+        let templates = tlv::parse(&fci[..n])
+            .find_tag(TAG_FCI_ISSUER_DISCRETIONARY_DATA)
+            .map(|t| t.children())
+            .unwrap_or_else(|| Tlvs::new(&[]));
+        for template in templates.filter(|t| t.tag() == TAG_APPLICATION_TEMPLATE) {
+            // AIDs are at most 16 bytes long
+            let aid = match template
+                .find(TAG_AID)
+                .map(|aid| Vec::from_slice(aid.value()))
+            {
+                Some(Ok(aid)) => aid,
+                _ => continue,
+            };
+            let label = template
+                .find(TAG_APPLICATION_LABEL)
+                .map(|l| l.value())
+                .unwrap_or(&[]);
+            let application = Application {
+                aid,
+                label: Vec::from_slice(&label[..label.len().min(16)]).unwrap(),
+                priority: template
+                    .find(TAG_APPLICATION_PRIORITY)
+                    .and_then(|p| p.value().first().copied()),
+            };
+            if applications.push(application).is_err() {
+                break;
+            }
+        }
+        Ok(applications)
+    }
+
+    /// SELECT an application by its name (AID), the FCI is stored in `rx`.
+    /// Returns the length of the FCI.
+    pub fn select(&mut self, aid: &[u8], rx: &mut [u8]) -> Result<usize, Error<E>> {
+        self.transmit(0x00, 0xA4, 0x04, 0x00, aid, rx)
+    }
+
+    /// GET PROCESSING OPTIONS, answering the PDOL (if any) with neutral terminal data:
+    /// all values are zero, except for the terminal transaction qualifiers.
+    ///
+    /// The response message template is stored in `rx`, returns its length.
+    pub fn get_processing_options(
+        &mut self,
+        pdol: Option<&[u8]>,
+        rx: &mut [u8],
+    ) -> Result<usize, Error<E>> {
+        // the command template has to fit in a short APDU, with up to 3 bytes for tag and length
+        let mut values = Vec::<u8, 252>::new();
+        let mut dol = pdol.unwrap_or(&[]);
+        while !dol.is_empty() {
+            let (tag, rest) =
+                tlv::parse_tag(dol).ok_or(Error::IncompleteFrame(Context::default()))?;
+            let (&len, rest) = rest
+                .split_first()
+                .ok_or(Error::IncompleteFrame(Context::default()))?;
+            dol = rest;
+
+            let len = len as usize;
+            let start = values.len();
+            values.resize(start + len, 0).map_err(|_| Error::NoRoom)?;
+            if tag == TAG_TTQ {
+                let n = len.min(TTQ.len());
+                values[start..start + n].copy_from_slice(&TTQ[..n]);
+            }
+        }
+
+        let mut data = Vec::<u8, 255>::new();
+        data.push(TAG_COMMAND_TEMPLATE).unwrap();
+        // BER-TLV lengths from 128 on are encoded in the long form, 81 followed by the length
+        if values.len() >= 0x80 {
+            data.push(0x81).unwrap();
+        }
+        data.push(values.len() as u8).unwrap();
+        data.extend_from_slice(&values).unwrap();
+
+        self.transmit(0x80, 0xA8, 0x00, 0x00, &data, rx)
+    }
+
+    /// READ RECORD from the given short file identifier
+    pub fn read_record(&mut self, sfi: u8, record: u8, rx: &mut [u8]) -> Result<usize, Error<E>> {
+        self.transmit(0x00, 0xB2, record, (sfi << 3) | 0x04, &[], rx)
+    }
+
+    /// Read all public data of an application: the FCI, the response to
+    /// GET PROCESSING OPTIONS and all records listed in the AFL.
+    ///
+    /// The responses are stored one after the other in `buffer`,
+    /// which is returned as BER-TLV data objects.
+    pub fn read_application<'b>(
+        &mut self,
+        aid: &[u8],
+        buffer: &'b mut [u8],
+    ) -> Result<Tlvs<'b>, Error<E>> {
+        let mut len = self.select(aid, buffer)?;
+
+        let mut pdol = Vec::<u8, 255>::new();
+        if let Some(tlv) = tlv::parse(&buffer[..len]).find_tag(TAG_PDOL) {
+            pdol.extend_from_slice(tlv.value())
+                .map_err(|_| Error::NoRoom)?;
+        }
+
+        let n = self.get_processing_options(Some(&pdol), &mut buffer[len..])?;
+        let gpo = &buffer[len..len + n];
+        len += n;
+
+        // Format 1: AIP (2 bytes) followed by the AFL, format 2: AFL in its own data object
+        let mut afl = Vec::<u8, 252>::new();
+        let response = tlv::parse(gpo).next();
+        let afl_data = match response {
+            Some(t) if t.tag() == TAG_RESPONSE_FORMAT_1 => t.value().get(2..),
+            Some(t) if t.tag() == TAG_RESPONSE_FORMAT_2 => t.find(TAG_AFL).map(|t| t.value()),
+            _ => None,
+        };
+        afl.extend_from_slice(afl_data.unwrap_or(&[]))
+            .map_err(|_| Error::NoRoom)?;
+
+        for entry in afl.chunks_exact(4) {
+            let sfi = entry[0] >> 3;
+            for record in entry[1]..=entry[2] {
+                len += self.read_record(sfi, record, &mut buffer[len..])?;
+            }
+        }
+
+        Ok(Tlvs::new(&buffer[..len]))
+    }
+
+    /// Transmit a command APDU, returns the length of the response data.
+    ///
+    /// Responses indicating more data is available (`61XX`)
+    /// or a wrong length was requested (`6CXX`) are handled.
+    fn transmit(
+        &mut self,
+        cla: u8,
+        ins: u8,
+        p1: u8,
+        p2: u8,
+        data: &[u8],
+        rx: &mut [u8],
+    ) -> Result<usize, Error<E>> {
+        let mut apdu = Vec::<u8, 261>::new();
+        apdu.extend_from_slice(&[cla, ins, p1, p2]).unwrap();
+        if !data.is_empty() {
+            apdu.push(data.len() as u8).map_err(|_| Error::NoRoom)?;
+            apdu.extend_from_slice(data).map_err(|_| Error::NoRoom)?;
+        }
+        apdu.push(0x00).unwrap();
+
+        let mut len = 0;
+        loop {
+            let mut response = [0u8; RESPONSE_SIZE];
+            let n = self.iso_dep.exchange(&apdu, &mut response)?;
+            if n < 2 {
+                return Err(Error::IncompleteFrame(Context::default()));
+            }
+            let sw = u16::from_be_bytes([response[n - 2], response[n - 1]]);
+            let data = &response[..n - 2];
+            rx.get_mut(len..len + data.len())
+                .ok_or(Error::NoRoom)?
+                .copy_from_slice(data);
+            len += data.len();
+
+            match sw {
+                SW_OK => return Ok(len),
+                0x6100..=0x61FF => {
+                    // GET RESPONSE
+                    apdu.clear();
+                    apdu.extend_from_slice(&[0x00, 0xC0, 0x00, 0x00, sw as u8])
+                        .unwrap();
+                }
+                0x6C00..=0x6CFF => {
+                    // repeat the command with the correct Le
+                    let le = apdu.len() - 1;
+                    apdu[le] = sw as u8;
+                }
+                _ => return Err(Error::Status(sw)),
+            }
+        }
+    }
+}
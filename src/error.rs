@@ -1,40 +1,177 @@
+use core::fmt;
+
+use crate::register::{BUFFER_OVFL, COLL_ERR, CRC_ERR, PARITY_ERR, PROTOCOL_ERR, TEMP_ERR, WR_ERR};
+
 /// Errors
+///
+/// The errors reported by the MFRC522 while communicating with a PICC carry a [Context]:
+/// the raw ErrorReg and ComIrqReg values and the operation that failed.
+/// The frame errors detected by the driver (BCC, incomplete frame and NAK) carry the
+/// operation only. [Display](core::fmt::Display) formats all of it, eg
+/// `READ block 4: wrong CRC_A (ErrorReg 04: CRCErr, ComIrqReg 66)` or
+/// `WRITE block 5: NAK from the PICC: invalid operation`.
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error<E> {
     /// Wrong Block Character Check (BCC)
-    Bcc,
+    Bcc(Context),
     /// FIFO buffer overflow
-    BufferOverflow,
+    BufferOverflow(Context),
     /// Collision
-    Collision,
+    Collision(Context),
     /// Wrong CRC
-    Crc,
+    Crc(Context),
     /// Incomplete RX frame
-    IncompleteFrame,
+    IncompleteFrame(Context),
     /// Provided buffer not large enough
     NoRoom,
     /// Internal temperature sensor detects overheating
-    Overheating,
+    Overheating(Context),
     /// Parity check failed
-    Parity,
+    Parity(Context),
     /// Error during MFAuthent operation
-    Protocol,
+    Protocol(Context),
     /// SPI bus error
     Spi(E),
     /// Timeout
-    Timeout,
+    Timeout(Context),
     /// Write error: FIFO buffer was written at invalid time
-    Wr,
-    /// Not acknowledge
-    Nak,
+    Wr(Context),
+    /// Not acknowledge, the NAK code decoded according to the PICC type
+    Nak(Nak, Context),
     /// Proprietary frames, commands or protocols used
     Proprietary,
+    /// Unexpected ISO/IEC 14443-4 block received
+    InvalidBlock,
+    /// The PICC returned an error status word (SW1 SW2)
+    Status(u16),
+    /// Verification of a cryptogram or MAC failed
+    Integrity,
+    /// The operation requires an authenticated session
+    NotAuthenticated,
+    /// The requested parameters are not supported (by the PICC)
+    Unsupported,
+    /// MIFARE Classic authentication failed: the PICC answered, but Crypto1 was not
+    /// switched on (Status2Reg MFCrypto1On), most likely because of a wrong key
+    AuthFailed(Context),
 }
 
-#[cfg(feature = "std")]
-impl<E> std::fmt::Display for Error<E> {
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Ok(())
+impl<E> Error<E> {
+    /// Convert the SPI error, eg to a common error type of the application
+    pub fn map<F, E2>(self, f: F) -> Error<E2>
+    where
+        F: FnOnce(E) -> E2,
+    {
+        match self {
+            Error::Bcc(c) => Error::Bcc(c),
+            Error::BufferOverflow(c) => Error::BufferOverflow(c),
+            Error::Collision(c) => Error::Collision(c),
+            Error::Crc(c) => Error::Crc(c),
+            Error::IncompleteFrame(c) => Error::IncompleteFrame(c),
+            Error::NoRoom => Error::NoRoom,
+            Error::Overheating(c) => Error::Overheating(c),
+            Error::Parity(c) => Error::Parity(c),
+            Error::Protocol(c) => Error::Protocol(c),
+            Error::Spi(e) => Error::Spi(f(e)),
+            Error::Timeout(c) => Error::Timeout(c),
+            Error::Wr(c) => Error::Wr(c),
+            Error::Nak(nak, c) => Error::Nak(nak, c),
+            Error::Proprietary => Error::Proprietary,
+            Error::InvalidBlock => Error::InvalidBlock,
+            Error::Status(sw) => Error::Status(sw),
+            Error::Integrity => Error::Integrity,
+            Error::NotAuthenticated => Error::NotAuthenticated,
+            Error::Unsupported => Error::Unsupported,
+            Error::AuthFailed(c) => Error::AuthFailed(c),
+        }
+    }
+
+    /// The context of an error reported by the MFRC522 or of a frame error
+    pub fn context(&self) -> Option<&Context> {
+        match self {
+            Error::Bcc(c)
+            | Error::BufferOverflow(c)
+            | Error::Collision(c)
+            | Error::Crc(c)
+            | Error::IncompleteFrame(c)
+            | Error::Overheating(c)
+            | Error::Parity(c)
+            | Error::Protocol(c)
+            | Error::Timeout(c)
+            | Error::Wr(c)
+            | Error::Nak(_, c)
+            | Error::AuthFailed(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Attach the operation that failed, unless the error already tells a more specific one
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        match &mut self {
+            Error::Bcc(c)
+            | Error::BufferOverflow(c)
+            | Error::Collision(c)
+            | Error::Crc(c)
+            | Error::IncompleteFrame(c)
+            | Error::Overheating(c)
+            | Error::Parity(c)
+            | Error::Protocol(c)
+            | Error::Timeout(c)
+            | Error::Wr(c)
+            | Error::Nak(_, c)
+            | Error::AuthFailed(c) => {
+                c.operation.get_or_insert(operation);
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Error::Spi(e)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context();
+        if let Some(operation) = context.and_then(|c| c.operation) {
+            write!(f, "{}: ", operation)?;
+        }
+        match self {
+            Error::Bcc(_) => f.write_str("wrong BCC of the UID"),
+            Error::BufferOverflow(_) => f.write_str("FIFO buffer overflow"),
+            Error::Collision(_) => f.write_str("bit collision"),
+            Error::Crc(_) => f.write_str("wrong CRC_A"),
+            Error::IncompleteFrame(_) => f.write_str("incomplete frame received"),
+            Error::NoRoom => f.write_str("buffer too small"),
+            Error::Overheating(_) => f.write_str("the MFRC522 is overheating"),
+            Error::Parity(_) => f.write_str("parity error"),
+            Error::Protocol(_) => f.write_str("protocol error"),
+            Error::Spi(e) => write!(f, "SPI bus error: {:?}", e),
+            Error::Timeout(c) => match c.operation {
+                Some(Operation::CalcCrc | Operation::Command) => {
+                    f.write_str("the MFRC522 did not finish in time")
+                }
+                Some(Operation::WakeUp) => f.write_str("the oscillator did not start"),
+                _ => f.write_str("no answer from the PICC"),
+            },
+            Error::Wr(_) => f.write_str("FIFO buffer written at an invalid time"),
+            Error::Nak(nak, _) => write!(f, "NAK from the PICC: {}", nak),
+            Error::Proprietary => f.write_str("proprietary anticollision, not supported"),
+            Error::InvalidBlock => f.write_str("unexpected ISO/IEC 14443-4 block"),
+            Error::Status(sw) => write!(f, "error status {:04X}", sw),
+            Error::Integrity => f.write_str("verification of a cryptogram or MAC failed"),
+            Error::NotAuthenticated => f.write_str("not authenticated"),
+            Error::Unsupported => f.write_str("not supported by the PICC"),
+            Error::AuthFailed(_) => f.write_str("authentication failed, wrong key"),
+        }?;
+        match context {
+            Some(c) if c.error_reg != 0 || c.com_irq != 0 => write!(f, " ({})", c),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -55,3 +192,177 @@ where
         self.source()
     }
 }
+
+/// NAK of a MIFARE PICC: the 4 bit answer to a command, in place of the ACK.
+///
+/// The codes differ between MIFARE Classic and MIFARE Ultralight/NTAG, see [Nak::classic]
+/// and [Nak::ultralight].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Nak {
+    /// Invalid operation or argument, eg a block beyond the memory, an operation not allowed
+    /// by the access conditions or a locked page
+    InvalidOperation,
+    /// The PICC received the command with a parity or CRC error
+    TransmissionError,
+    /// Invalid authentication counter overflow (NTAG21x, MIFARE Ultralight EV1)
+    CounterOverflow,
+    /// Write to the EEPROM failed (NTAG21x, MIFARE Ultralight EV1)
+    WriteError,
+    /// Another NAK code
+    Other(u8),
+    /// An answer that is neither an ACK nor a NAK, eg a PICC answering HLTA
+    Unexpected,
+}
+
+impl Nak {
+    /// NAK code of a MIFARE Classic PICC
+    pub fn classic(code: u8) -> Self {
+        match code & 0x0F {
+            0x0 | 0x4 => Nak::InvalidOperation,
+            0x1 | 0x5 => Nak::TransmissionError,
+            code => Nak::Other(code),
+        }
+    }
+
+    /// NAK code of a MIFARE Ultralight or NTAG PICC
+    pub fn ultralight(code: u8) -> Self {
+        match code & 0x0F {
+            0x0 => Nak::InvalidOperation,
+            0x1 => Nak::TransmissionError,
+            0x4 => Nak::CounterOverflow,
+            0x5 => Nak::WriteError,
+            code => Nak::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for Nak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Nak::InvalidOperation => f.write_str("invalid operation"),
+            Nak::TransmissionError => f.write_str("parity or CRC error"),
+            Nak::CounterOverflow => f.write_str("authentication counter overflow"),
+            Nak::WriteError => f.write_str("EEPROM write error"),
+            Nak::Other(code) => write!(f, "code {:X}", code),
+            Nak::Unexpected => f.write_str("unexpected answer"),
+        }
+    }
+}
+
+/// Operation on a PICC or of the MFRC522 itself, telling which one failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation {
+    Reqa,
+    Wupa,
+    Hlta,
+    /// Anticollision and SELECT of a cascade level (1 to 3)
+    Select(u8),
+    /// MIFARE Classic authentication for a block
+    Authenticate(u8),
+    /// READ of a block (MIFARE Classic) or page (MIFARE Ultralight, NTAG)
+    Read(u8),
+    /// WRITE of a block or page
+    Write(u8),
+    /// CRC_A calculation of the coprocessor
+    CalcCrc,
+    /// Leaving soft power-down, until the oscillator is stable
+    WakeUp,
+    /// A command of the MFRC522 that terminates by itself (eg Mem or Generate RandomID)
+    Command,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Reqa => f.write_str("REQA"),
+            Operation::Wupa => f.write_str("WUPA"),
+            Operation::Hlta => f.write_str("HLTA"),
+            Operation::Select(level) => write!(f, "SELECT CL{}", level),
+            Operation::Authenticate(block) => write!(f, "AUTH block {}", block),
+            Operation::Read(block) => write!(f, "READ block {}", block),
+            Operation::Write(block) => write!(f, "WRITE block {}", block),
+            Operation::CalcCrc => f.write_str("CalcCRC"),
+            Operation::WakeUp => f.write_str("wake-up"),
+            Operation::Command => f.write_str("MFRC522 command"),
+        }
+    }
+}
+
+/// State of the MFRC522 when it reported an error
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Context {
+    /// The operation that failed, if known
+    pub operation: Option<Operation>,
+    /// ErrorReg, all error bits that were set (the variant of the [Error] only tells the
+    /// most relevant one)
+    pub error_reg: u8,
+    /// ComIrqReg, the interrupt request bits
+    pub com_irq: u8,
+}
+
+impl Context {
+    pub fn new(error_reg: u8, com_irq: u8) -> Self {
+        Context {
+            operation: None,
+            error_reg,
+            com_irq,
+        }
+    }
+}
+
+/// The register values, eg `ErrorReg 0A: ParityErr CollErr, ComIrqReg 64`
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ErrorReg {:02X}", self.error_reg)?;
+        if self.error_reg != 0 {
+            write!(f, ": {}", ErrorBits(self.error_reg))?;
+        }
+        write!(f, ", ComIrqReg {:02X}", self.com_irq)
+    }
+}
+
+/// Bits of the ErrorReg, formatted with their names in the datasheet
+pub(crate) struct ErrorBits(pub u8);
+
+impl ErrorBits {
+    const NAMES: [(u8, &'static str); 7] = [
+        (PROTOCOL_ERR, "ProtocolErr"),
+        (PARITY_ERR, "ParityErr"),
+        (CRC_ERR, "CRCErr"),
+        (COLL_ERR, "CollErr"),
+        (BUFFER_OVFL, "BufferOvfl"),
+        (TEMP_ERR, "TempErr"),
+        (WR_ERR, "WrErr"),
+    ];
+
+    fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        Self::NAMES
+            .iter()
+            .filter(|(bit, _)| self.0 & bit != 0)
+            .map(|&(_, name)| name)
+    }
+}
+
+impl fmt::Display for ErrorBits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, name) in self.names().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ErrorBits {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        for name in self.names() {
+            defmt::write!(f, "{=str} ", name)
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! PICC type identification according to NXP AN10833
+//! (MIFARE type identification procedure).
+//!
+//! The SAK alone only tells the family of a PICC. Depending on the SAK,
+//! the PICC is asked for more information:
+//! - PICCs without ISO/IEC 14443-4 support (SAK `0x00`) are sent GET_VERSION,
+//!   to tell apart MIFARE Ultralight EV1 and NTAG21x. PICCs that don't support GET_VERSION
+//!   are MIFARE Ultralight or, if they start a 3DES authentication, MIFARE Ultralight C.
+//! - PICCs with ISO/IEC 14443-4 support are activated with RATS and sent the (wrapped)
+//!   native GET_VERSION command, to tell apart MIFARE DESFire and MIFARE Plus.
+//!   When that is not supported, the historical bytes of the ATS are used.
+//!
+//! ```ignore
+//! let mut card = mfrc522.select(&atqa)?;
+//! let card_type = card.identify()?;
+//! if let Some(size) = card_type.memory_size() {
+//!     // total memory in bytes
+//! }
+//! ```
+
+use crate::error::{Context, Error};
+use crate::{spi, Initialized, Mfrc522, Uid, WithNssDelay};
+
+/// GET_VERSION command of MIFARE Ultralight EV1, NTAG21x, DESFire and Plus
+const GET_VERSION: u8 = 0x60;
+/// Start of the MIFARE Ultralight C 3DES authentication
+const ULC_AUTHENTICATE: u8 = 0x1A;
+/// Additional frame status, returned by a MIFARE Ultralight C when starting authentication
+const ADDITIONAL_FRAME: u8 = 0xAF;
+/// Vendor ID of NXP Semiconductors in the GET_VERSION response
+const VENDOR_NXP: u8 = 0x04;
+
+/// Product type of a PICC, as returned by [identify](crate::Card::identify)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    /// MIFARE Mini, 320 bytes
+    MifareMini,
+    /// MIFARE Classic 1K, or MIFARE Plus (2K) in security level 1
+    MifareClassic1k,
+    /// MIFARE Classic 4K, or MIFARE Plus (4K) in security level 1
+    MifareClassic4k,
+    /// MIFARE Ultralight, 64 bytes
+    MifareUltralight,
+    /// MIFARE Ultralight C, 192 bytes
+    MifareUltralightC,
+    /// MIFARE Ultralight EV1, MF0UL11 (80 bytes) or MF0UL21 (164 bytes)
+    MifareUltralightEv1 {
+        memory_size: usize,
+    },
+    /// NTAG210, 80 bytes
+    Ntag210,
+    /// NTAG212, 164 bytes
+    Ntag212,
+    /// NTAG213, 180 bytes
+    Ntag213,
+    /// NTAG215, 540 bytes
+    Ntag215,
+    /// NTAG216, 924 bytes
+    Ntag216,
+    /// MIFARE Plus in security level 2 or 3.
+    ///
+    /// Security level 0 (not personalized) can't be told apart from security level 3.
+    MifarePlus {
+        security_level: u8,
+        memory_size: usize,
+    },
+    /// MIFARE DESFire
+    MifareDesfire {
+        version: DesfireVersion,
+        memory_size: usize,
+    },
+    /// SmartMX with MIFARE Classic 1K emulation
+    SmartMx1k,
+    /// SmartMX with MIFARE Classic 4K emulation
+    SmartMx4k,
+    /// Toy PICC, only mentioned in NXP AN10833
+    Tnp3xxx,
+    /// Other PICC compliant with ISO/IEC 14443-4
+    Iso14443_4,
+    /// PICC compliant with ISO/IEC 18092 (NFC)
+    Iso18092,
+    /// Other NXP PICC, with the GET_VERSION response
+    UnknownNxp {
+        version: [u8; 8],
+    },
+    Unknown,
+}
+
+/// MIFARE DESFire product generation, from the hardware major version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesfireVersion {
+    /// MF3ICD40
+    D40,
+    Ev1,
+    Ev2,
+    Ev3,
+    /// Other major version
+    Other(u8),
+    /// The PICC does not support GET_VERSION, it was identified using the ATS
+    Unknown,
+}
+
+impl CardType {
+    /// Total memory size in bytes, if known
+    pub fn memory_size(&self) -> Option<usize> {
+        match *self {
+            CardType::MifareMini => Some(320),
+            CardType::MifareClassic1k | CardType::SmartMx1k => Some(1024),
+            CardType::MifareClassic4k | CardType::SmartMx4k => Some(4096),
+            CardType::MifareUltralight => Some(64),
+            CardType::MifareUltralightC => Some(192),
+            CardType::MifareUltralightEv1 { memory_size } => Some(memory_size),
+            CardType::Ntag210 => Some(80),
+            CardType::Ntag212 => Some(164),
+            CardType::Ntag213 => Some(180),
+            CardType::Ntag215 => Some(540),
+            CardType::Ntag216 => Some(924),
+            CardType::MifarePlus { memory_size, .. } => Some(memory_size),
+            CardType::MifareDesfire { memory_size, .. } if memory_size > 0 => Some(memory_size),
+            _ => None,
+        }
+    }
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Identify the type of a selected PICC, see [Card::identify](crate::Card::identify)
+    pub(crate) fn identify(&mut self, uid: &Uid) -> Result<CardType, Error<E>> {
+        let sak = uid.sak().as_byte() & 0x7F;
+        let atqa = uid.atqa();
+        // a ATQA of 0x0002 or 0x0042 indicates a 4K PICC
+        let is_4k = atqa.bit_frame_anticollision() == 0x02;
+
+        let card_type = match sak {
+            0x09 => return Ok(CardType::MifareMini),
+            0x08 => return Ok(CardType::MifareClassic1k),
+            0x18 => return Ok(CardType::MifareClassic4k),
+            0x10 => {
+                return Ok(CardType::MifarePlus {
+                    security_level: 2,
+                    memory_size: 2048,
+                })
+            }
+            0x11 => {
+                return Ok(CardType::MifarePlus {
+                    security_level: 2,
+                    memory_size: 4096,
+                })
+            }
+            0x01 => return Ok(CardType::Tnp3xxx),
+            0x40 => return Ok(CardType::Iso18092),
+            0x00 => self.identify_ultralight()?,
+            _ if sak & 0x20 != 0 => self.identify_iso_dep(sak, is_4k)?,
+            _ => return Ok(CardType::Unknown),
+        };
+
+        self.reactivate(uid)?;
+        Ok(card_type)
+    }
+
+    fn identify_ultralight(&mut self) -> Result<CardType, Error<E>> {
+        let version: [u8; 8] = match self.transceive_crc::<10>(&[GET_VERSION], true) {
+            Ok(rx) if rx.valid_bytes == 8 && rx.valid_bits == 0 => {
+                rx.buffer[..8].try_into().unwrap()
+            }
+            Err(e @ Error::Crc(_)) => return Err(e),
+            Err(Error::Spi(e)) => return Err(Error::Spi(e)),
+            // NAK or no response: MIFARE Ultralight or Ultralight C,
+            // the PICC went back to IDLE so it has to be selected again
+            _ => {
+                self.reactivate_any()?;
+                return self.identify_ultralight_c();
+            }
+        };
+
+        // fixed header, vendor ID, product type, subtype, major and minor product version,
+        // storage size, protocol type
+        Ok(match version {
+            [_, VENDOR_NXP, 0x03, _, _, _, storage, _] => CardType::MifareUltralightEv1 {
+                memory_size: match storage {
+                    0x0B => 80,
+                    _ => 164,
+                },
+            },
+            [_, VENDOR_NXP, 0x04, _, _, _, 0x0B, _] => CardType::Ntag210,
+            [_, VENDOR_NXP, 0x04, _, _, _, 0x0E, _] => CardType::Ntag212,
+            [_, VENDOR_NXP, 0x04, _, _, _, 0x0F, _] => CardType::Ntag213,
+            [_, VENDOR_NXP, 0x04, _, _, _, 0x11, _] => CardType::Ntag215,
+            [_, VENDOR_NXP, 0x04, _, _, _, 0x13, _] => CardType::Ntag216,
+            [_, VENDOR_NXP, ..] => CardType::UnknownNxp { version },
+            _ => CardType::Unknown,
+        })
+    }
+
+    /// A MIFARE Ultralight C answers the start of the 3DES authentication with
+    /// the additional frame status and the encrypted RndB.
+    fn identify_ultralight_c(&mut self) -> Result<CardType, Error<E>> {
+        match self.transceive_crc::<11>(&[ULC_AUTHENTICATE, 0x00], true) {
+            Ok(rx) if rx.valid_bytes == 9 && rx.buffer[0] == ADDITIONAL_FRAME => {
+                Ok(CardType::MifareUltralightC)
+            }
+            Err(Error::Spi(e)) => Err(Error::Spi(e)),
+            _ => Ok(CardType::MifareUltralight),
+        }
+    }
+
+    fn identify_iso_dep(&mut self, sak: u8, is_4k: bool) -> Result<CardType, Error<E>> {
+        let mut iso_dep = self.iso_dep()?;
+
+        // native GET_VERSION, wrapped in an ISO/IEC 7816-4 APDU
+        let mut rx = [0u8; 9];
+        let version = match iso_dep.exchange(&[0x90, GET_VERSION, 0x00, 0x00, 0x00], &mut rx) {
+            Ok(9) if rx[7] == 0x91 && rx[8] == ADDITIONAL_FRAME => {
+                Some([rx[0], rx[1], rx[2], rx[3], rx[4], rx[5], rx[6], 0x00])
+            }
+            Err(Error::Spi(e)) => return Err(Error::Spi(e)),
+            _ => None,
+        };
+        let historical_bytes = iso_dep.ats().historical_bytes();
+        let historical_bytes_desfire = historical_bytes == [0x80];
+        let historical_bytes_plus = historical_bytes.starts_with(&[0xC1, 0x05, 0x2F, 0x2F]);
+
+        if let Err(Error::Spi(e)) = iso_dep.deselect() {
+            return Err(Error::Spi(e));
+        }
+
+        let storage_size = |storage: u8| {
+            // the most significant 7 bits are n in 2^n,
+            // the least significant bit indicates the size is between 2^n and 2^(n+1)
+            1usize << (storage >> 1)
+        };
+
+        Ok(match version {
+            Some([VENDOR_NXP, 0x01, _, major, _, storage, _, _]) => CardType::MifareDesfire {
+                version: match major {
+                    0x00 => DesfireVersion::D40,
+                    0x01 => DesfireVersion::Ev1,
+                    0x12 => DesfireVersion::Ev2,
+                    0x30 | 0x33 => DesfireVersion::Ev3,
+                    other => DesfireVersion::Other(other),
+                },
+                memory_size: storage_size(storage),
+            },
+            Some([VENDOR_NXP, 0x02, _, _, _, storage, _, _]) => CardType::MifarePlus {
+                security_level: 3,
+                memory_size: storage_size(storage),
+            },
+            Some(version @ [VENDOR_NXP, ..]) => CardType::UnknownNxp { version },
+            _ if historical_bytes_desfire => CardType::MifareDesfire {
+                version: DesfireVersion::Unknown,
+                memory_size: 0,
+            },
+            // MIFARE Plus S and X don't support GET_VERSION, the ATQA indicates the size
+            _ if historical_bytes_plus => CardType::MifarePlus {
+                security_level: 3,
+                memory_size: if is_4k { 4096 } else { 2048 },
+            },
+            _ => match sak {
+                0x28 => CardType::SmartMx1k,
+                0x38 => CardType::SmartMx4k,
+                _ => CardType::Iso14443_4,
+            },
+        })
+    }
+
+    /// Wake up and select the PICC again, it has to be the same PICC as before
+    pub(crate) fn reactivate(&mut self, uid: &Uid) -> Result<(), Error<E>> {
+        let selected = self.reactivate_any()?;
+        if selected.as_bytes() != uid.as_bytes() {
+            return Err(Error::Collision(Context::default()));
+        }
+        Ok(())
+    }
+
+    fn reactivate_any(&mut self) -> Result<Uid, Error<E>> {
+        // make sure the PICC is in the HALT or IDLE state, only WUPA wakes up both
+        if let Err(Error::Spi(e)) = self.hlta() {
+            return Err(Error::Spi(e));
+        }
+        let atqa = self.wupa()?;
+        self.anticollision(&atqa)
+    }
+}
@@ -0,0 +1,325 @@
+//! ISO/IEC 14443-4 half-duplex block transmission protocol (ISO-DEP).
+//!
+//! PICCs that indicate ISO/IEC 14443-4 compliance in their SAK (eg MIFARE DESFire,
+//! MIFARE Plus, contactless smart cards) are activated with a RATS command.
+//! Afterwards, application data is exchanged using I-blocks, with support for
+//! block chaining in both directions and waiting time extensions requested by the PICC.
+//!
+//! Right after activation, a higher bit rate can be negotiated with [IsoDep::pps],
+//! if the PICC supports it according to its ATS.
+//!
+//! ```ignore
+//! let mut card = mfrc522.select(&atqa)?;
+//! if card.uid().is_compliant() {
+//!     let mut iso_dep = card.iso_dep()?;
+//!     let n = iso_dep.exchange(&apdu, &mut response)?;
+//! }
+//! ```
+
+use heapless::Vec;
+
+use crate::error::{Context, Error};
+use crate::picc;
+use crate::rf::BitRate;
+use crate::{spi, Initialized, Mfrc522, WithNssDelay, DEFAULT_TIMEOUT};
+
+/// Frame Size for proximity coupling Device Integer, announced in RATS
+const FSDI: u8 = 8;
+/// Frame Size for proximity coupling Device, frames longer than the 64 byte FIFO buffer
+/// of the MFRC522 are streamed through it
+const FSD: usize = 256;
+
+/// I-block PCB, used to convey information for use by the application layer
+const I_BLOCK: u8 = 0x02;
+/// R(ACK) PCB, used to convey positive acknowledgement
+const R_ACK: u8 = 0xA2;
+/// S(DESELECT) PCB
+const S_DESELECT: u8 = 0xC2;
+/// S(WTX) PCB, waiting time extension
+const S_WTX: u8 = 0xF2;
+/// PPSS, start byte of the Protocol and Parameter Selection request (CID 0)
+const PPSS: u8 = 0xD0;
+/// PPS0, indicating the presence of PPS1
+const PPS0_PPS1: u8 = 0x11;
+/// PCB bit indicating chaining
+const CHAINING: u8 = 1 << 4;
+/// PCB bit containing the block number
+const BLOCK_NUMBER: u8 = 1 << 0;
+
+/// Answer To Select, returned by the PICC in response to RATS
+#[derive(Debug, Clone)]
+pub struct Ats {
+    /// The ATS bytes, starting with the length byte TL and without CRC
+    bytes: Vec<u8, FSD>,
+}
+
+impl Ats {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Format byte, if present
+    fn t0(&self) -> Option<u8> {
+        self.bytes.get(1).copied()
+    }
+
+    /// Returns the value of the given interface byte (TA = 0, TB = 1, TC = 2), if present
+    fn interface_byte(&self, idx: u8) -> Option<u8> {
+        let t0 = self.t0()?;
+        if t0 & (1 << (4 + idx)) == 0 {
+            return None;
+        }
+        let preceding = (t0 >> 4) & ((1 << idx) - 1);
+        self.bytes.get(2 + preceding.count_ones() as usize).copied()
+    }
+
+    /// Frame Size for proximity Card Integer
+    pub fn fsci(&self) -> u8 {
+        // default value when T0 is absent
+        self.t0().map(|t0| t0 & 0x0F).unwrap_or(2)
+    }
+
+    /// Maximum frame size accepted by the PICC, in bytes
+    pub fn fsc(&self) -> usize {
+        match self.fsci() {
+            0 => 16,
+            1 => 24,
+            2 => 32,
+            3 => 40,
+            4 => 48,
+            5 => 64,
+            6 => 96,
+            7 => 128,
+            _ => 256,
+        }
+    }
+
+    /// Is the PICC able to transmit at `rx` and to receive at `tx` (TA(1) interface byte).
+    ///
+    /// Without TA(1), only 106 kbit/s is supported in both directions.
+    pub fn supports_bit_rate(&self, tx: BitRate, rx: BitRate) -> bool {
+        let ta = self.interface_byte(0).unwrap_or(0);
+        // bit 8: only the same bit rate in both directions
+        if ta & 0x80 != 0 && tx != rx {
+            return false;
+        }
+        // bits 7 to 5: PICC to PCD (DS), bits 3 to 1: PCD to PICC (DR)
+        let supported = |ta: u8, bit_rate: BitRate| match bit_rate {
+            BitRate::Kbps106 => true,
+            _ => ta & (1 << (bit_rate.bits() - 1)) != 0,
+        };
+        supported(ta, tx) && supported(ta >> 4, rx)
+    }
+
+    /// Frame Waiting time Integer
+    pub fn fwi(&self) -> u8 {
+        self.interface_byte(1).map(|tb| tb >> 4).unwrap_or(4)
+    }
+
+    /// Start-up Frame Guard time Integer
+    pub fn sfgi(&self) -> u8 {
+        self.interface_byte(1).map(|tb| tb & 0x0F).unwrap_or(0)
+    }
+
+    /// Historical bytes, used by the card to give general information
+    pub fn historical_bytes(&self) -> &[u8] {
+        let start = match self.t0() {
+            Some(t0) => 2 + ((t0 >> 4) & 0b111).count_ones() as usize,
+            None => 1,
+        };
+        self.bytes.get(start..).unwrap_or(&[])
+    }
+}
+
+/// Handle to a PICC that has been activated for ISO/IEC 14443-4 communication
+///
+/// Created with [Card::iso_dep](crate::Card::iso_dep).
+pub struct IsoDep<'a, SPI, NSS, D> {
+    mfrc522: &'a mut Mfrc522<SPI, NSS, D, Initialized>,
+    ats: Ats,
+    block_number: u8,
+    /// Frame waiting time, in timer ticks
+    fwt: u16,
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Sends a Request for Answer To Select to a selected PICC,
+    /// see [Card::iso_dep](crate::Card::iso_dep).
+    pub(crate) fn iso_dep(&mut self) -> Result<IsoDep<'_, SPI, NSS, D>, Error<E>> {
+        // CID is not used, so it is always 0
+        let frame = transceive_frame(self, &[picc::Command::RAtS as u8, FSDI << 4])?;
+        let tl = *frame
+            .first()
+            .ok_or(Error::IncompleteFrame(Context::default()))? as usize;
+        if tl != frame.len() {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+
+        let ats = Ats { bytes: frame };
+        let fwt = fwt_ticks(ats.fwi());
+        Ok(IsoDep {
+            mfrc522: self,
+            ats,
+            block_number: 0,
+            fwt,
+        })
+    }
+}
+
+impl<'a, E, SPI, NSS, D> IsoDep<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// The ATS returned by the PICC during activation
+    pub fn ats(&self) -> &Ats {
+        &self.ats
+    }
+
+    /// Send `tx` to the PICC and store the response in `rx`.
+    ///
+    /// Data that does not fit in a single frame is sent and received using block chaining.
+    /// Returns the number of bytes of the response.
+    pub fn exchange(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<usize, Error<E>> {
+        self.mfrc522.set_timeout(self.fwt).map_err(Error::Spi)?;
+        let result = self.exchange_blocks(tx, rx);
+        self.mfrc522
+            .set_timeout(DEFAULT_TIMEOUT)
+            .map_err(Error::Spi)?;
+        result
+    }
+
+    /// Negotiate the bit rates used to transmit to the PICC (`tx`) and to receive from the
+    /// PICC (`rx`) with a Protocol and Parameter Selection request, and switch the MFRC522 to them.
+    ///
+    /// This is only possible right after activation, before any block has been exchanged.
+    /// Returns [Error::Unsupported] if the ATS does not list the bit rates as supported.
+    /// The MFRC522 returns to 106 kbit/s on the next REQA or WUPA.
+    pub fn pps(&mut self, tx: BitRate, rx: BitRate) -> Result<(), Error<E>> {
+        if !self.ats.supports_bit_rate(tx, rx) {
+            return Err(Error::Unsupported);
+        }
+
+        // PPS1: DSI (PICC to PCD) in bits 4 and 3, DRI (PCD to PICC) in bits 2 and 1
+        let pps1 = (rx.bits() << 2) | tx.bits();
+        let frame = transceive_frame(self.mfrc522, &[PPSS, PPS0_PPS1, pps1])?;
+        if frame.as_slice() != [PPSS] {
+            return Err(Error::InvalidBlock);
+        }
+
+        // the PICC switches after its response, the next frame uses the new bit rates
+        self.mfrc522.set_bit_rate(tx, rx)
+    }
+
+    /// Deactivate the PICC, it will enter the HALT state.
+    pub fn deselect(self) -> Result<(), Error<E>> {
+        let frame = transceive_frame(self.mfrc522, &[S_DESELECT])?;
+        if frame.first() != Some(&S_DESELECT) {
+            return Err(Error::InvalidBlock);
+        }
+        Ok(())
+    }
+
+    fn exchange_blocks(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<usize, Error<E>> {
+        // PCB + INF + CRC_A has to fit in the frame size of both the PICC and the MFRC522
+        let max_inf = self.ats.fsc().min(FSD) - 3;
+
+        let mut offset = 0;
+        let mut frame = loop {
+            let end = tx.len().min(offset + max_inf);
+            let chaining = end < tx.len();
+            let mut pcb = I_BLOCK | self.block_number;
+            if chaining {
+                pcb |= CHAINING;
+            }
+
+            let frame = self.transceive_block(pcb, &tx[offset..end])?;
+            if !chaining {
+                break frame;
+            }
+
+            // each chained I-block is acknowledged by the PICC
+            if frame.len() != 1 || frame[0] != R_ACK | self.block_number {
+                return Err(Error::InvalidBlock);
+            }
+            self.block_number ^= BLOCK_NUMBER;
+            offset = end;
+        };
+
+        let mut len = 0;
+        loop {
+            let pcb = frame[0];
+            if pcb & 0xE2 != I_BLOCK || pcb & BLOCK_NUMBER != self.block_number {
+                return Err(Error::InvalidBlock);
+            }
+            self.block_number ^= BLOCK_NUMBER;
+
+            let inf = &frame[1..];
+            rx.get_mut(len..len + inf.len())
+                .ok_or(Error::NoRoom)?
+                .copy_from_slice(inf);
+            len += inf.len();
+
+            if pcb & CHAINING == 0 {
+                return Ok(len);
+            }
+            frame = self.transceive_block(R_ACK | self.block_number, &[])?;
+        }
+    }
+
+    /// Send a single block and return the response block (without CRC),
+    /// handling any waiting time extension requests of the PICC.
+    fn transceive_block(&mut self, pcb: u8, inf: &[u8]) -> Result<Vec<u8, FSD>, Error<E>> {
+        let mut tx = Vec::<u8, FSD>::new();
+        tx.push(pcb).map_err(|_| Error::NoRoom)?;
+        tx.extend_from_slice(inf).map_err(|_| Error::NoRoom)?;
+
+        let mut frame = transceive_frame(self.mfrc522, &tx)?;
+        while frame.first().map(|pcb| pcb & 0xF7) == Some(S_WTX) {
+            let wtxm = frame
+                .get(1)
+                .ok_or(Error::IncompleteFrame(Context::default()))?
+                & 0x3F;
+            let ticks = (self.fwt as u32 * wtxm.max(1) as u32).min(u16::MAX as u32);
+            self.mfrc522.set_timeout(ticks as u16).map_err(Error::Spi)?;
+            let result = transceive_frame(self.mfrc522, &[S_WTX, wtxm]);
+            self.mfrc522.set_timeout(self.fwt).map_err(Error::Spi)?;
+            frame = result?;
+        }
+
+        if frame.is_empty() {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+        Ok(frame)
+    }
+}
+
+/// Send a frame with CRC_A appended and return the received frame after verifying its CRC_A.
+fn transceive_frame<E, SPI, NSS, D>(
+    mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>,
+    data: &[u8],
+) -> Result<Vec<u8, FSD>, Error<E>>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    let rx = mfrc522.transceive_crc::<FSD>(data, true)?;
+    if rx.valid_bits != 0 || rx.valid_bytes < 1 {
+        return Err(Error::IncompleteFrame(Context::default()));
+    }
+
+    Ok(Vec::from_slice(&rx.buffer[..rx.valid_bytes]).unwrap())
+}
+
+/// Convert the frame waiting time integer to timer ticks (25μs):
+///     FWT = (256 * 16 / fc) * 2^FWI
+/// with one tick being 339 periods of the 13.56 MHz carrier frequency fc.
+/// The result is never shorter than the default timeout.
+fn fwt_ticks(fwi: u8) -> u16 {
+    let ticks = ((4096u32 << fwi.min(14)) / 339) + 1;
+    ticks.clamp(DEFAULT_TIMEOUT as u32, u16::MAX as u32) as u16
+}
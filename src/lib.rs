@@ -14,15 +14,20 @@
 //! However, currently only SPI communication is implemented in this crate.
 //!
 //! # Quickstart
-//! ```rust
+//! ```no_run
+//! # use linux_embedded_hal::{Pin, Spidev};
+//! # use mfrc522::Mfrc522;
+//! # fn main() -> Result<(), mfrc522::error::Error<std::io::Error>> {
 //! // create an SPI device that implements the embedded-hal `spi::Transfer` and `spi::Write` traits
-//! let spi = spi::Spi::new(/* */);
+//! let spi = Spidev::open("/dev/spidev0.0")?;
 //! // create a GPIO output for chip-select control
-//! let cs = gpio::Output::new(/* */);
+//! let cs = Pin::new(22);
 //! let mut mfrc522 = Mfrc522::new(spi).with_nss(cs).init()?;
 //!
-//! // The reported version is expected to be 0x91 or 0x92
-//! let mfrc522_version = mfrc522.version()?;
+//! // The reported version is expected to be 0x91 or 0x92, see the `chip` module
+//! let chip = mfrc522.chip_info()?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! Take a look at [Mfrc522] for information on the available functions.
@@ -35,9 +40,34 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[macro_use]
+mod logging;
+mod card;
+pub mod chip;
+pub mod crc;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "crypto")]
+pub mod desfire;
+#[cfg(feature = "crypto")]
+pub mod diversify;
+pub mod emv;
 pub mod error;
+pub mod identify;
+pub mod iso_dep;
+pub mod monitor;
 mod picc;
+pub mod power;
+pub mod random;
+pub mod raw;
 mod register;
+pub mod retry;
+pub mod rf;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod tlv;
+pub mod trace;
+mod ultralight;
 mod util;
 
 use embedded_hal as hal;
@@ -46,14 +76,37 @@ use hal::digital::v2::OutputPin;
 
 use heapless::Vec;
 
-use error::Error;
+pub use card::{AuthenticatedSector, Card};
+use error::{Context, Error, Nak, Operation};
+pub use picc::{Sak, Type};
+pub use register::Register;
 use register::*;
+use retry::RetryPolicy;
+use rf::{BitRate, RfConfig};
+pub use ultralight::{UltralightCKey, ULC_KEYSIZE};
 use util::{DummyDelay, DummyNSS, Sealed};
 
+/// 4 bit answer of a MIFARE PICC acknowledging a command
 const MIFARE_ACK: u8 = 0xA;
 const MIFARE_KEYSIZE: usize = 6;
 pub type MifareKey = [u8; MIFARE_KEYSIZE];
 
+/// Default timeout when communicating with a PICC, in timer ticks of 25μs (25ms)
+const DEFAULT_TIMEOUT: u16 = 1000;
+/// Size of the FIFO buffer of the MFRC522
+const FIFO_SIZE: usize = 64;
+/// FIFO level at which the LoAlert and HiAlert warnings are raised, so the FIFO buffer can be
+/// refilled and drained while transmitting and receiving frames longer than the FIFO buffer
+const WATER_LEVEL: u8 = 32;
+/// Maximum size of a frame, including the CRC_A (FSD of ISO/IEC 14443-4)
+const MAX_FRAME_SIZE: usize = 256;
+
+/// UID of a selected PICC, together with the SAK and ATQA it answered with.
+///
+/// UIDs are compared, ordered and hashed by their bytes only, so they can be used as keys
+/// in maps or sets. [Display](core::fmt::Display) formats the bytes as uppercase hex.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Uid {
     /// Single sized UID, 4 bytes long
     Single(GenericUid<4>),
@@ -71,8 +124,89 @@ impl Uid {
             Uid::Triple(u) => u.as_bytes(),
         }
     }
+
+    /// The ATQA the PICC answered with before it was selected
+    pub fn atqa(&self) -> &AtqA {
+        match &self {
+            Uid::Single(u) => u.atqa(),
+            Uid::Double(u) => u.atqa(),
+            Uid::Triple(u) => u.atqa(),
+        }
+    }
+
+    /// The SAK the PICC answered with after it was selected
+    pub fn sak(&self) -> &Sak {
+        match &self {
+            Uid::Single(u) => u.sak(),
+            Uid::Double(u) => u.sak(),
+            Uid::Triple(u) => u.sak(),
+        }
+    }
+
+    /// The PICC type, as indicated by the SAK.
+    ///
+    /// Use [identify](Card::identify) for a more detailed identification.
+    pub fn card_type(&self) -> Type {
+        self.sak().card_type()
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.sak().is_compliant()
+    }
+}
+
+/// A slice could not be converted to a UID, because it is not 4, 7 or 10 bytes long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidUidLength(pub usize);
+
+/// Create a UID from its bytes, the SAK and ATQA are zero
+impl TryFrom<&[u8]> for Uid {
+    type Error = InvalidUidLength;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        match bytes.len() {
+            4 => Ok(Uid::Single(GenericUid::try_from(bytes)?)),
+            7 => Ok(Uid::Double(GenericUid::try_from(bytes)?)),
+            10 => Ok(Uid::Triple(GenericUid::try_from(bytes)?)),
+            n => Err(InvalidUidLength(n)),
+        }
+    }
+}
+
+impl PartialEq for Uid {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Uid {}
+
+impl PartialOrd for Uid {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Uid {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl core::hash::Hash for Uid {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
 }
 
+impl core::fmt::Display for Uid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_hex(f, self.as_bytes())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GenericUid<const T: usize>
 where
     [u8; T]: Sized,
@@ -80,14 +214,17 @@ where
     /// The UID can have 4, 7 or 10 bytes.
     bytes: [u8; T],
     /// The SAK (Select acknowledge) byte returned from the PICC after successful selection.
-    sak: picc::Sak,
+    sak: Sak,
+    /// The ATQA (Answer To reQuest type A) returned from the PICC before selection.
+    atqa: AtqA,
 }
 
 impl<const T: usize> GenericUid<T> {
-    pub fn new(bytes: [u8; T], sak_byte: u8) -> Self {
+    pub fn new(bytes: [u8; T], sak_byte: u8, atqa: AtqA) -> Self {
         Self {
             bytes,
-            sak: picc::Sak::from(sak_byte),
+            sak: Sak::from(sak_byte),
+            atqa,
         }
     }
 
@@ -95,16 +232,135 @@ impl<const T: usize> GenericUid<T> {
         &self.bytes
     }
 
+    pub fn atqa(&self) -> &AtqA {
+        &self.atqa
+    }
+
+    pub fn sak(&self) -> &Sak {
+        &self.sak
+    }
+
+    /// The PICC type, as indicated by the SAK
+    pub fn card_type(&self) -> Type {
+        self.sak.card_type()
+    }
+
     pub fn is_compliant(&self) -> bool {
         self.sak.is_compliant()
     }
 }
 
+/// Create a UID from its bytes, the SAK and ATQA are zero
+impl<const T: usize> TryFrom<&[u8]> for GenericUid<T> {
+    type Error = InvalidUidLength;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| InvalidUidLength(bytes.len()))?;
+        Ok(GenericUid::new(bytes, 0, AtqA::from([0, 0])))
+    }
+}
+
+impl<const T: usize> PartialEq for GenericUid<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<const T: usize> Eq for GenericUid<T> {}
+
+impl<const T: usize> PartialOrd for GenericUid<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const T: usize> Ord for GenericUid<T> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl<const T: usize> core::hash::Hash for GenericUid<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state)
+    }
+}
+
+impl<const T: usize> core::fmt::Display for GenericUid<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_hex(f, &self.bytes)
+    }
+}
+
+fn write_hex(f: &mut core::fmt::Formatter<'_>, bytes: &[u8]) -> core::fmt::Result {
+    for byte in bytes {
+        write!(f, "{:02X}", byte)?;
+    }
+    Ok(())
+}
+
 /// Answer To reQuest type A
+///
+/// Returned by [reqa](Mfrc522::reqa) and [wupa](Mfrc522::wupa),
+/// the coding is defined in ISO/IEC 14443-3 (6.5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AtqA {
     bytes: [u8; 2],
 }
 
+/// Size of the UID, as indicated in the ATQA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UidSize {
+    /// 4 bytes, cascade level 1
+    Single,
+    /// 7 bytes, cascade level 2
+    Double,
+    /// 10 bytes, cascade level 3
+    Triple,
+}
+
+impl From<[u8; 2]> for AtqA {
+    fn from(bytes: [u8; 2]) -> Self {
+        AtqA { bytes }
+    }
+}
+
+impl AtqA {
+    /// The ATQA bytes, in the order they were received (least significant byte first)
+    pub fn as_bytes(&self) -> &[u8; 2] {
+        &self.bytes
+    }
+
+    /// UID size bit frame, `None` if the PICC uses the RFU value
+    pub fn uid_size(&self) -> Option<UidSize> {
+        match self.bytes[0] >> 6 {
+            0b00 => Some(UidSize::Single),
+            0b01 => Some(UidSize::Double),
+            0b10 => Some(UidSize::Triple),
+            _ => None,
+        }
+    }
+
+    /// Bit frame anticollision bits (b1 to b5 of the first byte)
+    pub fn bit_frame_anticollision(&self) -> u8 {
+        self.bytes[0] & 0x1F
+    }
+
+    /// Does the PICC support bit frame anticollision:
+    /// exactly one of the bit frame anticollision bits has to be set.
+    pub fn supports_anticollision(&self) -> bool {
+        self.bit_frame_anticollision().count_ones() == 1
+    }
+
+    /// Proprietary coding (b1 to b4 of the second byte)
+    pub fn proprietary_coding(&self) -> u8 {
+        self.bytes[1] & 0x0F
+    }
+}
+
 /// Implemented by the different states of the MFRC522 driver.
 ///
 /// This trait cannot be implemented outside of this crate.
@@ -120,11 +376,36 @@ impl State for Initialized {}
 impl Sealed for Uninitialized {}
 impl Sealed for Initialized {}
 
+/// How the CRC_A is appended to transmitted frames and verified for received frames
+///
+/// The CRC coprocessor is used by default, as in the previous versions of the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CrcMode {
+    /// Use the CRC coprocessor of the MFRC522 (CalcCRC command) for each frame,
+    /// which takes a few extra SPI transactions per frame
+    #[default]
+    Coprocessor,
+    /// Let the MFRC522 append and check the CRC_A while transmitting and receiving
+    /// (TxModeReg TxCRCEn and RxModeReg RxCRCEn), a wrong CRC_A is reported as [Error::Crc]
+    Hardware,
+    /// Calculate the CRC_A in software
+    Software,
+}
+
 /// MFRC522 driver
 pub struct Mfrc522<SPI, NSS, D, S: State> {
     spi: SPI,
     nss: NSS,
     delay: D,
+    rf_config: RfConfig,
+    /// The energy carrier is switched on, on the drivers enabled in `rf_config`
+    antenna: bool,
+    crc_mode: CrcMode,
+    retry_policy: RetryPolicy,
+    /// Last value written to TxModeReg
+    tx_mode: u8,
+    /// Last value written to RxModeReg
+    rx_mode: u8,
     state: core::marker::PhantomData<S>,
 }
 
@@ -147,6 +428,12 @@ where
             spi,
             nss: DummyNSS {},
             delay: DummyDelay {},
+            rf_config: RfConfig::default(),
+            antenna: true,
+            crc_mode: CrcMode::default(),
+            retry_policy: RetryPolicy::default(),
+            tx_mode: 0,
+            rx_mode: 0,
             state: core::marker::PhantomData,
         }
     }
@@ -163,6 +450,12 @@ impl<SPI, D> Mfrc522<SPI, DummyNSS, D, Uninitialized> {
             spi: self.spi,
             nss,
             delay: self.delay,
+            rf_config: self.rf_config,
+            antenna: self.antenna,
+            crc_mode: self.crc_mode,
+            retry_policy: self.retry_policy,
+            tx_mode: self.tx_mode,
+            rx_mode: self.rx_mode,
             state: core::marker::PhantomData,
         }
     }
@@ -183,11 +476,38 @@ impl<SPI, NSS> Mfrc522<SPI, NSS, DummyDelay, Uninitialized> {
             spi: self.spi,
             nss: self.nss,
             delay,
+            rf_config: self.rf_config,
+            antenna: self.antenna,
+            crc_mode: self.crc_mode,
+            retry_policy: self.retry_policy,
+            tx_mode: self.tx_mode,
+            rx_mode: self.rx_mode,
             state: core::marker::PhantomData,
         }
     }
 }
 
+impl<SPI, NSS, D> Mfrc522<SPI, NSS, D, Uninitialized> {
+    /// Set the receiver and antenna driver configuration that is applied by
+    /// [init](Mfrc522::init), instead of the reset values.
+    pub fn with_rf_config(self, rf_config: RfConfig) -> Self {
+        Mfrc522 { rf_config, ..self }
+    }
+
+    /// Set how the CRC_A of frames is calculated and verified, see [CrcMode].
+    pub fn with_crc_mode(self, crc_mode: CrcMode) -> Self {
+        Mfrc522 { crc_mode, ..self }
+    }
+
+    /// Set how block operations are retried after transient errors, see [RetryPolicy].
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Mfrc522 {
+            retry_policy,
+            ..self
+        }
+    }
+}
+
 impl<SPI, NSS, D, S: State> Mfrc522<SPI, NSS, D, S> {
     /// Release the underlying SPI device and NSS pin
     pub fn release(self) -> (SPI, NSS) {
@@ -206,35 +526,18 @@ where
     /// This needs to be called before you can do any other operation.
     pub fn init(mut self) -> Result<Mfrc522<SPI, NSS, D, Initialized>, E> {
         self.reset()?;
-        self.write(Register::TxModeReg, 0x00)?;
-        self.write(Register::RxModeReg, 0x00)?;
-        // Reset ModWidthReg to default value
-        self.write(Register::ModWidthReg, 0x26)?;
-
-        // Configure the timer, so we can get a timeout if something goes wrong
-        // when communicating with a PICC:
-        // - Set timer to start automatically at the end of the transmission
-        self.write(Register::TModeReg, 0x80)?;
-        // - Configure the prescaler to determine the timer frequency:
-        //   f_timer = 13.56 MHz / (2 * TPreScaler + 1)
-        //   so for 40kHz frequency (25μs period), TPreScaler = 0x0A9
-        self.write(Register::TPrescalerReg, 0xA9)?;
-        // - Set the reload value to determine the timeout
-        //   for a 25ms timeout, we need a value of 1000 = 0x3E8
-        self.write(Register::TReloadRegHigh, 0x03)?;
-        self.write(Register::TReloadRegLow, 0xE8)?;
-
-        // TODO: may not be necessary?
-        self.write(Register::TxASKReg, FORCE_100_ASK)?;
-        // Set preset value of CRC coprocessor according to ISO 14443-3 part 6.2.4
-        self.write(Register::ModeReg, (0x3f & (!0b11)) | 0b01)?;
-        // Enable antenna
-        self.rmw(Register::TxControlReg, |b| b | 0b11)?;
+        self.configure()?;
 
         Ok(Mfrc522 {
             spi: self.spi,
             nss: self.nss,
             delay: self.delay,
+            rf_config: self.rf_config,
+            antenna: self.antenna,
+            crc_mode: self.crc_mode,
+            retry_policy: self.retry_policy,
+            tx_mode: self.tx_mode,
+            rx_mode: self.rx_mode,
             state: core::marker::PhantomData,
         })
     }
@@ -247,11 +550,20 @@ where
     Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
 {
     /// Sends a REQuest type A to nearby PICCs
+    ///
+    /// The bit rate is set back to 106 kbit/s first.
     pub fn reqa(&mut self) -> Result<AtqA, Error<E>> {
+        // PICCs that were switched to a higher bit rate return to 106 kbit/s when they
+        // lose the field or are halted, so polling always starts at 106 kbit/s
+        self.write_bit_rate(BitRate::Kbps106, BitRate::Kbps106)
+            .map_err(Error::Spi)?;
+
         // NOTE REQA is a short frame (7 bits)
-        let fifo_data = self.transceive(&[picc::Command::ReqA as u8], 7, 0)?;
+        let fifo_data = self
+            .transceive(&[picc::Command::ReqA as u8], 7, 0)
+            .map_err(|e| e.with_operation(Operation::Reqa))?;
         if fifo_data.valid_bytes != 2 || fifo_data.valid_bits != 0 {
-            Err(Error::IncompleteFrame)
+            Err(Error::IncompleteFrame(Context::default()).with_operation(Operation::Reqa))
         } else {
             Ok(AtqA {
                 bytes: fifo_data.buffer,
@@ -260,11 +572,20 @@ where
     }
 
     /// Sends a Wake UP type A to nearby PICCs
+    ///
+    /// The bit rate is set back to 106 kbit/s first.
     pub fn wupa(&mut self) -> Result<AtqA, Error<E>> {
+        // PICCs that were switched to a higher bit rate return to 106 kbit/s when they
+        // lose the field or are halted, so polling always starts at 106 kbit/s
+        self.write_bit_rate(BitRate::Kbps106, BitRate::Kbps106)
+            .map_err(Error::Spi)?;
+
         // NOTE WUPA is a short frame (7 bits)
-        let fifo_data = self.transceive(&[picc::Command::WupA as u8], 7, 0)?;
+        let fifo_data = self
+            .transceive(&[picc::Command::WupA as u8], 7, 0)
+            .map_err(|e| e.with_operation(Operation::Wupa))?;
         if fifo_data.valid_bytes != 2 || fifo_data.valid_bits != 0 {
-            Err(Error::IncompleteFrame)
+            Err(Error::IncompleteFrame(Context::default()).with_operation(Operation::Wupa))
         } else {
             Ok(AtqA {
                 bytes: fifo_data.buffer,
@@ -274,27 +595,34 @@ where
 
     /// Sends command to enter HALT state
     pub fn hlta(&mut self) -> Result<(), Error<E>> {
-        let mut buffer: [u8; 4] = [picc::Command::HltA as u8, 0, 0, 0];
-        let crc = self.calculate_crc(&buffer[..2])?;
-        buffer[2..].copy_from_slice(&crc);
-
         // The standard says:
         //   If the PICC responds with any modulation during a period of 1 ms
         //   after the end of the frame containing the HLTA command,
         //   this response shall be interpreted as 'not acknowledge'.
         // We interpret that this way: only Error::Timeout is a success.
-        match self.transceive::<0>(&buffer, 0, 0) {
-            Err(Error::Timeout) => Ok(()),
-            Ok(_) => Err(Error::Nak),
-            Err(e) => Err(e),
+        match self.transceive_crc::<0>(&[picc::Command::HltA as u8, 0], false) {
+            Err(Error::Timeout(_)) => Ok(()),
+            Ok(_) => {
+                Err(Error::Nak(Nak::Unexpected, Context::default()).with_operation(Operation::Hlta))
+            }
+            Err(e) => Err(e.with_operation(Operation::Hlta)),
         }
     }
 
     /// Selects a PICC in the READY state
+    ///
+    /// The returned [Card] borrows the driver for as long as the PICC is in use,
+    /// the PICC is halted when it is dropped.
+    pub fn select(&mut self, atqa: &AtqA) -> Result<Card<'_, SPI, NSS, D>, Error<E>> {
+        let uid = self.anticollision(atqa)?;
+        Ok(Card::new(self, uid))
+    }
+
+    /// Run the anticollision loop and select a PICC in the READY state
     // TODO add optional UID to select a specific PICC
-    pub fn select(&mut self, atqa: &AtqA) -> Result<Uid, Error<E>> {
+    fn anticollision(&mut self, atqa: &AtqA) -> Result<Uid, Error<E>> {
         // check for proprietary anticollision
-        if (atqa.bytes[0] & 0b00011111).count_ones() != 1 {
+        if !atqa.supports_anticollision() {
             return Err(Error::Proprietary);
         }
 
@@ -314,8 +642,9 @@ where
                 _ => unreachable!(),
             };
             let mut known_bits = 0;
-            let mut tx = [0u8; 9];
+            let mut tx = [0u8; 7];
             tx[0] = cmd as u8;
+            let operation = Operation::Select(cascade_level + 1);
 
             // TODO: limit to 32 iterations (as spec dictates)
             'anticollision: loop {
@@ -327,26 +656,33 @@ where
                 // Tell transceive the only send `tx_last_bits` of the last byte
                 // and also to put the first received bit at location `tx_last_bits`.
                 // This makes it easier to append the received bits to the uid (in `tx`).
-                match self.transceive::<5>(&tx[0..end], tx_last_bits, tx_last_bits) {
-                    Ok(fifo_data) => {
+                let mut fifo_data = FifoData::<5>::new();
+                let result =
+                    self.transceive_into(&tx[0..end], tx_last_bits, tx_last_bits, &mut fifo_data);
+                match result {
+                    Ok(()) => {
                         fifo_data.copy_bits_to(&mut tx[2..=6], known_bits);
                         break 'anticollision;
                     }
-                    Err(Error::Collision) => {
+                    Err(Error::Collision(context)) => {
+                        let collision = Error::Collision(context).with_operation(operation);
                         let coll_reg = self.read(Register::CollReg).map_err(Error::Spi)?;
-                        if coll_reg & (1 << 5) != 0 {
-                            // CollPosNotValid
-                            return Err(Error::Collision);
+                        if coll_reg & COLL_POS_NOT_VALID != 0 {
+                            return Err(collision);
                         }
                         let mut coll_pos = coll_reg & 0x1F;
                         if coll_pos == 0 {
                             coll_pos = 32;
                         }
+                        debug!(
+                            "collision at bit {} of cascade level {}",
+                            coll_pos,
+                            cascade_level + 1
+                        );
                         if coll_pos < known_bits {
                             // No progress
-                            return Err(Error::Collision);
+                            return Err(collision);
                         }
-                        let fifo_data = self.fifo_data::<5>()?;
                         fifo_data.copy_bits_to(&mut tx[2..=6], known_bits);
                         known_bits = coll_pos;
 
@@ -357,30 +693,25 @@ where
                             1 + (known_bits / 8) as usize + if count != 0 { 1 } else { 0 };
                         tx[index] |= 1 << check_bit;
                     }
-                    Err(e) => return Err(e),
+                    Err(e) => return Err(e.with_operation(operation)),
                 }
             }
 
             // send select
             tx[1] = 0x70; // NVB: 7 valid bytes
-            tx[6] = tx[2] ^ tx[3] ^ tx[4] ^ tx[5]; // BCC
-
-            let crc = self.calculate_crc(&tx[..7])?;
-            tx[7..].copy_from_slice(&crc);
-
-            let rx = self.transceive::<3>(&tx[0..9], 0, 0)?;
-            if rx.valid_bytes != 3 || rx.valid_bits != 0 {
-                return Err(Error::IncompleteFrame);
+            if !crc::check_bcc(&tx[2..7]) {
+                return Err(Error::Bcc(Context::default()).with_operation(operation));
             }
 
-            let sak = picc::Sak::from(rx.buffer[0]);
-
-            let crc_a = &rx.buffer[1..];
-            let crc_verify = self.calculate_crc(&rx.buffer[..1])?;
-            if crc_a != crc_verify {
-                return Err(Error::Crc);
+            let rx = self
+                .transceive_crc::<3>(&tx[0..7], true)
+                .map_err(|e| e.with_operation(operation))?;
+            if rx.valid_bytes != 1 || rx.valid_bits != 0 {
+                return Err(Error::IncompleteFrame(Context::default()).with_operation(operation));
             }
 
+            let sak = Sak::from(rx.buffer[0]);
+
             if !sak.is_complete() {
                 uid_bytes[uid_idx..uid_idx + 3].copy_from_slice(&tx[3..6]);
                 uid_idx += 3;
@@ -391,18 +722,26 @@ where
             }
         };
 
+        debug!(
+            "selected UID {} SAK {:02X}",
+            logging::Bytes(&uid_bytes[..4 + 3 * cascade_level as usize]),
+            sak.as_byte()
+        );
         match cascade_level {
             0 => Ok(Uid::Single(GenericUid {
                 bytes: uid_bytes[0..4].try_into().unwrap(),
                 sak,
+                atqa: *atqa,
             })),
             1 => Ok(Uid::Double(GenericUid {
                 bytes: uid_bytes[0..7].try_into().unwrap(),
                 sak,
+                atqa: *atqa,
             })),
             2 => Ok(Uid::Triple(GenericUid {
                 bytes: uid_bytes,
                 sak,
+                atqa: *atqa,
             })),
             _ => unreachable!(),
         }
@@ -411,16 +750,13 @@ where
     /// Switch off the MIFARE Crypto1 unit.
     /// Must be done after communication with an authenticated PICC
     pub fn stop_crypto1(&mut self) -> Result<(), Error<E>> {
-        self.rmw(Register::Status2Reg, |b| b & !0x08)
+        self.rmw(Register::Status2Reg, |b| b & !MF_CRYPTO1_ON)
             .map_err(Error::Spi)
     }
 
-    pub fn mf_authenticate(
-        &mut self,
-        uid: &Uid,
-        block: u8,
-        key: &MifareKey,
-    ) -> Result<(), Error<E>> {
+    fn mf_authenticate(&mut self, uid: &Uid, block: u8, key: &MifareKey) -> Result<(), Error<E>> {
+        // the MFAuthent command takes care of the CRC_A itself
+        self.write_crc_enable(false, false).map_err(Error::Spi)?;
         // stop any ongoing command
         self.command(Command::Idle).map_err(Error::Spi)?;
         // clear all interrupt flags
@@ -434,69 +770,95 @@ where
         tx_buffer[0] = picc::Command::MfAuthKeyA as u8;
         tx_buffer[1] = block;
         tx_buffer[2..8].copy_from_slice(key);
-        match uid {
-            Uid::Single(u) => tx_buffer[8..12].copy_from_slice(&u.bytes[0..4]),
-            Uid::Double(u) => tx_buffer[8..12].copy_from_slice(&u.bytes[0..4]),
-            Uid::Triple(u) => tx_buffer[8..12].copy_from_slice(&u.bytes[0..4]),
-        };
+        // the last 4 bytes of the UID, for 7 and 10 byte UIDs
+        let uid = uid.as_bytes();
+        tx_buffer[8..12].copy_from_slice(&uid[uid.len() - 4..]);
+        debug!(
+            "authenticate block {} of UID {} with key A",
+            block,
+            logging::Bytes(uid)
+        );
         // write data to transmit to the FIFO buffer
         self.write_many(Register::FIFODataReg, &tx_buffer)?;
 
         // signal command
         self.command(Command::MFAuthent).map_err(Error::Spi)?;
 
-        let mut irq;
-        loop {
-            irq = self.read(Register::ComIrqReg).map_err(Error::Spi)?;
+        let irq = loop {
+            let irq = self.read(Register::ComIrqReg).map_err(Error::Spi)?;
+            if irq & (ERR_IRQ | IDLE_IRQ | TIMER_IRQ) != 0 {
+                break irq;
+            }
+        };
 
-            if irq & (ERR_IRQ | IDLE_IRQ) != 0 {
-                break;
-            } else if irq & TIMER_IRQ != 0 {
-                return Err(Error::Timeout);
+        let operation = Operation::Authenticate(block);
+        self.check_error_register(irq)
+            .map_err(|e| e.with_operation(operation))?;
+
+        // Crypto1 is only switched on when the PICC accepted the key. If the PICC answered the
+        // first pass (RxIRq) and the command did not succeed, the PICC rejected the key,
+        // otherwise there was no PICC to answer.
+        let status2 = self.read(Register::Status2Reg).map_err(Error::Spi)?;
+        if status2 & MF_CRYPTO1_ON == 0 {
+            if irq & (TIMER_IRQ | RX_IRQ) == TIMER_IRQ {
+                debug!("authentication of block {} failed: timeout", block);
+                return Err(self.timeout(irq)?.with_operation(operation));
             }
+            debug!("authentication of block {} failed: wrong key", block);
+            return Err(Error::AuthFailed(Context::new(0, irq)).with_operation(operation));
         }
-
-        self.check_error_register()?;
+        debug!("authenticated block {}", block);
         Ok(())
     }
 
-    pub fn mf_read(&mut self, block: u8, check_crc: bool) -> Result<[u8; 16], Error<E>> {
-        let mut tx = [picc::Command::MfRead as u8, block, 0u8, 0u8];
+    /// READ of 16 bytes, shared by MIFARE Classic and Ultralight, `nak` decodes the NAK codes
+    /// of the PICC type
+    fn mf_read(
+        &mut self,
+        block: u8,
+        check_crc: bool,
+        nak: fn(u8) -> Nak,
+    ) -> Result<[u8; 16], Error<E>> {
+        let operation = Operation::Read(block);
+        let rx = self
+            .transceive_crc::<18>(&[picc::Command::MfRead as u8, block], check_crc)
+            .map_err(|e| e.with_operation(operation))?;
+        if rx.is_ack_nak() {
+            rx.ack(nak).map_err(|e| e.with_operation(operation))?;
+        }
+        if rx.valid_bytes < 16 || rx.valid_bits != 0 {
+            return Err(Error::IncompleteFrame(Context::default()).with_operation(operation));
+        }
+        Ok(rx.buffer[..16].try_into().unwrap())
+    }
 
-        let crc = self.calculate_crc(&tx[0..2])?;
-        tx[2..].copy_from_slice(&crc);
+    fn mf_write(&mut self, block: u8, data: [u8; 16]) -> Result<(), Error<E>> {
+        let operation = Operation::Write(block);
+        self.transceive_crc::<1>(&[picc::Command::MfWrite as u8, block], false)
+            .and_then(|fifo_data| fifo_data.ack(Nak::classic))
+            .map_err(|e| e.with_operation(operation))?;
 
-        let rx = self.transceive::<18>(&tx, 0, 0)?.buffer;
+        self.transceive_crc::<1>(&data, false)
+            .and_then(|fifo_data| fifo_data.ack(Nak::classic))
+            .map_err(|e| e.with_operation(operation))
+    }
 
-        if check_crc {
-            // verify CRC
-            let crc = self.calculate_crc(&rx[..16])?;
-            if crc != rx[16..] {
-                return Err(Error::Crc);
-            }
-        }
-        Ok(rx[..16].try_into().unwrap())
+    pub fn crc_mode(&self) -> CrcMode {
+        self.crc_mode
     }
 
-    pub fn mf_write(&mut self, block: u8, data: [u8; 16]) -> Result<(), Error<E>> {
-        let mut cmd = [picc::Command::MfWrite as u8, block, 0, 0];
-        let crc = self.calculate_crc(&cmd[0..2])?;
-        cmd[2..].copy_from_slice(&crc);
-        let fifo_data = self.transceive::<1>(&cmd, 0, 0)?;
-        if fifo_data.valid_bytes != 1 || fifo_data.valid_bits != 4 {
-            return Err(Error::Nak);
-        }
+    /// Change how the CRC_A of frames is calculated and verified, see [CrcMode].
+    pub fn set_crc_mode(&mut self, crc_mode: CrcMode) {
+        self.crc_mode = crc_mode;
+    }
 
-        let mut tx = [0u8; 18];
-        let crc = self.calculate_crc(&data)?;
-        tx[..16].copy_from_slice(&data);
-        tx[16..].copy_from_slice(&crc);
-        let fifo_data = self.transceive::<1>(&tx, 0, 0)?;
-        if fifo_data.valid_bytes != 1 || fifo_data.valid_bits != 4 {
-            return Err(Error::Nak);
-        }
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
 
-        Ok(())
+    /// Change how block operations are retried after transient errors, see [RetryPolicy].
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
     }
 
     /// Returns the version reported by the MFRC522
@@ -505,11 +867,6 @@ where
     }
 
     pub fn new_card_present(&mut self) -> Result<AtqA, Error<E>> {
-        self.write(Register::TxModeReg, 0x00).map_err(Error::Spi)?;
-        self.write(Register::RxModeReg, 0x00).map_err(Error::Spi)?;
-        self.write(Register::ModWidthReg, 0x26)
-            .map_err(Error::Spi)?;
-
         self.reqa()
     }
 }
@@ -532,51 +889,76 @@ where
         self.fifo_flush().map_err(Error::Spi)?;
 
         // write data to transmit to the FIFO buffer
-        self.write_many(Register::FIFODataReg, data)?;
+        let mut chunks = data.chunks(FIFO_SIZE);
+        self.write_many(Register::FIFODataReg, chunks.next().unwrap_or_default())?;
 
         self.command(Command::CalcCRC).map_err(Error::Spi)?;
 
-        // Wait for the CRC calculation to complete.
-        let mut irq;
-        for _ in 0..5000 {
-            irq = self.read(Register::DivIrqReg).map_err(Error::Spi)?;
+        // The calculation continues with the data written while the command is active,
+        // data longer than the FIFO buffer is written once the previous part is processed
+        for chunk in chunks {
+            self.wait_for_crc()?;
+            self.write(Register::DivIrqReg, 1 << 2)
+                .map_err(Error::Spi)?;
+            self.write_many(Register::FIFODataReg, chunk)?;
+        }
 
-            if irq & CRC_IRQ != 0 {
-                self.command(Command::Idle).map_err(Error::Spi)?;
-                let crc = [
-                    self.read(Register::CRCResultRegLow).map_err(Error::Spi)?,
-                    self.read(Register::CRCResultRegHigh).map_err(Error::Spi)?,
-                ];
+        self.wait_for_crc()?;
+        self.command(Command::Idle).map_err(Error::Spi)?;
+        Ok([
+            self.read(Register::CRCResultRegLow).map_err(Error::Spi)?,
+            self.read(Register::CRCResultRegHigh).map_err(Error::Spi)?,
+        ])
+    }
 
-                return Ok(crc);
+    /// Wait until the CRC coprocessor has processed the data in the FIFO buffer
+    fn wait_for_crc(&mut self) -> Result<(), Error<E>> {
+        for _ in 0..5000 {
+            let irq = self.read(Register::DivIrqReg).map_err(Error::Spi)?;
+            if irq & CRC_IRQ != 0 {
+                return Ok(());
             }
         }
-        Err(Error::Timeout)
+        Err(Error::Timeout(Context::default()).with_operation(Operation::CalcCrc))
     }
 
-    fn check_error_register(&mut self) -> Result<(), Error<E>> {
+    /// Check the ErrorReg at the end of a command, `com_irq` is the last value read from the
+    /// ComIrqReg. If several error bits are set, the most relevant one is reported, the
+    /// [Context] of the error keeps all of them.
+    fn check_error_register(&mut self, com_irq: u8) -> Result<(), Error<E>> {
         let err = self.read(Register::ErrorReg).map_err(Error::Spi)?;
+        if err != 0 {
+            debug!("ErrorReg {:02X}: {}", err, error::ErrorBits(err));
+        }
 
+        let context = Context::new(err, com_irq);
         if err & PROTOCOL_ERR != 0 {
-            Err(Error::Protocol)
+            Err(Error::Protocol(context))
         } else if err & PARITY_ERR != 0 {
-            Err(Error::Parity)
+            Err(Error::Parity(context))
         } else if err & CRC_ERR != 0 {
-            Err(Error::Crc)
+            Err(Error::Crc(context))
         } else if err & COLL_ERR != 0 {
-            Err(Error::Collision)
+            Err(Error::Collision(context))
         } else if err & BUFFER_OVFL != 0 {
-            Err(Error::BufferOverflow)
+            Err(Error::BufferOverflow(context))
         } else if err & TEMP_ERR != 0 {
-            Err(Error::Overheating)
+            Err(Error::Overheating(context))
         } else if err & WR_ERR != 0 {
-            Err(Error::Wr)
+            Err(Error::Wr(context))
         } else {
             Ok(())
         }
     }
 
-    // Transmit + Receive
+    /// The timer expired before the PICC answered, `com_irq` is the last value read from the
+    /// ComIrqReg. The ErrorReg is read for the context of the error.
+    fn timeout(&mut self, com_irq: u8) -> Result<Error<E>, Error<E>> {
+        let err = self.read(Register::ErrorReg).map_err(Error::Spi)?;
+        Ok(Error::Timeout(Context::new(err, com_irq)))
+    }
+
+    // Transmit + Receive, without CRC
     fn transceive<const RX: usize>(
         &mut self,
         // the data to be sent
@@ -589,6 +971,139 @@ where
     where
         [u8; RX]: Sized,
     {
+        let mut fifo_data = FifoData::new();
+        self.transceive_into(tx_buffer, tx_last_bits, rx_align_bits, &mut fifo_data)?;
+        Ok(fifo_data)
+    }
+
+    /// Like [transceive](Mfrc522::transceive), the response is kept in `fifo_data` when
+    /// the ErrorReg reports an error, eg the bits received up to a collision.
+    fn transceive_into<const RX: usize>(
+        &mut self,
+        tx_buffer: &[u8],
+        tx_last_bits: u8,
+        rx_align_bits: u8,
+        fifo_data: &mut FifoData<RX>,
+    ) -> Result<(), Error<E>> {
+        self.write_crc_enable(false, false).map_err(Error::Spi)?;
+        self.execute_transceive(tx_buffer, tx_last_bits, rx_align_bits, fifo_data)
+    }
+
+    /// Transmit `tx_buffer` followed by its CRC_A, according to the [CrcMode].
+    ///
+    /// If `rx_crc` is set, the CRC_A of the response is verified and not included in the
+    /// valid bytes, `RX` still has to leave room for it. A 4-bit response (ACK or NAK)
+    /// has no CRC_A, it is returned as is.
+    ///
+    /// Above 106 kbit/s, the MFRC522 always handles the CRC_A itself.
+    fn transceive_crc<const RX: usize>(
+        &mut self,
+        tx_buffer: &[u8],
+        rx_crc: bool,
+    ) -> Result<FifoData<RX>, Error<E>>
+    where
+        [u8; RX]: Sized,
+    {
+        let mut fifo_data = FifoData::new();
+        self.transceive_crc_into(tx_buffer, rx_crc, &mut fifo_data)?;
+        Ok(fifo_data)
+    }
+
+    /// Like [transceive_crc](Mfrc522::transceive_crc), the response is kept in `fifo_data`
+    /// when the ErrorReg reports an error.
+    fn transceive_crc_into<const RX: usize>(
+        &mut self,
+        tx_buffer: &[u8],
+        rx_crc: bool,
+        fifo_data: &mut FifoData<RX>,
+    ) -> Result<(), Error<E>> {
+        let above_106 = (self.tx_mode | self.rx_mode) & SPEED != 0;
+        if self.crc_mode == CrcMode::Hardware || above_106 {
+            self.write_crc_enable(true, rx_crc || above_106)
+                .map_err(Error::Spi)?;
+            return match self.execute_transceive(tx_buffer, 0, 0, fifo_data) {
+                // an ACK or NAK has no CRC_A
+                Err(Error::Crc(_)) if fifo_data.is_ack_nak() => Ok(()),
+                result => result,
+            };
+        }
+
+        let mut tx = Vec::<u8, MAX_FRAME_SIZE>::new();
+        tx.extend_from_slice(tx_buffer).map_err(|_| Error::NoRoom)?;
+        let crc = self.crc_a(tx_buffer)?;
+        tx.extend_from_slice(&crc).map_err(|_| Error::NoRoom)?;
+
+        self.transceive_into(&tx, 0, 0, fifo_data)?;
+        if !rx_crc || fifo_data.is_ack_nak() {
+            return Ok(());
+        }
+        if fifo_data.valid_bytes < 2 || fifo_data.valid_bits != 0 {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+        let n = fifo_data.valid_bytes - 2;
+        let crc = self.crc_a(&fifo_data.buffer[..n])?;
+        if crc != fifo_data.buffer[n..n + 2] {
+            return Err(Error::Crc(Context::default()));
+        }
+        fifo_data.valid_bytes = n;
+        Ok(())
+    }
+
+    /// CRC_A calculated by the coprocessor or in software, according to the [CrcMode]
+    fn crc_a(&mut self, data: &[u8]) -> Result<[u8; 2], Error<E>> {
+        match self.crc_mode {
+            CrcMode::Coprocessor => self.calculate_crc(data),
+            _ => Ok(crc::crc_a(data)),
+        }
+    }
+
+    /// Set or clear TxCRCEn and RxCRCEn
+    fn write_crc_enable(&mut self, tx: bool, rx: bool) -> Result<(), E> {
+        let tx_mode = if tx {
+            self.tx_mode | CRC_EN
+        } else {
+            self.tx_mode & !CRC_EN
+        };
+        let rx_mode = if rx {
+            self.rx_mode | CRC_EN
+        } else {
+            self.rx_mode & !CRC_EN
+        };
+        self.write_modes(tx_mode, rx_mode)
+    }
+
+    /// Write TxModeReg and RxModeReg, the registers are only written when they change
+    pub(crate) fn write_modes(&mut self, tx_mode: u8, rx_mode: u8) -> Result<(), E> {
+        if tx_mode != self.tx_mode {
+            self.write(Register::TxModeReg, tx_mode)?;
+            self.tx_mode = tx_mode;
+        }
+        if rx_mode != self.rx_mode {
+            self.write(Register::RxModeReg, rx_mode)?;
+            self.rx_mode = rx_mode;
+        }
+        Ok(())
+    }
+
+    /// Run the Transceive command, the response is stored in `fifo_data`.
+    ///
+    /// The response is also stored when the ErrorReg reports an error,
+    /// as far as it was received.
+    fn execute_transceive<const RX: usize>(
+        &mut self,
+        tx_buffer: &[u8],
+        tx_last_bits: u8,
+        rx_align_bits: u8,
+        fifo_data: &mut FifoData<RX>,
+    ) -> Result<(), Error<E>> {
+        let tx_bits = logging::bit_len(tx_buffer.len(), tx_last_bits as usize);
+        trace!(
+            "PCD -> PICC {}: {} ({} bits)",
+            logging::CommandName(tx_buffer, tx_bits),
+            logging::Bytes(tx_buffer),
+            tx_bits
+        );
+
         // stop any ongoing command
         self.command(Command::Idle).map_err(Error::Spi)?;
 
@@ -598,8 +1113,9 @@ where
         // flush FIFO buffer
         self.fifo_flush().map_err(Error::Spi)?;
 
-        // write data to transmit to the FIFO buffer
-        self.write_many(Register::FIFODataReg, tx_buffer)?;
+        // write data to transmit to the FIFO buffer, as far as it fits
+        let mut sent = tx_buffer.len().min(FIFO_SIZE);
+        self.write_many(Register::FIFODataReg, &tx_buffer[..sent])?;
 
         // signal command
         self.command(Command::Transceive).map_err(Error::Spi)?;
@@ -611,44 +1127,77 @@ where
         )
         .map_err(Error::Spi)?;
 
+        *fifo_data = FifoData::new();
+
         // TODO timeout when connection to the MFRC522 is lost (?)
         // wait for transmission + reception to complete
-        loop {
+        let irq = loop {
             let irq = self.read(Register::ComIrqReg).map_err(Error::Spi)?;
 
             if irq & (RX_IRQ | ERR_IRQ | IDLE_IRQ) != 0 {
-                break;
+                break irq;
             } else if irq & TIMER_IRQ != 0 {
-                return Err(Error::Timeout);
+                trace!("PICC -> PCD: timeout");
+                return Err(self.timeout(irq)?);
             }
-        }
 
-        self.check_error_register()?;
-        self.fifo_data()
-    }
+            if sent < tx_buffer.len() {
+                if irq & LO_ALERT_IRQ != 0 {
+                    // refill the FIFO buffer before it runs empty
+                    self.write(Register::ComIrqReg, LO_ALERT_IRQ)
+                        .map_err(Error::Spi)?;
+                    let level = self.read(Register::FIFOLevelReg).map_err(Error::Spi)? as usize;
+                    let end = tx_buffer.len().min(sent + FIFO_SIZE - level);
+                    self.write_many(Register::FIFODataReg, &tx_buffer[sent..end])?;
+                    sent = end;
+                }
+            } else if irq & TX_IRQ != 0 && irq & HI_ALERT_IRQ != 0 {
+                // the transmission is done, drain the FIFO buffer before it overflows
+                self.write(Register::ComIrqReg, HI_ALERT_IRQ)
+                    .map_err(Error::Spi)?;
+                fifo_data.valid_bytes +=
+                    self.read_fifo(&mut fifo_data.buffer[fifo_data.valid_bytes..])?;
+            }
+        };
 
-    /// Get the data from the internal FIFO buffer
-    fn fifo_data<const RX: usize>(&mut self) -> Result<FifoData<RX>, Error<E>> {
-        let mut buffer = [0u8; RX];
-        let mut valid_bytes = 0;
-        let mut valid_bits = 0;
+        // the rest of the response is read first, it is needed to decode some errors
+        let received = self.receive_rest(fifo_data);
+        self.check_error_register(irq)?;
+        received?;
+        trace!(
+            "PICC -> PCD: {} ({} bits)",
+            logging::Bytes(&fifo_data.buffer[..fifo_data.valid_bytes]),
+            logging::bit_len(fifo_data.valid_bytes, fifo_data.valid_bits)
+        );
+        Ok(())
+    }
 
+    /// Move the rest of the response from the FIFO buffer to `fifo_data`
+    fn receive_rest<const RX: usize>(
+        &mut self,
+        fifo_data: &mut FifoData<RX>,
+    ) -> Result<(), Error<E>> {
         if RX > 0 {
-            valid_bytes = self.read(Register::FIFOLevelReg).map_err(Error::Spi)? as usize;
-            if valid_bytes > RX {
-                return Err(Error::NoRoom);
-            }
-            if valid_bytes > 0 {
-                self.read_many(Register::FIFODataReg, &mut buffer[0..valid_bytes])?;
-                valid_bits = (self.read(Register::ControlReg).map_err(Error::Spi)? & 0x07) as usize;
+            fifo_data.valid_bytes +=
+                self.read_fifo(&mut fifo_data.buffer[fifo_data.valid_bytes..])?;
+            if fifo_data.valid_bytes > 0 {
+                fifo_data.valid_bits =
+                    (self.read(Register::ControlReg).map_err(Error::Spi)? & 0x07) as usize;
             }
         }
+        Ok(())
+    }
 
-        Ok(FifoData {
-            buffer,
-            valid_bytes,
-            valid_bits,
-        })
+    /// Move the contents of the FIFO buffer to `buffer`, returns the number of bytes
+    fn read_fifo(&mut self, buffer: &mut [u8]) -> Result<usize, Error<E>> {
+        let level = self.read(Register::FIFOLevelReg).map_err(Error::Spi)? as usize;
+        if level > buffer.len() {
+            return Err(Error::NoRoom);
+        }
+        if level > 0 {
+            self.read_many(Register::FIFODataReg, &mut buffer[..level])?;
+        }
+        Ok(level)
     }
 
     /// Flush the internal FIFO buffer
@@ -661,6 +1210,43 @@ where
         self.write(Register::CommandReg, command.into())
     }
 
+    /// Set the reload value of the timer used to detect a timeout, in ticks of 25μs
+    fn set_timeout(&mut self, ticks: u16) -> Result<(), E> {
+        self.write(Register::TReloadRegHigh, (ticks >> 8) as u8)?;
+        self.write(Register::TReloadRegLow, ticks as u8)
+    }
+
+    /// Configure the MFRC522 after a reset
+    fn configure(&mut self) -> Result<(), E> {
+        self.write(Register::TxModeReg, 0x00)?;
+        self.write(Register::RxModeReg, 0x00)?;
+        self.tx_mode = 0x00;
+        self.rx_mode = 0x00;
+        // Reset ModWidthReg to default value
+        self.write(Register::ModWidthReg, 0x26)?;
+
+        // Configure the timer, so we can get a timeout if something goes wrong
+        // when communicating with a PICC:
+        // - Set timer to start automatically at the end of the transmission
+        self.write(Register::TModeReg, 0x80)?;
+        // - Configure the prescaler to determine the timer frequency:
+        //   f_timer = 13.56 MHz / (2 * TPreScaler + 1)
+        //   so for 40kHz frequency (25μs period), TPreScaler = 0x0A9
+        self.write(Register::TPrescalerReg, 0xA9)?;
+        // - Set the reload value to determine the timeout
+        //   for a 25ms timeout, we need a value of 1000 = 0x3E8
+        self.set_timeout(DEFAULT_TIMEOUT)?;
+
+        // TODO: may not be necessary?
+        self.write(Register::TxASKReg, FORCE_100_ASK)?;
+        self.write(Register::WaterLevelReg, WATER_LEVEL)?;
+        // Set preset value of CRC coprocessor according to ISO 14443-3 part 6.2.4
+        self.write(Register::ModeReg, (0x3f & (!0b11)) | 0b01)?;
+        // Configure the receiver gain and antenna drivers, this enables the antenna
+        self.apply_rf_config()?;
+        Ok(())
+    }
+
     /// Perform a software reset
     fn reset(&mut self) -> Result<(), E> {
         self.command(Command::SoftReset)?;
@@ -773,9 +1359,9 @@ where
     where
         F: FnOnce(&mut Self) -> T,
     {
-        self.nss.set_low();
+        let _ = self.nss.set_low();
         let result = f(self);
-        self.nss.set_high();
+        let _ = self.nss.set_high();
 
         result
     }
@@ -791,9 +1377,9 @@ where
     where
         F: FnOnce(&mut Self) -> T,
     {
-        self.nss.set_low();
+        let _ = self.nss.set_low();
         let result = f(self);
-        self.nss.set_high();
+        let _ = self.nss.set_high();
         (self.delay)();
 
         result
@@ -810,6 +1396,29 @@ struct FifoData<const L: usize> {
 }
 
 impl<const L: usize> FifoData<L> {
+    fn new() -> Self {
+        FifoData {
+            buffer: [0; L],
+            valid_bytes: 0,
+            valid_bits: 0,
+        }
+    }
+
+    /// Is it a 4 bit answer, an ACK or NAK of a MIFARE PICC
+    fn is_ack_nak(&self) -> bool {
+        self.valid_bytes == 1 && self.valid_bits == 4
+    }
+
+    /// Check that the answer is an ACK, a NAK is decoded with `nak`
+    fn ack<E>(&self, nak: fn(u8) -> Nak) -> Result<(), Error<E>> {
+        let nak = match self.buffer[0] & 0x0F {
+            _ if !self.is_ack_nak() => Nak::Unexpected,
+            MIFARE_ACK => return Ok(()),
+            code => nak(code),
+        };
+        Err(Error::Nak(nak, Context::default()))
+    }
+
     /// Copies FIFO data to destination buffer.
     /// Assumes the FIFO data is aligned properly to append directly to the current known bits.
     /// Returns the number of valid bits in the destination buffer after copy.
@@ -0,0 +1,120 @@
+//! Logging of the frames exchanged with the PICC, through `defmt` (`defmt` feature)
+//! or `log` (`log` feature).
+//!
+//! The frames are logged at the trace level, the anticollision, the selection and the
+//! authentication at the debug level. Without either feature, the macros expand to nothing.
+//!
+//! The format strings are shared by both backends, so they only use `{}`, `{:?}` and
+//! `{:02X}`, with arguments implementing both `core::fmt` and `defmt::Format`.
+
+use core::fmt;
+
+use crate::picc;
+
+macro_rules! trace {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::trace!($s $(, $x)*);
+        #[cfg(feature = "log")]
+        ::log::trace!($s $(, $x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        let _ = ($(&$x),*);
+    }};
+}
+
+macro_rules! debug {
+    ($s:literal $(, $x:expr)* $(,)?) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::debug!($s $(, $x)*);
+        #[cfg(feature = "log")]
+        ::log::debug!($s $(, $x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        let _ = ($(&$x),*);
+    }};
+}
+
+/// Bytes of a frame, formatted as hex
+pub(crate) struct Bytes<'a>(pub &'a [u8]);
+
+impl fmt::Display for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Bytes<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=[u8]:02X}", self.0)
+    }
+}
+
+/// Command of a PCD to PICC frame with its length in bits, formatted as the command name,
+/// or as the first byte if the command is not known
+pub(crate) struct CommandName<'a>(pub &'a [u8], pub usize);
+
+impl fmt::Display for CommandName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (command(self.0, self.1), self.0.first()) {
+            (Some(command), _) => write!(f, "{:?}", command),
+            (None, Some(byte)) => write!(f, "0x{:02X}", byte),
+            (None, None) => f.write_str("empty frame"),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for CommandName<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match (command(self.0, self.1), self.0.first()) {
+            (Some(command), _) => defmt::write!(f, "{}", command),
+            (None, Some(byte)) => defmt::write!(f, "0x{=u8:02X}", byte),
+            (None, None) => defmt::write!(f, "empty frame"),
+        }
+    }
+}
+
+/// Length of a frame in bits, `last_bits` is the number of valid bits of the last byte
+/// (0 if all 8 are valid)
+pub(crate) fn bit_len(bytes: usize, last_bits: usize) -> usize {
+    match last_bits {
+        0 => bytes * 8,
+        n => bytes.saturating_sub(1) * 8 + n,
+    }
+}
+
+/// The command of a PCD to PICC frame, from its first byte. 7 bit frames are REQA or WUPA.
+///
+/// Several PICC types use the same codes for different commands (eg 0x60 is a MIFARE Classic
+/// authentication and a GET_VERSION of an NTAG), the name is only a hint.
+fn command(frame: &[u8], bits: usize) -> Option<picc::Command> {
+    use picc::Command::*;
+
+    let command = match (frame.first()?, bits) {
+        (0x26, 7) => ReqA,
+        (0x52, 7) => WupA,
+        (_, 7) => return None,
+        (0x93, _) => SelCl1,
+        (0x95, _) => SelCl2,
+        (0x97, _) => SelCl3,
+        (0x50, _) => HltA,
+        (0xE0, _) => RAtS,
+        (0x60, _) => MfAuthKeyA,
+        (0x61, _) => MfAuthKeyB,
+        (0x30, _) => MfRead,
+        (0xA0, _) => MfWrite,
+        (0xC0, _) => MfDecrement,
+        (0xC1, _) => MfIncrement,
+        (0xC2, _) => MfRestore,
+        (0xB0, _) => MfTransfer,
+        (0xA2, _) => UlWrite,
+        _ => return None,
+    };
+    Some(command)
+}
@@ -0,0 +1,139 @@
+//! Continuous monitoring of the PICC in the field.
+//!
+//! [CardMonitor::poll] checks once whether a PICC is present and compares it with the PICC
+//! seen previously, so a PICC that stays in the field is only reported as arriving once.
+//! Each poll wakes up the PICC with WUPA, selects it and halts it again, so it can be
+//! called periodically from a blocking loop or from an async task, in between other work.
+//!
+//! Short interruptions (eg a PICC moving in the field) are debounced:
+//! the PICC is only reported as removed after a number of consecutive failed polls.
+//!
+//! ```ignore
+//! let mut monitor = CardMonitor::new();
+//! loop {
+//!     match monitor.poll(&mut mfrc522)? {
+//!         Some(CardEvent::Arrived(uid)) => open_door(&uid),
+//!         Some(CardEvent::Removed(_)) => close_door(),
+//!         Some(CardEvent::StillPresent) | None => {}
+//!     }
+//!     delay.delay_ms(100u32);
+//! }
+//! ```
+
+use crate::error::Error;
+use crate::{spi, Initialized, Mfrc522, Uid, WithNssDelay};
+
+/// Default number of consecutive failed polls before a PICC is considered removed
+pub const DEFAULT_DEBOUNCE: u8 = 3;
+
+/// Change in the PICC presence, returned by [CardMonitor::poll]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardEvent {
+    /// A new PICC entered the field
+    Arrived(Uid),
+    /// The PICC has left the field (or another PICC has taken its place)
+    Removed(Uid),
+    /// The same PICC is still present
+    StillPresent,
+}
+
+/// Polling state machine tracking the PICC in the field
+#[derive(Debug, Clone)]
+pub struct CardMonitor {
+    /// The PICC that is currently present
+    current: Option<Uid>,
+    /// Number of consecutive polls the current PICC did not answer
+    misses: u8,
+    debounce: u8,
+}
+
+impl Default for CardMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CardMonitor {
+    pub fn new() -> Self {
+        CardMonitor {
+            current: None,
+            misses: 0,
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+
+    /// Set the number of consecutive failed polls before a PICC is considered removed.
+    ///
+    /// A value of 1 reports the PICC as removed on the first failed poll.
+    pub fn with_debounce(self, debounce: u8) -> Self {
+        CardMonitor {
+            debounce: debounce.max(1),
+            ..self
+        }
+    }
+
+    /// The PICC that is currently present, if any
+    pub fn current(&self) -> Option<&Uid> {
+        self.current.as_ref()
+    }
+
+    /// Check whether a PICC is present and report any change.
+    ///
+    /// Returns `None` when there was no PICC and there still is none.
+    /// While the current PICC is being debounced, [CardEvent::StillPresent] is returned.
+    /// The PICC is left in the HALT state, use [wupa](Mfrc522::wupa) and
+    /// [select](Mfrc522::select) to use it.
+    ///
+    /// Only SPI errors are returned, all other errors count as a failed poll.
+    pub fn poll<E, SPI, NSS, D>(
+        &mut self,
+        mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>,
+    ) -> Result<Option<CardEvent>, Error<E>>
+    where
+        SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+        Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+    {
+        let found = match detect(mfrc522) {
+            Ok(uid) => Some(uid),
+            Err(Error::Spi(e)) => return Err(Error::Spi(e)),
+            Err(_) => None,
+        };
+
+        let event = match (found, self.current.take()) {
+            (Some(uid), None) => {
+                self.current = Some(uid.clone());
+                Some(CardEvent::Arrived(uid))
+            }
+            (Some(uid), Some(current)) if uid == current => {
+                self.current = Some(current);
+                Some(CardEvent::StillPresent)
+            }
+            // another PICC took its place, it will be reported on the next poll
+            (Some(_), Some(current)) => Some(CardEvent::Removed(current)),
+            (None, Some(current)) => {
+                self.misses += 1;
+                if self.misses < self.debounce {
+                    self.current = Some(current);
+                    return Ok(Some(CardEvent::StillPresent));
+                }
+                Some(CardEvent::Removed(current))
+            }
+            (None, None) => None,
+        };
+
+        self.misses = 0;
+        Ok(event)
+    }
+}
+
+/// Wake up and select a PICC, then halt it again
+fn detect<E, SPI, NSS, D>(mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>) -> Result<Uid, Error<E>>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    let atqa = mfrc522.wupa()?;
+    let uid = mfrc522.anticollision(&atqa)?;
+    mfrc522.hlta()?;
+    Ok(uid)
+}
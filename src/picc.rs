@@ -13,8 +13,8 @@
 /// then use the other commands to read/write/modify the blocks on the sector.
 ///
 /// The read/write commands can also be used for MIFARE Ultralight.
-#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Command {
     /// REQuest command, Type A. invites PICCs in state IDLE to go to READY\
     /// and prepare for anticollision or selection. 7 bit frame.
@@ -22,8 +22,6 @@ pub enum Command {
     /// Wake-UP command, Type A. invites PICCs in state IDLE and HALT to go to READY(*)\
     /// and prepare for anticollision or selection. 7 bit frame.
     WupA = 0x52,
-    /// Cascade Tag. Not really a command, but used during anti collision.
-    CT = 0x88,
     /// Anti collision/Select, Cascade Level 1
     SelCl1 = 0x93,
     /// Anti collision/Select, Cascade Level 2
@@ -56,9 +54,10 @@ pub enum Command {
     UlWrite = 0xA2,
 }
 
-/// PICC Type
-#[allow(dead_code)]
-#[derive(Debug)]
+/// PICC Type, as indicated by the SAK
+///
+/// See [identify](crate::Card::identify) for a more detailed identification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Type {
     Unknown,
     /// PICC compliant with ISO/IEC 14443-4
@@ -84,6 +83,8 @@ pub enum Type {
 }
 
 /// Select Acknowledge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Sak {
     byte: u8,
 }
@@ -95,7 +96,13 @@ impl From<u8> for Sak {
 }
 
 impl Sak {
+    #[deprecated(note = "use `card_type`")]
     pub fn get_type(&self) -> Type {
+        self.card_type()
+    }
+
+    /// The PICC type indicated by the SAK
+    pub fn card_type(&self) -> Type {
         // https://www.nxp.com/docs/en/application-note/AN10833.pdf
         // 3.2 Coding of Select Acknowledge (SAK)
         // ignore 8-bit (iso14443 starts with LSBit = bit 1)
@@ -114,6 +121,11 @@ impl Sak {
         }
     }
 
+    /// The raw SAK byte
+    pub fn as_byte(&self) -> u8 {
+        self.byte
+    }
+
     /// Is the PICC compliant with ISO/IEC 14443-4
     pub fn is_compliant(&self) -> bool {
         self.byte & (1 << 5) != 0
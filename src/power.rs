@@ -0,0 +1,185 @@
+//! Power management and low-power card detection.
+//!
+//! In soft power-down mode, the MFRC522 switches off its internal current sinks and
+//! oscillator, while keeping its register contents. Switching off the antenna as well
+//! saves most of the power, as the RF field is by far the largest consumer.
+//!
+//! [LowPowerDetector] combines both for battery-powered devices: the MFRC522 is only woken up
+//! briefly to send a REQA with a short timeout, and put back to sleep if no PICC answered.
+//!
+//! ```ignore
+//! let detector = LowPowerDetector::new(250);
+//! loop {
+//!     if let Some(atqa) = detector.ping(&mut mfrc522)? {
+//!         let card = mfrc522.select(&atqa)?;
+//!         // ...
+//!     }
+//!     delay.delay_ms(detector.interval_ms());
+//! }
+//! ```
+
+use crate::error::{Context, Error, Operation};
+use crate::register::*;
+use crate::{spi, AtqA, Initialized, Mfrc522, WithNssDelay, DEFAULT_TIMEOUT};
+
+/// Default timeout for the REQA sent by [LowPowerDetector], in timer ticks of 25μs.
+///
+/// The timer stops when the PICC starts responding, which happens about 90μs
+/// after the end of the REQA.
+pub const DEFAULT_PING_TIMEOUT: u16 = 8;
+/// Estimated time to wake up from soft power-down, in μs.
+/// This depends on the crystal, which needs to start oscillating again.
+const ESTIMATED_WAKE_UP_US: u32 = 1000;
+/// Duration of the REQA short frame (7 bits at 106 kbit/s) with some margin, in μs
+const REQA_US: u32 = 100;
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Enter soft power-down mode.
+    ///
+    /// The register contents are kept, but no communication with PICCs is possible
+    /// until [wake_up](Mfrc522::wake_up) is called.
+    /// The antenna is not switched off, see [antenna_off](Mfrc522::antenna_off).
+    pub fn power_down(&mut self) -> Result<(), Error<E>> {
+        self.rmw(Register::CommandReg, |b| b | POWER_DOWN)
+            .map_err(Error::Spi)
+    }
+
+    /// Leave soft power-down mode and wait until the oscillator is stable.
+    pub fn wake_up(&mut self) -> Result<(), Error<E>> {
+        self.rmw(Register::CommandReg, |b| b & !POWER_DOWN)
+            .map_err(Error::Spi)?;
+
+        // The PowerDown bit is cleared once the MFRC522 is ready
+        for _ in 0..5000 {
+            let command = self.read(Register::CommandReg).map_err(Error::Spi)?;
+            if command & POWER_DOWN == 0 {
+                return Ok(());
+            }
+        }
+        Err(Error::Timeout(Context::default()).with_operation(Operation::WakeUp))
+    }
+
+    /// Is the MFRC522 in soft power-down mode
+    pub fn is_powered_down(&mut self) -> Result<bool, Error<E>> {
+        let command = self.read(Register::CommandReg).map_err(Error::Spi)?;
+        Ok(command & POWER_DOWN != 0)
+    }
+
+    /// Switch on the 13.56 MHz energy carrier on the antenna drivers (TX1 and TX2)
+    /// enabled in the [RfConfig](crate::rf::RfConfig).
+    ///
+    /// The antenna is switched on by [init](Mfrc522::init).
+    pub fn antenna_on(&mut self) -> Result<(), Error<E>> {
+        self.antenna = true;
+        self.write_tx_control().map_err(Error::Spi)
+    }
+
+    /// Switch off the 13.56 MHz energy carrier, PICCs in the field lose their power.
+    ///
+    /// The antenna stays off when the RF configuration is changed, until
+    /// [antenna_on](Mfrc522::antenna_on) is called.
+    pub fn antenna_off(&mut self) -> Result<(), Error<E>> {
+        self.antenna = false;
+        self.write_tx_control().map_err(Error::Spi)
+    }
+
+    /// Is the energy carrier switched on for at least one antenna driver
+    pub fn is_antenna_on(&mut self) -> Result<bool, Error<E>> {
+        let tx_control = self.read(Register::TxControlReg).map_err(Error::Spi)?;
+        Ok(tx_control & (TX1_RF_EN | TX2_RF_EN) != 0)
+    }
+}
+
+/// Duty-cycled card detection
+///
+/// The MFRC522 spends most of the time in soft power-down with the antenna off,
+/// see [ping](LowPowerDetector::ping).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LowPowerDetector {
+    /// Time between pings, in ms
+    interval_ms: u32,
+    /// Timeout for the REQA, in timer ticks of 25μs
+    ping_timeout: u16,
+}
+
+impl LowPowerDetector {
+    /// Create a detector that is pinged every `interval_ms` milliseconds.
+    ///
+    /// The interval is only used to estimate the duty cycle,
+    /// waiting between the pings is up to the caller.
+    pub fn new(interval_ms: u32) -> Self {
+        LowPowerDetector {
+            interval_ms,
+            ping_timeout: DEFAULT_PING_TIMEOUT,
+        }
+    }
+
+    /// Set the timeout for the REQA, in timer ticks of 25μs
+    pub fn with_ping_timeout(self, ticks: u16) -> Self {
+        LowPowerDetector {
+            ping_timeout: ticks.max(1),
+            ..self
+        }
+    }
+
+    pub fn interval_ms(&self) -> u32 {
+        self.interval_ms
+    }
+
+    /// Estimated time the MFRC522 is awake for a single ping without a PICC, in μs
+    pub fn awake_time_us(&self) -> u32 {
+        ESTIMATED_WAKE_UP_US + REQA_US + self.ping_timeout as u32 * 25
+    }
+
+    /// Estimated fraction of the time the MFRC522 is awake, between 0 and 1
+    pub fn duty_cycle(&self) -> f32 {
+        let interval_us = self.interval_ms as f32 * 1000.0;
+        if interval_us <= 0.0 {
+            return 1.0;
+        }
+        (self.awake_time_us() as f32 / interval_us).min(1.0)
+    }
+
+    /// Wake up the MFRC522, switch on the antenna and send a REQA with a short timeout.
+    ///
+    /// If no PICC answered, the antenna is switched off and the MFRC522 is put in
+    /// soft power-down mode again, and `None` is returned.
+    /// Otherwise the MFRC522 is left awake and the ATQA is returned,
+    /// so the PICC can be selected.
+    ///
+    /// Errors other than a timeout also leave the MFRC522 awake,
+    /// they may indicate multiple PICCs are in the field.
+    ///
+    /// ISO/IEC 14443-3 allows PICCs up to 5ms after the field is switched on to
+    /// be ready. The REQA is sent right after switching on the field: most PICCs are ready
+    /// much sooner, slower PICCs may be missed.
+    pub fn ping<E, SPI, NSS, D>(
+        &self,
+        mfrc522: &mut Mfrc522<SPI, NSS, D, Initialized>,
+    ) -> Result<Option<AtqA>, Error<E>>
+    where
+        SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+        Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+    {
+        mfrc522.wake_up()?;
+        mfrc522.antenna_on()?;
+
+        mfrc522.set_timeout(self.ping_timeout).map_err(Error::Spi)?;
+        let result = mfrc522.reqa();
+        mfrc522.set_timeout(DEFAULT_TIMEOUT).map_err(Error::Spi)?;
+
+        match result {
+            Ok(atqa) => Ok(Some(atqa)),
+            Err(Error::Timeout(_)) => {
+                mfrc522.antenna_off()?;
+                mfrc522.power_down()?;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Random numbers and the internal buffer of the MFRC522.
+//!
+//! The MFRC522 has a 25 byte internal buffer, written and read with the Mem command
+//! ([mem_store](crate::Mfrc522::mem_store) and [mem_load](crate::Mfrc522::mem_load)).
+//! The Generate RandomID command stores a 10 byte random number in its first 10 bytes,
+//! [random_id](crate::Mfrc522::random_id) runs it and reads the result back.
+//!
+//! With the `crypto` feature, `Rng` adapts the random numbers to `rand_core::RngCore`, eg for
+//! the random challenges of the authentications. It borrows the driver, so while a
+//! [Card](crate::Card) is in use, seed a software generator from it beforehand:
+//!
+//! ```ignore
+//! let mut rng = rand_chacha::ChaCha20Rng::from_rng(mfrc522.rng())?;
+//! let atqa = mfrc522.reqa()?;
+//! let mut card = mfrc522.select(&atqa)?;
+//! card.ulc_authenticate(&key, &mut rng)?;
+//! ```
+
+use crate::error::{Context, Error, Operation};
+use crate::register::*;
+use crate::{spi, Initialized, Mfrc522, WithNssDelay};
+
+/// Size of the internal buffer
+pub const MEM_SIZE: usize = 25;
+/// Size of the random number of the Generate RandomID command
+pub const RANDOM_ID_SIZE: usize = 10;
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Generate a 10 byte random number.
+    ///
+    /// The number is generated in the internal buffer, overwriting its first 10 bytes.
+    pub fn random_id(&mut self) -> Result<[u8; RANDOM_ID_SIZE], Error<E>> {
+        self.run_command(Command::GenerateRandomId)?;
+        let mem = self.mem_load()?;
+        Ok(mem[..RANDOM_ID_SIZE].try_into().unwrap())
+    }
+
+    /// Write the 25 bytes of the internal buffer
+    pub fn mem_store(&mut self, data: &[u8; MEM_SIZE]) -> Result<(), Error<E>> {
+        self.command(Command::Idle).map_err(Error::Spi)?;
+        self.fifo_flush().map_err(Error::Spi)?;
+        self.write_many(Register::FIFODataReg, data)?;
+        self.run_command(Command::Mem)
+    }
+
+    /// Read the 25 bytes of the internal buffer
+    pub fn mem_load(&mut self) -> Result<[u8; MEM_SIZE], Error<E>> {
+        self.command(Command::Idle).map_err(Error::Spi)?;
+        // the Mem command only copies the internal buffer to an empty FIFO buffer
+        self.fifo_flush().map_err(Error::Spi)?;
+        self.run_command(Command::Mem)?;
+
+        let mut data = [0u8; MEM_SIZE];
+        self.read_many(Register::FIFODataReg, &mut data)?;
+        Ok(data)
+    }
+
+    /// Adapt [random_id](Mfrc522::random_id) to [rand_core::RngCore]
+    #[cfg(feature = "crypto")]
+    pub fn rng(&mut self) -> Rng<'_, SPI, NSS, D> {
+        Rng { mfrc522: self }
+    }
+
+    /// Run a command that terminates by itself
+    fn run_command(&mut self, command: Command) -> Result<(), Error<E>> {
+        self.command(command).map_err(Error::Spi)?;
+        for _ in 0..5000 {
+            let current = self.read(Register::CommandReg).map_err(Error::Spi)? & 0x0F;
+            if current == u8::from(Command::Idle) {
+                return Ok(());
+            }
+        }
+        Err(Error::Timeout(Context::default()).with_operation(Operation::Command))
+    }
+}
+
+/// [rand_core::RngCore] generating random numbers with the MFRC522, see
+/// [random_id](Mfrc522::random_id).
+///
+/// The MFRC522 does not specify the quality of its random numbers, so it is not a
+/// [rand_core::CryptoRng]. `next_u32`, `next_u64` and `fill_bytes` panic if the communication
+/// with the MFRC522 fails, `try_fill_bytes` returns the error instead.
+#[cfg(feature = "crypto")]
+pub struct Rng<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8> + spi::Write<u8, Error = <SPI as spi::Transfer<u8>>::Error>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    mfrc522: &'a mut Mfrc522<SPI, NSS, D, Initialized>,
+}
+
+#[cfg(feature = "crypto")]
+impl<'a, E, SPI, NSS, D> rand_core::RngCore for Rng<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .expect("generating a random number with the MFRC522 failed")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        for chunk in dest.chunks_mut(RANDOM_ID_SIZE) {
+            let random = self.mfrc522.random_id().map_err(|_| {
+                rand_core::Error::from(
+                    core::num::NonZeroU32::new(rand_core::Error::CUSTOM_START).unwrap(),
+                )
+            })?;
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! Raw frames, for commands that are not implemented by this crate.
+//!
+//! A [RawFrame] describes the bits to transmit and how the response is framed.
+//! The response is stored in a buffer provided by the caller, [RawResponse] tells how many
+//! bits were received and where the first collision occurred.
+//!
+//! ```ignore
+//! // Send the 7-bit "unlock" command of a UID changeable MIFARE Classic
+//! let frame = RawFrame::new(&[0x40]).with_last_bits(7);
+//! let mut rx = [0u8; 1];
+//! let response = mfrc522.transceive_raw(&frame, &mut rx)?;
+//! if response.valid_bits() == 4 && rx[0] == 0x0A {
+//!     // ACK
+//! }
+//! ```
+
+use crate::error::Error;
+use crate::register::*;
+use crate::{spi, FifoData, Initialized, Mfrc522, WithNssDelay, MAX_FRAME_SIZE};
+
+/// Frame to transmit with [transceive_raw](Mfrc522::transceive_raw)
+///
+/// By default, all bits of all bytes are transmitted, with parity bits and without CRC_A.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawFrame<'a> {
+    bytes: &'a [u8],
+    last_bits: u8,
+    rx_align: u8,
+    crc: bool,
+    parity: bool,
+}
+
+impl<'a> RawFrame<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        RawFrame {
+            bytes,
+            last_bits: 0,
+            rx_align: 0,
+            crc: false,
+            parity: true,
+        }
+    }
+
+    /// Only transmit the given number of bits of the last byte (1 to 7, 0 for all bits)
+    pub fn with_last_bits(self, last_bits: u8) -> Self {
+        RawFrame {
+            last_bits: last_bits & 0b111,
+            ..self
+        }
+    }
+
+    /// Store the first received bit at this bit position of the first byte (0 to 7)
+    pub fn with_rx_align(self, rx_align: u8) -> Self {
+        RawFrame {
+            rx_align: rx_align & 0b111,
+            ..self
+        }
+    }
+
+    /// Append the CRC_A to the frame, and verify and remove the CRC_A of the response.
+    ///
+    /// Only possible for whole bytes: with [with_last_bits](RawFrame::with_last_bits) or
+    /// [with_rx_align](RawFrame::with_rx_align), [Error::Unsupported] is returned.
+    /// A 4-bit response (ACK or NAK) has no CRC_A, it is returned as is.
+    pub fn with_crc(self, crc: bool) -> Self {
+        RawFrame { crc, ..self }
+    }
+
+    /// Generate and check parity bits (MfRxReg ParityDisable).
+    ///
+    /// Without parity, the parity bits have to be included in the data,
+    /// eg for the encrypted frames of MIFARE Classic.
+    pub fn with_parity(self, parity: bool) -> Self {
+        RawFrame { parity, ..self }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+/// Response to a [RawFrame]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawResponse {
+    valid_bytes: usize,
+    valid_bits: u8,
+    collision: Option<u8>,
+}
+
+impl RawResponse {
+    /// Number of bytes received, including a last byte with only some valid bits
+    pub fn valid_bytes(&self) -> usize {
+        self.valid_bytes
+    }
+
+    /// Number of valid bits in the last byte, 0 if all bits are valid
+    pub fn valid_bits(&self) -> u8 {
+        self.valid_bits
+    }
+
+    /// Total number of bits received
+    pub fn bit_len(&self) -> usize {
+        match self.valid_bits {
+            0 => self.valid_bytes * 8,
+            bits => (self.valid_bytes - 1) * 8 + bits as usize,
+        }
+    }
+
+    /// Position of the first bit with a collision, starting at 1 (CollReg CollPos).
+    ///
+    /// `None` if there was no collision, or if it was beyond the first 32 bits.
+    pub fn collision(&self) -> Option<u8> {
+        self.collision
+    }
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Transmit a raw frame to the PICC and store the response in `rx`.
+    ///
+    /// A collision is not an error, the bits received up to the collision are returned,
+    /// together with its position. Returns [Error::NoRoom] if `rx` is too small.
+    pub fn transceive_raw(
+        &mut self,
+        frame: &RawFrame,
+        rx: &mut [u8],
+    ) -> Result<RawResponse, Error<E>> {
+        if frame.crc && (frame.last_bits != 0 || frame.rx_align != 0) {
+            return Err(Error::Unsupported);
+        }
+
+        if !frame.parity {
+            self.rmw(Register::MfRxReg, |b| b | PARITY_DISABLE)
+                .map_err(Error::Spi)?;
+        }
+        let result = self.transceive_frame(frame);
+        if !frame.parity {
+            self.rmw(Register::MfRxReg, |b| b & !PARITY_DISABLE)
+                .map_err(Error::Spi)?;
+        }
+
+        let (fifo_data, collision) = result?;
+        let n = fifo_data.valid_bytes;
+        rx.get_mut(..n)
+            .ok_or(Error::NoRoom)?
+            .copy_from_slice(&fifo_data.buffer[..n]);
+        Ok(RawResponse {
+            valid_bytes: n,
+            valid_bits: fifo_data.valid_bits as u8,
+            collision,
+        })
+    }
+
+    fn transceive_frame(
+        &mut self,
+        frame: &RawFrame,
+    ) -> Result<(FifoData<MAX_FRAME_SIZE>, Option<u8>), Error<E>> {
+        let mut fifo_data = FifoData::new();
+        let result = if frame.crc {
+            self.transceive_crc_into(frame.bytes, true, &mut fifo_data)
+        } else {
+            self.transceive_into(frame.bytes, frame.last_bits, frame.rx_align, &mut fifo_data)
+        };
+
+        match result {
+            Ok(()) => Ok((fifo_data, None)),
+            Err(Error::Collision(_)) => {
+                let coll = self.read(Register::CollReg).map_err(Error::Spi)?;
+                let collision = match coll & 0x1F {
+                    _ if coll & COLL_POS_NOT_VALID != 0 => None,
+                    0 => Some(32),
+                    pos => Some(pos),
+                };
+                Ok((fifo_data, collision))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
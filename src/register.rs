@@ -1,7 +1,7 @@
 //! MFRC522 register definitions
 
 /// List of all registers for the MFRC522
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Register {
     /// Starts and stops command execution.
@@ -125,6 +125,64 @@ impl Register {
     pub fn write_address(&self) -> u8 {
         ((*self as u8) << 1) | Self::W
     }
+
+    /// The register at a 6 bit address, `None` for the reserved addresses
+    pub fn from_address(address: u8) -> Option<Register> {
+        let register = match address {
+            0x01 => Register::CommandReg,
+            0x02 => Register::ComlEnReg,
+            0x03 => Register::DivlEnReg,
+            0x04 => Register::ComIrqReg,
+            0x05 => Register::DivIrqReg,
+            0x06 => Register::ErrorReg,
+            0x07 => Register::Status1Reg,
+            0x08 => Register::Status2Reg,
+            0x09 => Register::FIFODataReg,
+            0x0A => Register::FIFOLevelReg,
+            0x0B => Register::WaterLevelReg,
+            0x0C => Register::ControlReg,
+            0x0D => Register::BitFramingReg,
+            0x0E => Register::CollReg,
+            0x11 => Register::ModeReg,
+            0x12 => Register::TxModeReg,
+            0x13 => Register::RxModeReg,
+            0x14 => Register::TxControlReg,
+            0x15 => Register::TxASKReg,
+            0x16 => Register::TxSelReg,
+            0x17 => Register::RxSelReg,
+            0x18 => Register::RxThresholdReg,
+            0x19 => Register::DemodReg,
+            0x1C => Register::MfTxReg,
+            0x1D => Register::MfRxReg,
+            0x1F => Register::SerialSpeedReg,
+            0x21 => Register::CRCResultRegHigh,
+            0x22 => Register::CRCResultRegLow,
+            0x24 => Register::ModWidthReg,
+            0x26 => Register::RFCfgReg,
+            0x27 => Register::GsNReg,
+            0x28 => Register::CWGsPReg,
+            0x29 => Register::ModGsPReg,
+            0x2A => Register::TModeReg,
+            0x2B => Register::TPrescalerReg,
+            0x2C => Register::TReloadRegHigh,
+            0x2D => Register::TReloadRegLow,
+            0x2E => Register::TCounterValRegHigh,
+            0x2F => Register::TCounterValRegLow,
+            0x31 => Register::TestSel1Reg,
+            0x32 => Register::TestSel2Reg,
+            0x33 => Register::TestPinEnReg,
+            0x34 => Register::TestPinValueReg,
+            0x35 => Register::TestBusReg,
+            0x36 => Register::AutoTestReg,
+            0x37 => Register::VersionReg,
+            0x38 => Register::AnalogTestReg,
+            0x39 => Register::TestDAC1Reg,
+            0x3A => Register::TestDAC2Reg,
+            0x3B => Register::TestADCReg,
+            _ => return None,
+        };
+        Some(register)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -168,10 +226,16 @@ pub const POWER_DOWN: u8 = 1 << 4;
 pub const TIMER_IRQ: u8 = 1 << 0;
 /// ComIrqReg: an error bit in ErrorReg is set
 pub const ERR_IRQ: u8 = 1 << 1;
+/// ComIrqReg: the FIFO buffer is almost empty (Status1Reg LoAlert)
+pub const LO_ALERT_IRQ: u8 = 1 << 2;
+/// ComIrqReg: the FIFO buffer is almost full (Status1Reg HiAlert)
+pub const HI_ALERT_IRQ: u8 = 1 << 3;
 /// ComIrqReg: TODO
 pub const IDLE_IRQ: u8 = 1 << 4;
 /// ComIrqReg: receiver detected the end of a valid data stream
 pub const RX_IRQ: u8 = 1 << 5;
+/// ComIrqReg: the last bit of the transmitted data was sent out
+pub const TX_IRQ: u8 = 1 << 6;
 
 /// DivIrqReg: CalcCRC command is active and all data is processed
 pub const CRC_IRQ: u8 = 1 << 2;
@@ -199,6 +263,30 @@ pub const WR_ERR: u8 = 1 << 7;
 /// and ErrorReg register’s BufferOvfl bit
 pub const FLUSH_BUFFER: u8 = 1 << 7;
 
+/// TxControlReg: output signal on pin TX1 delivers the 13.56 MHz energy carrier
+pub const TX1_RF_EN: u8 = 1 << 0;
+/// TxControlReg: output signal on pin TX2 delivers the 13.56 MHz energy carrier
+pub const TX2_RF_EN: u8 = 1 << 1;
+
+/// CollReg: no collision detected or the position of the collision is out of the range of CollPos
+pub const COLL_POS_NOT_VALID: u8 = 1 << 5;
+
+/// Status2Reg: the MIFARE Crypto1 unit is switched on, set by a successful MFAuthent command
+pub const MF_CRYPTO1_ON: u8 = 1 << 3;
+
+/// MfRxReg: generation of the parity bit for transmission and the parity check
+/// for receiving is switched off
+pub const PARITY_DISABLE: u8 = 1 << 4;
+
+/// TxModeReg TxCRCEn and RxModeReg RxCRCEn: CRC generation during transmission
+/// and CRC check during reception
+pub const CRC_EN: u8 = 1 << 7;
+/// TxModeReg TxSpeed and RxModeReg RxSpeed: transfer speed
+pub const SPEED: u8 = 0b111 << 4;
+
 /// TxASKReg: forces a 100 % ASK modulation independent of the ModGsPReg
 /// register setting
 pub const FORCE_100_ASK: u8 = 1 << 6;
+
+/// AutoTestReg SelfTest: value enabling the digital self test, started by the CalcCRC command
+pub const SELF_TEST: u8 = 0x09;
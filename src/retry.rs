@@ -0,0 +1,133 @@
+//! Automatic retries of block operations after transient RF errors.
+//!
+//! A PICC at the edge of the field occasionally answers with a wrong CRC_A or parity, or not
+//! at all. With a [RetryPolicy] of more than one attempt, the block operations of
+//! [AuthenticatedSector](crate::AuthenticatedSector) and the MIFARE Ultralight page
+//! operations of [Card](crate::Card) re-establish the session after such an error and try
+//! again: the PICC is woken up with WUPA and selected again (it has to be the same UID),
+//! and a MIFARE Classic sector is authenticated again with the same key.
+//!
+//! The policy is off by default (a single attempt).
+//!
+//! ```ignore
+//! let policy = RetryPolicy {
+//!     attempts: 3,
+//!     backoff: Some(|_retry| cortex_m::asm::delay(480_000)),
+//!     ..RetryPolicy::default()
+//! };
+//! let mut mfrc522 = Mfrc522::new(spi).with_retry_policy(policy).init()?;
+//! ```
+
+use crate::error::{Error, Nak};
+use crate::{spi, Initialized, Mfrc522, MifareKey, Uid, WithNssDelay};
+
+/// How block operations are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of attempts, including the first one, 1 disables retries
+    pub attempts: u8,
+    /// The errors that are retried
+    pub retry_on: RetryOn,
+    /// Called before re-establishing the session, with the number of the retry (starting at 1),
+    /// eg to wait a bit longer after each failure
+    pub backoff: Option<fn(u8)>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 1,
+            retry_on: RetryOn::default(),
+            backoff: None,
+        }
+    }
+}
+
+/// The errors that are retried, the transient RF errors except collisions by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryOn {
+    /// [Error::Crc]
+    pub crc: bool,
+    /// [Error::Parity]
+    pub parity: bool,
+    /// [Error::Timeout], the PICC did not answer
+    pub timeout: bool,
+    /// [Error::Collision], another PICC answered as well. Not retried by default,
+    /// it usually persists as long as both PICCs are in the field.
+    pub collision: bool,
+    /// [Error::IncompleteFrame]
+    pub incomplete_frame: bool,
+    /// [Nak::TransmissionError], the PICC received the command with a parity or CRC error
+    pub transmission_nak: bool,
+}
+
+impl Default for RetryOn {
+    fn default() -> Self {
+        RetryOn {
+            crc: true,
+            parity: true,
+            timeout: true,
+            collision: false,
+            incomplete_frame: true,
+            transmission_nak: true,
+        }
+    }
+}
+
+impl RetryOn {
+    /// Is `error` to be retried
+    pub fn matches<E>(&self, error: &Error<E>) -> bool {
+        match error {
+            Error::Crc(_) => self.crc,
+            Error::Parity(_) => self.parity,
+            Error::Timeout(_) => self.timeout,
+            Error::Collision(_) => self.collision,
+            Error::IncompleteFrame(_) => self.incomplete_frame,
+            Error::Nak(Nak::TransmissionError, _) => self.transmission_nak,
+            _ => false,
+        }
+    }
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Run `operation` on the PICC with `uid`, retrying it according to the [RetryPolicy].
+    ///
+    /// `auth` is the block and key a MIFARE Classic sector was authenticated with.
+    pub(crate) fn retry<T>(
+        &mut self,
+        uid: &Uid,
+        auth: Option<(u8, &MifareKey)>,
+        mut operation: impl FnMut(&mut Self) -> Result<T, Error<E>>,
+    ) -> Result<T, Error<E>> {
+        let policy = self.retry_policy;
+        let mut result = operation(self);
+        for retry in 1..policy.attempts {
+            match &result {
+                Err(e) if policy.retry_on.matches(e) => {}
+                _ => break,
+            }
+            debug!("retry {} of {}", retry, policy.attempts - 1);
+            if let Some(backoff) = policy.backoff {
+                backoff(retry);
+            }
+            result = self.reestablish(uid, auth).and_then(|()| operation(self));
+        }
+        result
+    }
+
+    /// Select the PICC again and authenticate the sector again
+    fn reestablish(&mut self, uid: &Uid, auth: Option<(u8, &MifareKey)>) -> Result<(), Error<E>> {
+        // the PICC left the authenticated state on the error, or does so when it receives
+        // the plain HLTA of the reactivation
+        self.stop_crypto1()?;
+        self.reactivate(uid)?;
+        if let Some((block, key)) = auth {
+            self.mf_authenticate(uid, block, key)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+//! Configuration of the receiver gain, the antenna drivers and the bit rate.
+//!
+//! The defaults match the reset values of the MFRC522, which suit the typical
+//! reader modules. Small antennas usually need a higher receiver gain,
+//! the driver conductances determine the field strength and modulation depth.
+//!
+//! The bit rate is normally negotiated with [IsoDep::pps](crate::iso_dep::IsoDep::pps),
+//! it falls back to 106 kbit/s on every REQA and WUPA.
+//!
+//! ```ignore
+//! let config = RfConfig {
+//!     rx_gain: RxGain::Db48,
+//!     ..RfConfig::default()
+//! };
+//! let mut mfrc522 = Mfrc522::new(spi).with_rf_config(config).init()?;
+//! ```
+
+use crate::error::Error;
+use crate::register::*;
+use crate::{spi, Initialized, Mfrc522, State, WithNssDelay};
+
+/// TxControlReg: TX1 output is inverted when the driver is switched off
+const INV_TX1_RF_OFF: u8 = 1 << 4;
+/// TxControlReg: TX2 output is inverted when the driver is switched off
+const INV_TX2_RF_OFF: u8 = 1 << 5;
+/// TxControlReg: TX1 output is inverted when the driver is switched on
+const INV_TX1_RF_ON: u8 = 1 << 6;
+/// TxControlReg: TX2 output is inverted when the driver is switched on
+const INV_TX2_RF_ON: u8 = 1 << 7;
+
+/// Receiver gain (RFCfgReg RxGain)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxGain {
+    Db18,
+    Db23,
+    Db33,
+    Db38,
+    Db43,
+    Db48,
+}
+
+impl RxGain {
+    /// The gain in dB
+    pub fn db(&self) -> u8 {
+        match self {
+            RxGain::Db18 => 18,
+            RxGain::Db23 => 23,
+            RxGain::Db33 => 33,
+            RxGain::Db38 => 38,
+            RxGain::Db43 => 43,
+            RxGain::Db48 => 48,
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self {
+            RxGain::Db18 => 0b000,
+            RxGain::Db23 => 0b001,
+            RxGain::Db33 => 0b100,
+            RxGain::Db38 => 0b101,
+            RxGain::Db43 => 0b110,
+            RxGain::Db48 => 0b111,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        // 0b010 and 0b011 are duplicates of 18 dB and 23 dB
+        match bits & 0b111 {
+            0b000 | 0b010 => RxGain::Db18,
+            0b001 | 0b011 => RxGain::Db23,
+            0b100 => RxGain::Db33,
+            0b101 => RxGain::Db38,
+            0b110 => RxGain::Db43,
+            _ => RxGain::Db48,
+        }
+    }
+}
+
+/// Bit rate of the communication between the MFRC522 and the PICC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BitRate {
+    /// fc/128, used for polling, anticollision and activation
+    Kbps106,
+    /// fc/64
+    Kbps212,
+    /// fc/32
+    Kbps424,
+    /// fc/16
+    Kbps848,
+}
+
+impl BitRate {
+    /// The bit rate in kbit/s
+    pub fn kbps(&self) -> u16 {
+        match self {
+            BitRate::Kbps106 => 106,
+            BitRate::Kbps212 => 212,
+            BitRate::Kbps424 => 424,
+            BitRate::Kbps848 => 848,
+        }
+    }
+
+    /// TxSpeed/RxSpeed value, which is also the divisor integer (DSI/DRI) of ISO/IEC 14443-4
+    pub(crate) fn bits(&self) -> u8 {
+        match self {
+            BitRate::Kbps106 => 0b000,
+            BitRate::Kbps212 => 0b001,
+            BitRate::Kbps424 => 0b010,
+            BitRate::Kbps848 => 0b011,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b111 {
+            0b000 => Some(BitRate::Kbps106),
+            0b001 => Some(BitRate::Kbps212),
+            0b010 => Some(BitRate::Kbps424),
+            0b011 => Some(BitRate::Kbps848),
+            _ => None,
+        }
+    }
+
+    /// ModWidthReg value: the pause length of the modified Miller coding.
+    ///
+    /// The reset value (0x26, about 2.9μs) is meant for 106 kbit/s, it is scaled down with the
+    /// bit period for the higher bit rates and stays below half the bit period as required.
+    fn mod_width(&self) -> u8 {
+        // https://www.nxp.com/docs/en/data-sheet/MFRC522.pdf
+        // 9.3.3.4 ModWidthReg register, Table 94: the pause lasts (ModWidth + 1) / fclk,
+        // at most half the bit period (64, 32, 16 and 8 carrier cycles).
+        // 0x26: 39 cycles (reset value), 0x13: 20, 0x09: 10, 0x04: 5
+        match self {
+            BitRate::Kbps106 => 0x26,
+            BitRate::Kbps212 => 0x13,
+            BitRate::Kbps424 => 0x09,
+            BitRate::Kbps848 => 0x04,
+        }
+    }
+}
+
+/// Antenna driver pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxPin {
+    Tx1,
+    Tx2,
+}
+
+/// Configuration of an antenna driver pin (TxControlReg)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxDriver {
+    /// Deliver the modulated 13.56 MHz energy carrier
+    pub enabled: bool,
+    /// Invert the output when the driver is switched on
+    pub invert_on: bool,
+    /// Invert the output when the driver is switched off
+    pub invert_off: bool,
+}
+
+impl TxDriver {
+    fn masks(pin: TxPin) -> (u8, u8, u8) {
+        match pin {
+            TxPin::Tx1 => (TX1_RF_EN, INV_TX1_RF_ON, INV_TX1_RF_OFF),
+            TxPin::Tx2 => (TX2_RF_EN, INV_TX2_RF_ON, INV_TX2_RF_OFF),
+        }
+    }
+
+    fn from_register(pin: TxPin, tx_control: u8) -> Self {
+        let (enabled, invert_on, invert_off) = Self::masks(pin);
+        TxDriver {
+            enabled: tx_control & enabled != 0,
+            invert_on: tx_control & invert_on != 0,
+            invert_off: tx_control & invert_off != 0,
+        }
+    }
+
+    fn apply(&self, pin: TxPin, tx_control: u8) -> u8 {
+        let (enabled, invert_on, invert_off) = Self::masks(pin);
+        let mut value = tx_control & !(enabled | invert_on | invert_off);
+        if self.enabled {
+            value |= enabled;
+        }
+        if self.invert_on {
+            value |= invert_on;
+        }
+        if self.invert_off {
+            value |= invert_off;
+        }
+        value
+    }
+}
+
+/// Receiver and antenna driver configuration, applied by [init](Mfrc522::init)
+///
+/// The conductances are limited to their register width:
+/// 4 bits for the n-driver and 6 bits for the p-driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfConfig {
+    pub rx_gain: RxGain,
+    /// Conductance of the n-driver when there is no modulation (GsNReg CWGsN, 0 to 15)
+    pub cw_gs_n: u8,
+    /// Conductance of the n-driver during modulation (GsNReg ModGsN, 0 to 15)
+    pub mod_gs_n: u8,
+    /// Conductance of the p-driver when there is no modulation (CWGsPReg CWGsP, 0 to 63)
+    pub cw_gs_p: u8,
+    /// Conductance of the p-driver during modulation (ModGsPReg ModGsP, 0 to 63)
+    pub mod_gs_p: u8,
+    pub tx1: TxDriver,
+    pub tx2: TxDriver,
+}
+
+impl Default for RfConfig {
+    /// The reset values, with both antenna drivers enabled
+    fn default() -> Self {
+        RfConfig {
+            rx_gain: RxGain::Db33,
+            cw_gs_n: 0x8,
+            mod_gs_n: 0x8,
+            cw_gs_p: 0x20,
+            mod_gs_p: 0x20,
+            tx1: TxDriver {
+                enabled: true,
+                invert_on: false,
+                invert_off: false,
+            },
+            tx2: TxDriver {
+                enabled: true,
+                invert_on: true,
+                invert_off: false,
+            },
+        }
+    }
+}
+
+impl<E, SPI, NSS, D> Mfrc522<SPI, NSS, D, Initialized>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Read the current receiver and antenna driver configuration
+    pub fn rf_config(&mut self) -> Result<RfConfig, Error<E>> {
+        let (cw_gs_n, mod_gs_n) = self.gs_n()?;
+        let (cw_gs_p, mod_gs_p) = self.gs_p()?;
+        Ok(RfConfig {
+            rx_gain: self.rx_gain()?,
+            cw_gs_n,
+            mod_gs_n,
+            cw_gs_p,
+            mod_gs_p,
+            tx1: self.tx_driver(TxPin::Tx1)?,
+            tx2: self.tx_driver(TxPin::Tx2)?,
+        })
+    }
+
+    /// Apply a receiver and antenna driver configuration,
+    /// it is also used when the MFRC522 is initialized again.
+    pub fn set_rf_config(&mut self, config: RfConfig) -> Result<(), Error<E>> {
+        self.rf_config = config;
+        self.apply_rf_config().map_err(Error::Spi)
+    }
+
+    pub fn rx_gain(&mut self) -> Result<RxGain, Error<E>> {
+        let rf_cfg = self.read(Register::RFCfgReg).map_err(Error::Spi)?;
+        Ok(RxGain::from_bits(rf_cfg >> 4))
+    }
+
+    pub fn set_rx_gain(&mut self, rx_gain: RxGain) -> Result<(), Error<E>> {
+        self.rf_config.rx_gain = rx_gain;
+        self.write_rx_gain().map_err(Error::Spi)
+    }
+
+    /// The n-driver conductances (GsNReg), without and with modulation
+    pub fn gs_n(&mut self) -> Result<(u8, u8), Error<E>> {
+        let gs_n = self.read(Register::GsNReg).map_err(Error::Spi)?;
+        Ok((gs_n >> 4, gs_n & 0x0F))
+    }
+
+    /// Set the n-driver conductances (GsNReg), without and with modulation (0 to 15)
+    pub fn set_gs_n(&mut self, cw_gs_n: u8, mod_gs_n: u8) -> Result<(), Error<E>> {
+        self.rf_config.cw_gs_n = cw_gs_n;
+        self.rf_config.mod_gs_n = mod_gs_n;
+        self.write_gs_n().map_err(Error::Spi)
+    }
+
+    /// The p-driver conductances (CWGsPReg and ModGsPReg), without and with modulation
+    pub fn gs_p(&mut self) -> Result<(u8, u8), Error<E>> {
+        let cw_gs_p = self.read(Register::CWGsPReg).map_err(Error::Spi)?;
+        let mod_gs_p = self.read(Register::ModGsPReg).map_err(Error::Spi)?;
+        Ok((cw_gs_p & 0x3F, mod_gs_p & 0x3F))
+    }
+
+    /// Set the p-driver conductances (CWGsPReg and ModGsPReg),
+    /// without and with modulation (0 to 63)
+    pub fn set_gs_p(&mut self, cw_gs_p: u8, mod_gs_p: u8) -> Result<(), Error<E>> {
+        self.rf_config.cw_gs_p = cw_gs_p;
+        self.rf_config.mod_gs_p = mod_gs_p;
+        self.write_gs_p().map_err(Error::Spi)
+    }
+
+    pub fn tx_driver(&mut self, pin: TxPin) -> Result<TxDriver, Error<E>> {
+        let tx_control = self.read(Register::TxControlReg).map_err(Error::Spi)?;
+        Ok(TxDriver::from_register(pin, tx_control))
+    }
+
+    pub fn set_tx_driver(&mut self, pin: TxPin, driver: TxDriver) -> Result<(), Error<E>> {
+        match pin {
+            TxPin::Tx1 => self.rf_config.tx1 = driver,
+            TxPin::Tx2 => self.rf_config.tx2 = driver,
+        }
+        self.write_tx_control().map_err(Error::Spi)
+    }
+
+    /// The bit rates used to transmit to the PICC and to receive from the PICC,
+    /// `None` if TxSpeed or RxSpeed is set to one of the reserved values.
+    pub fn bit_rate(&mut self) -> Result<(Option<BitRate>, Option<BitRate>), Error<E>> {
+        let tx_mode = self.read(Register::TxModeReg).map_err(Error::Spi)?;
+        let rx_mode = self.read(Register::RxModeReg).map_err(Error::Spi)?;
+        Ok((
+            BitRate::from_bits(tx_mode >> 4),
+            BitRate::from_bits(rx_mode >> 4),
+        ))
+    }
+
+    /// Set the bit rates used to transmit to the PICC and to receive from the PICC.
+    ///
+    /// The PICC has to be switched to the same bit rates first, see
+    /// [IsoDep::pps](crate::iso_dep::IsoDep::pps). The modulation width is adjusted to the
+    /// transmit bit rate. ModGsPReg is left as configured: the modulation is forced to
+    /// 100 % ASK at all bit rates, which makes the MFRC522 ignore it.
+    pub fn set_bit_rate(&mut self, tx: BitRate, rx: BitRate) -> Result<(), Error<E>> {
+        self.write_bit_rate(tx, rx).map_err(Error::Spi)
+    }
+}
+
+impl<E, SPI, NSS, D, S: State> Mfrc522<SPI, NSS, D, S>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, S>: WithNssDelay,
+{
+    /// Write the stored RF configuration to the registers
+    pub(crate) fn apply_rf_config(&mut self) -> Result<(), E> {
+        self.write_rx_gain()?;
+        self.write_gs_n()?;
+        self.write_gs_p()?;
+        self.write_tx_control()
+    }
+
+    /// Set TxSpeed, RxSpeed and the modulation width, the other mode bits are kept
+    pub(crate) fn write_bit_rate(&mut self, tx: BitRate, rx: BitRate) -> Result<(), E> {
+        let tx_mode = (self.tx_mode & !SPEED) | (tx.bits() << 4);
+        let rx_mode = (self.rx_mode & !SPEED) | (rx.bits() << 4);
+        if tx_mode != self.tx_mode {
+            self.write(Register::ModWidthReg, tx.mod_width())?;
+        }
+        self.write_modes(tx_mode, rx_mode)
+    }
+
+    fn write_rx_gain(&mut self) -> Result<(), E> {
+        let bits = self.rf_config.rx_gain.bits();
+        self.rmw(Register::RFCfgReg, |b| (b & !0x70) | (bits << 4))
+    }
+
+    fn write_gs_n(&mut self) -> Result<(), E> {
+        let config = self.rf_config;
+        self.write(
+            Register::GsNReg,
+            (config.cw_gs_n.min(0x0F) << 4) | config.mod_gs_n.min(0x0F),
+        )
+    }
+
+    fn write_gs_p(&mut self) -> Result<(), E> {
+        let config = self.rf_config;
+        self.rmw(Register::CWGsPReg, |b| {
+            (b & !0x3F) | config.cw_gs_p.min(0x3F)
+        })?;
+        self.rmw(Register::ModGsPReg, |b| {
+            (b & !0x3F) | config.mod_gs_p.min(0x3F)
+        })
+    }
+
+    /// Write the antenna driver configuration, the drivers stay off while the antenna is off
+    pub(crate) fn write_tx_control(&mut self) -> Result<(), E> {
+        let mut config = self.rf_config;
+        config.tx1.enabled &= self.antenna;
+        config.tx2.enabled &= self.antenna;
+        self.rmw(Register::TxControlReg, |b| {
+            config
+                .tx2
+                .apply(TxPin::Tx2, config.tx1.apply(TxPin::Tx1, b))
+        })
+    }
+}
@@ -0,0 +1,909 @@
+//! Register-level simulator of the MFRC522, to test without hardware (`sim` feature).
+//!
+//! [Simulator] implements the SPI traits and emulates the register file of the MFRC522:
+//! the command state machine, the FIFO buffer with its water level warnings, the interrupt,
+//! error and collision registers, the CRC coprocessor and the timeout of the timer.
+//!
+//! The PICCs in the RF field are represented by a [Picc], which answers the frames transmitted
+//! by the MFRC522. Frames are exchanged as on air: including the CRC_A, without parity bits.
+//! [Classic] and [Ultralight] simulate MIFARE Classic, MIFARE Ultralight and NTAG21x PICCs,
+//! [Iso14443a] takes care of the activation for custom PICCs. Several PICCs in the field
+//! (arrays and tuples) answer at the same time, which results in bit collisions.
+//! While the MIFARE Crypto1 unit is switched on, frames are encrypted with [Crypto1].
+//!
+//! Time only advances when ComIrqReg is polled: each read moves the transmission or reception
+//! one step further, so frames longer than the FIFO buffer have to be streamed like on the
+//! real MFRC522. Likewise, after leaving soft power-down, CommandReg PowerDown keeps reading 1
+//! for a few reads while the oscillator starts up.
+//!
+//! ```
+//! # use mfrc522::sim::{Simulator, Ultralight};
+//! # use mfrc522::Mfrc522;
+//! # fn main() -> Result<(), mfrc522::error::Error<core::convert::Infallible>> {
+//! let uid = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+//! let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new_ntag213(uid))).init()?;
+//! let atqa = mfrc522.reqa()?;
+//! let mut card = mfrc522.select(&atqa)?;
+//! assert_eq!(card.uid().as_bytes(), &uid);
+//! let page = card.ul_read(4)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use core::convert::Infallible;
+
+use heapless::{Deque, Vec};
+
+use crate::chip::SELF_TEST_SIZE;
+use crate::crc;
+use crate::random::{MEM_SIZE, RANDOM_ID_SIZE};
+use crate::register::*;
+use crate::spi;
+
+mod classic;
+mod crypto1;
+mod iso14443a;
+mod ultralight;
+
+pub use classic::Classic;
+pub use crypto1::{prng_successor, Crypto1};
+pub use iso14443a::{Activation, Iso14443a, State};
+pub use ultralight::Ultralight;
+
+/// Size of the FIFO buffer
+const FIFO_SIZE: usize = 64;
+/// Maximum size of a simulated frame
+pub const MAX_FRAME_SIZE: usize = 256;
+/// Version reported by default, MFRC522 version 2.0
+pub const DEFAULT_VERSION: u8 = 0x92;
+
+/// ComIrqReg: writing 1 sets the marked bits instead of clearing them
+const SET1: u8 = 1 << 7;
+/// CommandReg: the analog part of the receiver is switched off
+const RCV_OFF: u8 = 1 << 5;
+/// BitFramingReg: start the transmission of the data in the FIFO buffer (Transceive)
+const START_SEND: u8 = 1 << 7;
+/// CollReg: received bits are not cleared after a collision
+const VALUES_AFTER_COLL: u8 = 1 << 7;
+/// TModeReg: the timer starts automatically at the end of the transmission
+const T_AUTO: u8 = 1 << 7;
+/// Status1Reg bits
+const LO_ALERT: u8 = 1 << 0;
+const HI_ALERT: u8 = 1 << 1;
+const IRQ: u8 = 1 << 4;
+const CRC_READY: u8 = 1 << 5;
+const CRC_OK: u8 = 1 << 6;
+/// Self test result of the MFRC522 version 1.0, as listed in the datasheet (section 16.1.1)
+const SELF_TEST_V1: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xC6, 0x37, 0xD5, 0x32, 0xB7, 0x57, 0x5C, 0xC2, 0xD8, 0x7C, 0x4D, 0xD9, 0x70, 0xC7, 0x73,
+    0x10, 0xE6, 0xD2, 0xAA, 0x5E, 0xA1, 0x3E, 0x5A, 0x14, 0xAF, 0x30, 0x61, 0xC9, 0x70, 0xDB, 0x2E,
+    0x64, 0x22, 0x72, 0xB5, 0xBD, 0x65, 0xF4, 0xEC, 0x22, 0xBC, 0xD3, 0x72, 0x35, 0xCD, 0xAA, 0x41,
+    0x1F, 0xA7, 0xF3, 0x53, 0x14, 0xDE, 0x7E, 0x02, 0xD9, 0x0F, 0xB5, 0x5E, 0x25, 0x1D, 0x29, 0x79,
+];
+/// Self test result of the MFRC522 version 2.0, as listed in the datasheet (section 16.1.1)
+const SELF_TEST_V2: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xEB, 0x66, 0xBA, 0x57, 0xBF, 0x23, 0x95, 0xD0, 0xE3, 0x0D, 0x3D, 0x27, 0x89, 0x5C, 0xDE,
+    0x9D, 0x3B, 0xA7, 0x00, 0x21, 0x5B, 0x89, 0x82, 0x51, 0x3A, 0xEB, 0x02, 0x0C, 0xA5, 0x00, 0x49,
+    0x7C, 0x84, 0x4D, 0xB3, 0xCC, 0xD2, 0x1B, 0x81, 0x5D, 0x48, 0x76, 0xD5, 0x71, 0x61, 0x21, 0xA9,
+    0x86, 0x96, 0x83, 0x38, 0xCF, 0x9D, 0x5B, 0x6D, 0xDC, 0x15, 0xBA, 0x3E, 0x7D, 0x95, 0x3B, 0x2F,
+];
+/// Self test result of the FM17522
+const SELF_TEST_FM17522: [u8; SELF_TEST_SIZE] = [
+    0x00, 0xD6, 0x78, 0x8C, 0xE2, 0xAA, 0x0C, 0x18, 0x2A, 0xB8, 0x7A, 0x7F, 0xD3, 0x6A, 0xCF, 0x0B,
+    0xB1, 0x37, 0x63, 0x4B, 0x69, 0xAE, 0x91, 0xC7, 0xC3, 0x97, 0xAE, 0x77, 0xF4, 0x37, 0xD7, 0x9B,
+    0x7C, 0xF5, 0x3C, 0x11, 0x8F, 0x15, 0xC3, 0xD7, 0xC1, 0x5B, 0x00, 0x2A, 0xD0, 0x75, 0xDE, 0x9E,
+    0x51, 0x64, 0xAB, 0x3E, 0xE9, 0x15, 0xB5, 0xAB, 0x56, 0x9A, 0x98, 0x82, 0x26, 0xEA, 0x2A, 0x62,
+];
+/// Number of CommandReg reads until the oscillator is stable after leaving soft power-down
+const WAKE_UP_READS: u8 = 3;
+
+/// Frame on the RF interface, without parity bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    bytes: Vec<u8, MAX_FRAME_SIZE>,
+    /// Number of valid bits in the last byte, 0 if all bits are valid
+    last_bits: u8,
+    /// Index of the first bit on which PICCs answering at the same time differ
+    collision: Option<usize>,
+}
+
+impl Frame {
+    /// Frame consisting of whole bytes
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::with_bits(bytes, 0)
+    }
+
+    /// Frame of which only `last_bits` bits of the last byte are valid (eg a 4-bit ACK)
+    pub fn with_bits(bytes: &[u8], last_bits: u8) -> Self {
+        let n = bytes.len().min(MAX_FRAME_SIZE);
+        Frame {
+            bytes: Vec::from_slice(&bytes[..n]).unwrap(),
+            last_bits: last_bits & 0b111,
+            collision: None,
+        }
+    }
+
+    /// Frame of the given bits, in the order they are transmitted
+    fn from_bits(bits: impl Iterator<Item = u8>) -> Self {
+        let mut frame = Self::new(&[]);
+        let mut len = 0;
+        for bit in bits.take(MAX_FRAME_SIZE * 8) {
+            if len % 8 == 0 {
+                frame.bytes.push(0).unwrap();
+            }
+            frame.bytes[len / 8] |= (bit & 1) << (len % 8);
+            len += 1;
+        }
+        frame.last_bits = (len % 8) as u8;
+        frame
+    }
+
+    /// Frame consisting of whole bytes, followed by their CRC_A
+    pub fn with_crc_a(bytes: &[u8]) -> Self {
+        let n = bytes.len().min(MAX_FRAME_SIZE - 2);
+        let mut frame = Self::new(&bytes[..n]);
+        frame
+            .bytes
+            .extend_from_slice(&crc::crc_a(&bytes[..n]))
+            .unwrap();
+        frame
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Number of valid bits in the last byte, 0 if all bits are valid
+    pub fn last_bits(&self) -> u8 {
+        self.last_bits
+    }
+
+    /// Total number of bits in the frame
+    pub fn bit_len(&self) -> usize {
+        match self.last_bits {
+            0 => self.bytes.len() * 8,
+            bits => (self.bytes.len().max(1) - 1) * 8 + bits as usize,
+        }
+    }
+
+    /// Bit `index` of the frame, in the order of transmission
+    fn bit(&self, index: usize) -> Option<u8> {
+        match index < self.bit_len() {
+            true => Some((self.bytes[index / 8] >> (index % 8)) & 1),
+            false => None,
+        }
+    }
+
+    /// Index of the first bit with a collision, if several PICCs answered at the same time
+    pub fn collision(&self) -> Option<usize> {
+        self.collision
+    }
+
+    /// The frame received when two PICCs answer at the same time.
+    ///
+    /// Bits sent by only one PICC, or with different values, are collisions.
+    /// The value of such a bit is the OR of the bits sent.
+    pub fn superpose(&self, other: &Frame) -> Frame {
+        let len = self.bit_len().max(other.bit_len());
+        let mut frame =
+            Self::from_bits((0..len).map(|i| self.bit(i).unwrap_or(0) | other.bit(i).unwrap_or(0)));
+        let first_difference = (0..len).find(|&i| self.bit(i) != other.bit(i));
+        frame.collision = [self.collision, other.collision, first_difference]
+            .into_iter()
+            .flatten()
+            .min();
+        frame
+    }
+
+    /// The bytes preceding the CRC_A, if the frame consists of whole bytes ending with a valid CRC_A
+    pub fn data_with_crc_a(&self) -> Option<&[u8]> {
+        if self.last_bits != 0 || !crc::check_crc_a(&self.bytes) {
+            return None;
+        }
+        Some(&self.bytes[..self.bytes.len() - 2])
+    }
+}
+
+/// The PICCs in the RF field of the [Simulator]
+pub trait Picc {
+    /// Answer a frame transmitted by the MFRC522, `None` if there is no response.
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame>;
+
+    /// The RF field was switched off, PICCs lose their power and state.
+    fn field_off(&mut self) {}
+}
+
+/// An empty RF field, no frame is ever answered
+impl Picc for () {
+    fn transceive(&mut self, _frame: &Frame) -> Option<Frame> {
+        None
+    }
+}
+
+/// A PICC that can be put into and taken out of the RF field
+impl<P: Picc> Picc for Option<P> {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        self.as_mut()?.transceive(frame)
+    }
+
+    fn field_off(&mut self) {
+        if let Some(picc) = self {
+            picc.field_off();
+        }
+    }
+}
+
+/// Several PICCs of the same type in the RF field, their answers are superposed
+impl<P: Picc, const N: usize> Picc for [P; N] {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        self.iter_mut()
+            .filter_map(|picc| picc.transceive(frame))
+            .reduce(|a, b| a.superpose(&b))
+    }
+
+    fn field_off(&mut self) {
+        self.iter_mut().for_each(Picc::field_off);
+    }
+}
+
+macro_rules! picc_tuple {
+    ($($name:ident)+) => {
+        /// Several PICCs in the RF field, their answers are superposed
+        impl<$($name: Picc),+> Picc for ($($name,)+) {
+            fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                [$($name.transceive(frame)),+]
+                    .into_iter()
+                    .flatten()
+                    .reduce(|a, b| a.superpose(&b))
+            }
+
+            fn field_off(&mut self) {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                $($name.field_off();)+
+            }
+        }
+    };
+}
+
+picc_tuple!(A B);
+picc_tuple!(A B C);
+picc_tuple!(A B C D);
+
+/// What the MFRC522 is doing with the RF interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    /// Sending the contents of the FIFO buffer, `consumed` is set if bytes were taken
+    /// from the FIFO buffer during the last step (the transmission continues)
+    Transmitting {
+        consumed: bool,
+    },
+    /// Moving the response to the FIFO buffer
+    Receiving,
+}
+
+/// Simulated MFRC522, connected through SPI
+pub struct Simulator<P> {
+    registers: [u8; 0x40],
+    fifo: Deque<u8, FIFO_SIZE>,
+    /// Internal buffer of the Mem and Generate RandomID commands
+    mem: [u8; MEM_SIZE],
+    phase: Phase,
+    tx: Vec<u8, MAX_FRAME_SIZE>,
+    rx: Vec<u8, MAX_FRAME_SIZE>,
+    /// Bytes of `rx` already moved to the FIFO buffer
+    rx_offset: usize,
+    /// Error bits to report at the end of the reception
+    rx_errors: u8,
+    version: u8,
+    random: u32,
+    /// CommandReg reads left until the wake up from soft power-down is finished
+    wake_up_reads: u8,
+    /// Reader side of the MIFARE Crypto1 unit, used while Status2Reg MFCrypto1On is set
+    crypto1: Crypto1,
+    picc: P,
+}
+
+impl<P: Picc> Simulator<P> {
+    pub fn new(picc: P) -> Self {
+        let mut sim = Simulator {
+            registers: [0; 0x40],
+            fifo: Deque::new(),
+            mem: [0; MEM_SIZE],
+            phase: Phase::Idle,
+            tx: Vec::new(),
+            rx: Vec::new(),
+            rx_offset: 0,
+            rx_errors: 0,
+            version: DEFAULT_VERSION,
+            random: 0x2545_F491,
+            wake_up_reads: 0,
+            crypto1: Crypto1::default(),
+            picc,
+        };
+        sim.reset();
+        sim
+    }
+
+    /// Report a different version in VersionReg, eg 0x91 for version 1.0
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self.registers[Register::VersionReg as usize] = version;
+        self
+    }
+
+    pub fn picc(&self) -> &P {
+        &self.picc
+    }
+
+    pub fn picc_mut(&mut self) -> &mut P {
+        &mut self.picc
+    }
+
+    /// Current value of a register, without side effects
+    pub fn register(&self, address: u8) -> u8 {
+        match address & 0x3F {
+            a if a == Register::FIFOLevelReg as u8 => self.fifo.len() as u8,
+            a if a == Register::Status1Reg as u8 => self.status1(),
+            a => self.registers[a as usize],
+        }
+    }
+
+    /// Contents of the FIFO buffer
+    pub fn fifo(&self) -> impl Iterator<Item = &u8> {
+        self.fifo.iter()
+    }
+
+    /// Is the RF field switched on (TX1 or TX2 delivers the energy carrier)
+    pub fn field_on(&self) -> bool {
+        self.registers[Register::TxControlReg as usize] & (TX1_RF_EN | TX2_RF_EN) != 0
+    }
+
+    /// Set the registers to their reset values
+    fn reset(&mut self) {
+        self.registers = [0; 0x40];
+        for (register, value) in [
+            (Register::CommandReg, 0x20),
+            (Register::ComlEnReg, 0x80),
+            (Register::ComIrqReg, 0x14),
+            (Register::Status1Reg, 0x21),
+            (Register::WaterLevelReg, 0x08),
+            (Register::ControlReg, 0x10),
+            (Register::CollReg, 0xA0),
+            (Register::ModeReg, 0x3F),
+            (Register::TxControlReg, 0x80),
+            (Register::TxSelReg, 0x10),
+            (Register::RxSelReg, 0x84),
+            (Register::RxThresholdReg, 0x84),
+            (Register::DemodReg, 0x4D),
+            (Register::MfTxReg, 0x62),
+            (Register::SerialSpeedReg, 0xEB),
+            (Register::CRCResultRegHigh, 0xFF),
+            (Register::CRCResultRegLow, 0xFF),
+            (Register::ModWidthReg, 0x26),
+            (Register::RFCfgReg, 0x48),
+            (Register::GsNReg, 0x88),
+            (Register::CWGsPReg, 0x20),
+            (Register::ModGsPReg, 0x20),
+            (Register::AutoTestReg, 0x40),
+            (Register::VersionReg, self.version),
+        ] {
+            self.registers[register as usize] = value;
+        }
+        self.fifo.clear();
+        self.phase = Phase::Idle;
+        self.tx.clear();
+        self.rx.clear();
+        self.rx_offset = 0;
+        self.rx_errors = 0;
+        self.wake_up_reads = 0;
+    }
+
+    fn read_register(&mut self, address: u8) -> u8 {
+        match address {
+            a if a == Register::FIFODataReg as u8 => self.fifo.pop_front().unwrap_or(0),
+            a if a == Register::CommandReg as u8 => {
+                let command = self.registers[a as usize];
+                if self.wake_up_reads > 0 {
+                    self.wake_up_reads -= 1;
+                    if self.wake_up_reads == 0 {
+                        self.registers[a as usize] &= !POWER_DOWN;
+                    }
+                }
+                command
+            }
+            a if a == Register::ComIrqReg as u8 => {
+                let irq = self.registers[a as usize] & !SET1;
+                self.step();
+                irq
+            }
+            a => self.register(a),
+        }
+    }
+
+    fn write_register(&mut self, address: u8, value: u8) {
+        let index = address as usize;
+        match address {
+            a if a == Register::CommandReg as u8 => {
+                let reg = &mut self.registers[index];
+                let powered_down = *reg & POWER_DOWN != 0;
+                *reg = (*reg & 0x0F) | (value & (RCV_OFF | POWER_DOWN));
+                if value & POWER_DOWN != 0 {
+                    self.wake_up_reads = 0;
+                } else if powered_down {
+                    // PowerDown only reads 0 once the MFRC522 is ready again
+                    *reg |= POWER_DOWN;
+                    if self.wake_up_reads == 0 {
+                        self.wake_up_reads = WAKE_UP_READS;
+                    }
+                }
+                let command = value & 0x0F;
+                if command != Command::NoCmdChange as u8 {
+                    self.start_command(command);
+                }
+            }
+            a if a == Register::ComIrqReg as u8 || a == Register::DivIrqReg as u8 => {
+                if value & SET1 != 0 {
+                    self.registers[index] |= value & !SET1;
+                } else {
+                    self.registers[index] &= !value;
+                }
+            }
+            a if a == Register::ErrorReg as u8
+                || a == Register::Status1Reg as u8
+                || a == Register::VersionReg as u8 => {}
+            a if a == Register::Status2Reg as u8 => {
+                // MFCrypto1On can only be cleared by software
+                let reg = &mut self.registers[index];
+                *reg = (*reg & 0x07) | (*reg & value & MF_CRYPTO1_ON) | (value & 0xC0);
+            }
+            a if a == Register::FIFODataReg as u8 => {
+                if self.fifo.push_back(value).is_err() {
+                    self.registers[Register::ErrorReg as usize] |= BUFFER_OVFL;
+                }
+                // data written while CalcCRC is active is added to the calculation
+                if self.command() == Command::CalcCRC as u8
+                    && self.registers[Register::AutoTestReg as usize] & 0x0F != SELF_TEST
+                {
+                    let crc = u16::from_be_bytes([
+                        self.registers[Register::CRCResultRegHigh as usize],
+                        self.registers[Register::CRCResultRegLow as usize],
+                    ]);
+                    self.update_crc(crc);
+                }
+            }
+            a if a == Register::FIFOLevelReg as u8 => {
+                if value & FLUSH_BUFFER != 0 {
+                    self.fifo.clear();
+                    self.registers[Register::ErrorReg as usize] &= !BUFFER_OVFL;
+                }
+            }
+            a if a == Register::BitFramingReg as u8 => {
+                self.registers[index] = value & !START_SEND;
+                if value & START_SEND != 0 && self.command() == Command::Transceive as u8 {
+                    self.start_transmission();
+                }
+            }
+            a if a == Register::TxControlReg as u8 => {
+                let was_on = self.field_on();
+                self.registers[index] = value;
+                if was_on && !self.field_on() {
+                    self.picc.field_off();
+                }
+            }
+            _ => self.registers[index] = value,
+        }
+    }
+
+    fn command(&self) -> u8 {
+        self.registers[Register::CommandReg as usize] & 0x0F
+    }
+
+    fn set_command(&mut self, command: Command) {
+        let reg = &mut self.registers[Register::CommandReg as usize];
+        *reg = (*reg & 0xF0) | command as u8;
+    }
+
+    fn set_irq(&mut self, irq: u8) {
+        self.registers[Register::ComIrqReg as usize] |= irq;
+    }
+
+    /// A command that ran to completion
+    fn finish_command(&mut self) {
+        self.set_command(Command::Idle);
+        self.set_irq(IDLE_IRQ);
+    }
+
+    fn start_command(&mut self, command: u8) {
+        let reg = &mut self.registers[Register::CommandReg as usize];
+        *reg = (*reg & 0xF0) | command;
+        self.phase = Phase::Idle;
+
+        match command {
+            c if c == Command::Mem as u8 => {
+                if self.fifo.is_empty() {
+                    for &b in self.mem.iter() {
+                        let _ = self.fifo.push_back(b);
+                    }
+                } else {
+                    for slot in self.mem.iter_mut() {
+                        *slot = self.fifo.pop_front().unwrap_or(0);
+                    }
+                }
+                self.finish_command();
+            }
+            c if c == Command::GenerateRandomId as u8 => {
+                for i in 0..RANDOM_ID_SIZE {
+                    self.mem[i] = self.next_random();
+                }
+                self.finish_command();
+            }
+            c if c == Command::CalcCRC as u8 => {
+                match self.registers[Register::AutoTestReg as usize] & 0x0F {
+                    SELF_TEST => self.self_test(),
+                    _ => self.calculate_crc(),
+                }
+            }
+            c if c == Command::Transmit as u8 => self.start_transmission(),
+            c if c == Command::SoftReset as u8 => self.reset(),
+            c if c == Command::MFAuthent as u8 => self.authenticate(),
+            // Idle, Transceive (waits for StartSend), Receive and reserved commands
+            _ => {}
+        }
+    }
+
+    /// The CRC coprocessor processes the FIFO buffer, starting with the preset of ModeReg
+    fn calculate_crc(&mut self) {
+        let preset = match self.registers[Register::ModeReg as usize] & 0b11 {
+            0b00 => 0x0000,
+            0b01 => 0x6363,
+            0b10 => 0xA671,
+            _ => 0xFFFF,
+        };
+        self.update_crc(preset);
+    }
+
+    /// The CRC coprocessor processes the FIFO buffer, the command stays active
+    fn update_crc(&mut self, crc: u16) {
+        let mut data = Vec::<u8, FIFO_SIZE>::new();
+        while let Some(b) = self.fifo.pop_front() {
+            data.push(b).unwrap();
+        }
+        let crc = crc::update(crc, &data);
+        self.registers[Register::CRCResultRegHigh as usize] = (crc >> 8) as u8;
+        self.registers[Register::CRCResultRegLow as usize] = crc as u8;
+        self.registers[Register::DivIrqReg as usize] |= CRC_IRQ;
+        let status = &mut self.registers[Register::Status1Reg as usize];
+        *status = (*status & !CRC_OK) | CRC_READY | if crc == 0 { CRC_OK } else { 0 };
+    }
+
+    /// The digital self test: the result of the version, if the internal buffer was cleared
+    /// (anything else for the versions without a known result)
+    fn self_test(&mut self) {
+        let reference = match self.version {
+            0x91 => Some(&SELF_TEST_V1),
+            0x92 => Some(&SELF_TEST_V2),
+            0x88 => Some(&SELF_TEST_FM17522),
+            _ => None,
+        };
+        self.fifo.clear();
+        for i in 0..SELF_TEST_SIZE {
+            let byte = match reference {
+                Some(reference) if self.mem.iter().all(|&b| b == 0) => reference[i],
+                _ => i as u8,
+            };
+            let _ = self.fifo.push_back(byte);
+        }
+    }
+
+    fn start_transmission(&mut self) {
+        // errors are cleared at the start of the receiver
+        self.registers[Register::ErrorReg as usize] &= BUFFER_OVFL | TEMP_ERR | WR_ERR;
+        self.tx.clear();
+        self.consume_fifo();
+        self.phase = Phase::Transmitting { consumed: true };
+    }
+
+    /// Move the FIFO buffer to the transmitter
+    fn consume_fifo(&mut self) {
+        while let Some(b) = self.fifo.pop_front() {
+            if self.tx.push(b).is_err() {
+                self.registers[Register::ErrorReg as usize] |= BUFFER_OVFL;
+            }
+        }
+        self.set_irq(LO_ALERT_IRQ);
+    }
+
+    /// Advance the transmission or reception by one step
+    fn step(&mut self) {
+        match self.phase {
+            Phase::Idle => {}
+            Phase::Transmitting { consumed } => {
+                if !self.fifo.is_empty() {
+                    self.consume_fifo();
+                    self.phase = Phase::Transmitting { consumed: true };
+                } else if consumed {
+                    self.phase = Phase::Transmitting { consumed: false };
+                } else {
+                    self.finish_transmission();
+                }
+            }
+            Phase::Receiving => self.fill_fifo(),
+        }
+    }
+
+    fn finish_transmission(&mut self) {
+        self.set_irq(TX_IRQ);
+        self.phase = Phase::Idle;
+
+        let last_bits = self.registers[Register::BitFramingReg as usize] & 0b111;
+        let mut frame = Frame::with_bits(&self.tx, last_bits);
+        if self.registers[Register::TxModeReg as usize] & CRC_EN != 0 && last_bits == 0 {
+            frame = Frame::with_crc_a(&self.tx);
+        }
+        if self.crypto1_on() {
+            frame = self.crypto1.crypt(&frame);
+        }
+
+        if self.command() == Command::Transmit as u8 {
+            if self.field_on() {
+                self.picc.transceive(&frame);
+            }
+            self.finish_command();
+            return;
+        }
+
+        let powered = self.registers[Register::CommandReg as usize] & (POWER_DOWN | RCV_OFF) == 0;
+        let response = match self.field_on() && powered {
+            true => self.picc.transceive(&frame),
+            false => None,
+        };
+        match response {
+            Some(response) if self.crypto1_on() => {
+                let response = self.crypto1.crypt(&response);
+                self.start_reception(response)
+            }
+            Some(response) => self.start_reception(response),
+            None => self.timeout(),
+        }
+    }
+
+    fn crypto1_on(&self) -> bool {
+        self.registers[Register::Status2Reg as usize] & MF_CRYPTO1_ON != 0
+    }
+
+    /// Reader side of the MIFARE Classic three pass authentication (MFAuthent command).
+    ///
+    /// The FIFO buffer contains the authentication command, the block address, the key
+    /// and 4 bytes of the UID. If the PICC doesn't answer as expected, the timer times out.
+    fn authenticate(&mut self) {
+        let mut data = [0u8; 12];
+        for b in data.iter_mut() {
+            *b = self.fifo.pop_front().unwrap_or(0);
+        }
+        let key = data[2..8].try_into().unwrap();
+        let uid = u32::from_be_bytes(data[8..12].try_into().unwrap());
+
+        match self.three_pass_authentication(&data[..2], key, uid) {
+            Some(crypto1) => {
+                self.crypto1 = crypto1;
+                self.registers[Register::Status2Reg as usize] |= MF_CRYPTO1_ON;
+                self.finish_command();
+            }
+            None => {
+                self.registers[Register::Status2Reg as usize] &= !MF_CRYPTO1_ON;
+                self.timeout();
+            }
+        }
+    }
+
+    fn three_pass_authentication(
+        &mut self,
+        command: &[u8],
+        key: &[u8; 6],
+        uid: u32,
+    ) -> Option<Crypto1> {
+        if !self.field_on() {
+            return None;
+        }
+
+        // a nested authentication is encrypted with the current key
+        let nested = self.crypto1_on();
+        let mut frame = Frame::with_crc_a(command);
+        if nested {
+            frame = self.crypto1.crypt(&frame);
+        }
+        let nt = self.picc.transceive(&frame)?;
+        // the answer of the PICC to the first pass is received like any other frame
+        self.set_irq(RX_IRQ);
+        let nt = u32::from_be_bytes(nt.as_bytes().try_into().ok()?);
+
+        let mut crypto1 = Crypto1::new(key);
+        let nt = match nested {
+            true => crypto1.word(uid ^ nt, true) ^ nt,
+            false => {
+                crypto1.word(uid ^ nt, false);
+                nt
+            }
+        };
+
+        let nr = u32::from_be_bytes([(); 4].map(|_| self.next_random()));
+        let mut nr_ar = [0u8; 8];
+        nr_ar[..4].copy_from_slice(&(crypto1.word(nr, false) ^ nr).to_be_bytes());
+        let ar = crypto1::prng_successor(nt, 64);
+        nr_ar[4..].copy_from_slice(&(crypto1.word(0, false) ^ ar).to_be_bytes());
+
+        let at = self.picc.transceive(&Frame::new(&nr_ar))?;
+        let at = u32::from_be_bytes(at.as_bytes().try_into().ok()?);
+        match at ^ crypto1.word(0, false) == crypto1::prng_successor(nt, 96) {
+            true => Some(crypto1),
+            false => None,
+        }
+    }
+
+    fn timeout(&mut self) {
+        if self.registers[Register::TModeReg as usize] & T_AUTO != 0 {
+            self.set_irq(TIMER_IRQ);
+        }
+    }
+
+    fn start_reception(&mut self, response: Frame) {
+        let collision = response.collision;
+        let mut bytes = response.bytes;
+        let mut last_bits = response.last_bits;
+        self.rx_errors = 0;
+
+        if self.registers[Register::RxModeReg as usize] & CRC_EN != 0 {
+            // the MFRC522 checks the CRC_A and does not store it in the FIFO buffer
+            if last_bits == 0 && bytes.len() >= 2 && crc::check_crc_a(&bytes) {
+                bytes.truncate(bytes.len() - 2);
+            } else {
+                self.rx_errors |= CRC_ERR;
+            }
+        }
+
+        // store the first bit at bit position RxAlign of the first byte
+        let align = (self.registers[Register::BitFramingReg as usize] >> 4) & 0b111;
+        let bits = match last_bits {
+            0 => bytes.len() * 8,
+            n => (bytes.len().max(1) - 1) * 8 + n as usize,
+        };
+
+        let mut coll = self.registers[Register::CollReg as usize] & VALUES_AFTER_COLL;
+        let mut cleared = bits;
+        match collision.filter(|&c| c < bits) {
+            Some(c) => {
+                self.rx_errors |= COLL_ERR;
+                // CollPos counts from 1, starting at the first bit of the first byte
+                coll |= match c + align as usize + 1 {
+                    pos if pos <= 32 => (pos % 32) as u8,
+                    _ => COLL_POS_NOT_VALID,
+                };
+                if coll & VALUES_AFTER_COLL == 0 {
+                    cleared = c;
+                }
+            }
+            None => coll |= COLL_POS_NOT_VALID,
+        }
+        self.registers[Register::CollReg as usize] = coll;
+
+        self.rx.clear();
+        for i in 0..bits {
+            let bit = match i < cleared {
+                true => (bytes[i / 8] >> (i % 8)) & 1,
+                false => 0,
+            };
+            let pos = i + align as usize;
+            if pos / 8 == self.rx.len() && self.rx.push(0).is_err() {
+                self.rx_errors |= BUFFER_OVFL;
+                break;
+            }
+            self.rx[pos / 8] |= bit << (pos % 8);
+        }
+        last_bits = ((bits + align as usize) % 8) as u8;
+
+        let control = &mut self.registers[Register::ControlReg as usize];
+        *control = (*control & !0b111) | last_bits;
+        self.rx_offset = 0;
+        self.phase = Phase::Receiving;
+        self.fill_fifo();
+    }
+
+    /// Move the received bytes to the FIFO buffer, as far as they fit
+    fn fill_fifo(&mut self) {
+        while self.rx_offset < self.rx.len() && !self.fifo.is_full() {
+            self.fifo.push_back(self.rx[self.rx_offset]).unwrap();
+            self.rx_offset += 1;
+        }
+        if self.status1() & HI_ALERT != 0 {
+            self.set_irq(HI_ALERT_IRQ);
+        }
+        if self.rx_offset == self.rx.len() {
+            self.phase = Phase::Idle;
+            self.registers[Register::ErrorReg as usize] |= self.rx_errors;
+            let mut irq = RX_IRQ;
+            if self.registers[Register::ErrorReg as usize] != 0 {
+                irq |= ERR_IRQ;
+            }
+            self.set_irq(irq);
+        }
+    }
+
+    fn status1(&self) -> u8 {
+        let water_level = self.registers[Register::WaterLevelReg as usize] as usize & 0x3F;
+        let mut status = self.registers[Register::Status1Reg as usize] & (CRC_READY | CRC_OK);
+        if self.fifo.len() <= water_level {
+            status |= LO_ALERT;
+        }
+        if FIFO_SIZE - self.fifo.len() <= water_level {
+            status |= HI_ALERT;
+        }
+        let com = self.registers[Register::ComIrqReg as usize]
+            & self.registers[Register::ComlEnReg as usize];
+        let div = self.registers[Register::DivIrqReg as usize]
+            & self.registers[Register::DivlEnReg as usize];
+        if (com | div) & 0x7F != 0 {
+            status |= IRQ;
+        }
+        status
+    }
+
+    /// xorshift32, deterministic so tests are reproducible
+    fn next_random(&mut self) -> u8 {
+        let mut x = self.random;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random = x;
+        x as u8
+    }
+}
+
+impl<P: Picc> spi::Transfer<u8> for Simulator<P> {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let Some(&first) = words.first() else {
+            return Ok(words);
+        };
+        if first & 0x80 == 0 {
+            // write access: the following bytes are written to the addressed register
+            let address = (first >> 1) & 0x3F;
+            for word in words[1..].iter_mut() {
+                self.write_register(address, *word);
+                *word = 0;
+            }
+            words[0] = 0;
+            return Ok(words);
+        }
+
+        // read access: each byte addresses the register that is returned with the next byte
+        let mut address = first;
+        words[0] = 0;
+        for word in words[1..].iter_mut() {
+            let next = *word;
+            *word = self.read_register((address >> 1) & 0x3F);
+            address = next;
+        }
+        Ok(words)
+    }
+}
+
+impl<P: Picc> spi::Write<u8> for Simulator<P> {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        if let Some((&first, data)) = words.split_first() {
+            let address = (first >> 1) & 0x3F;
+            if first & 0x80 == 0 {
+                for &value in data {
+                    self.write_register(address, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,444 @@
+//! Simulated MIFARE Classic PICCs (Mini, 1K and 4K).
+//!
+//! Sectors are authenticated with the three pass authentication of Crypto1, after which
+//! all frames are encrypted. The access conditions of the sector trailers are enforced
+//! for READ and WRITE. The value block commands (INCREMENT, DECREMENT, RESTORE and
+//! TRANSFER) are not supported, they are answered with a NAK.
+
+use super::crypto1::{prng_successor, Crypto1};
+use super::{Activation, Frame, Iso14443a, Picc, State};
+use crate::crc;
+
+const AUTH_KEY_A: u8 = 0x60;
+const AUTH_KEY_B: u8 = 0x61;
+const READ: u8 = 0x30;
+const WRITE: u8 = 0xA0;
+const ACK: u8 = 0xA;
+/// NAK: invalid operation, eg the access conditions don't allow it
+const NAK_INVALID: u8 = 0x4;
+/// NAK: parity or CRC error
+const NAK_CRC: u8 = 0x5;
+
+/// Sector trailer of a new PICC: key A and key B `FF FF FF FF FF FF`,
+/// access bits of the transport configuration
+const TRANSPORT_TRAILER: [u8; 16] = [
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x80, 0x69, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// Which keys allow an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Permission {
+    Never,
+    KeyA,
+    KeyB,
+    Both,
+}
+
+use Permission::*;
+
+impl Permission {
+    fn allows(self, key_b: bool) -> bool {
+        matches!((self, key_b), (Both, _) | (KeyA, false) | (KeyB, true))
+    }
+}
+
+/// Permissions of a sector trailer
+struct TrailerPermissions {
+    key_a_write: Permission,
+    access_read: Permission,
+    access_write: Permission,
+    key_b_read: Permission,
+    key_b_write: Permission,
+}
+
+/// Progress of the authentication
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Session {
+    None,
+    /// The PICC nonce was sent, waiting for the answer of the reader
+    Nonce {
+        sector: u8,
+        key_b: bool,
+        nt: u32,
+    },
+    /// The sector is authenticated, the frames are encrypted
+    Authenticated {
+        sector: u8,
+        key_b: bool,
+    },
+}
+
+/// Simulated MIFARE Classic PICC
+#[derive(Debug, Clone)]
+pub struct Classic {
+    iso: Iso14443a,
+    memory: [u8; 4096],
+    /// Number of 16 byte blocks
+    blocks: usize,
+    session: Session,
+    crypto1: Crypto1,
+    /// Block of a WRITE command that waits for its data
+    write_block: Option<u8>,
+    /// Last nonce sent by the PICC
+    nonce: u32,
+}
+
+impl Classic {
+    /// MIFARE Mini: 5 sectors of 4 blocks
+    pub fn new_mini(uid: &[u8]) -> Self {
+        Self::new(uid, 20, [0x04, 0x00], 0x09)
+    }
+
+    /// MIFARE Classic 1K: 16 sectors of 4 blocks
+    pub fn new_1k(uid: &[u8]) -> Self {
+        Self::new(uid, 64, [0x04, 0x00], 0x08)
+    }
+
+    /// MIFARE Classic 4K: 32 sectors of 4 blocks, followed by 8 sectors of 16 blocks
+    pub fn new_4k(uid: &[u8]) -> Self {
+        Self::new(uid, 256, [0x02, 0x00], 0x18)
+    }
+
+    /// The UID is 4 or 7 bytes long.
+    /// Block 0 contains the UID and manufacturer data, all sector trailers are in the
+    /// transport configuration and the other blocks are zero.
+    fn new(uid: &[u8], blocks: usize, atqa: [u8; 2], sak: u8) -> Self {
+        let iso = Iso14443a::new(uid, atqa, sak);
+        let mut classic = Classic {
+            memory: [0; 4096],
+            blocks,
+            session: Session::None,
+            crypto1: Crypto1::default(),
+            write_block: None,
+            nonce: (0x0102_0304 ^ u32::from_be_bytes(uid[uid.len() - 4..].try_into().unwrap())) | 1,
+            iso,
+        };
+
+        let block0 = &mut classic.memory[..16];
+        block0[..uid.len()].copy_from_slice(uid);
+        let mut n = uid.len();
+        if uid.len() == 4 {
+            block0[4] = crc::bcc(uid);
+            n += 1;
+        }
+        let atqa = classic.iso.atqa();
+        block0[n..n + 3].copy_from_slice(&[sak, atqa[0], atqa[1]]);
+        for (i, b) in block0[n + 3..].iter_mut().enumerate() {
+            *b = 0x62 + i as u8;
+        }
+        for sector in 0..=sector_of((blocks - 1) as u8) {
+            let trailer = trailer_of(sector) * 16;
+            classic.memory[trailer..trailer + 16].copy_from_slice(&TRANSPORT_TRAILER);
+        }
+        classic
+    }
+
+    pub fn iso(&self) -> &Iso14443a {
+        &self.iso
+    }
+
+    pub fn state(&self) -> State {
+        self.iso.state()
+    }
+
+    /// Is a sector authenticated (and Crypto1 switched on)
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.session, Session::Authenticated { .. })
+    }
+
+    /// The memory, block after block
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.blocks * 16]
+    }
+
+    /// The memory, to set up keys and data without access conditions
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory[..self.blocks * 16]
+    }
+
+    /// The 4 bytes of the UID used by Crypto1, the last 4 bytes of a 7 byte UID
+    fn crypto1_uid(&self) -> u32 {
+        let uid = self.iso.uid();
+        u32::from_be_bytes(uid[uid.len() - 4..].try_into().unwrap())
+    }
+
+    fn command(&mut self, frame: &Frame) -> Option<Frame> {
+        let Some(data) = frame.data_with_crc_a() else {
+            return self.nak(NAK_CRC);
+        };
+        if let Some(block) = self.write_block.take() {
+            let Ok(data) = data.try_into() else {
+                return self.nak(NAK_INVALID);
+            };
+            self.write(block, data);
+            return Some(Frame::with_bits(&[ACK], 4));
+        }
+
+        match *data {
+            [AUTH_KEY_A, block] => self.start_authentication(block, false),
+            [AUTH_KEY_B, block] => self.start_authentication(block, true),
+            [READ, block] => self.read(block),
+            [WRITE, block] => self.start_write(block),
+            _ => self.nak(NAK_INVALID),
+        }
+    }
+
+    /// Answer a NAK and leave the ACTIVE state
+    fn nak(&mut self, nak: u8) -> Option<Frame> {
+        self.iso.deactivate();
+        Some(Frame::with_bits(&[nak], 4))
+    }
+
+    /// Send the PICC nonce, encrypted if a sector is already authenticated
+    fn start_authentication(&mut self, block: u8, key_b: bool) -> Option<Frame> {
+        if block as usize >= self.blocks {
+            return self.nak(NAK_INVALID);
+        }
+        let sector = sector_of(block);
+        let trailer = trailer_of(sector) * 16;
+        let key = match key_b {
+            true => &self.memory[trailer + 10..trailer + 16],
+            false => &self.memory[trailer..trailer + 6],
+        };
+
+        self.nonce = prng_successor(self.nonce, 160);
+        let nt = self.nonce;
+        let nested = self.is_authenticated();
+        self.crypto1 = Crypto1::new(key.try_into().unwrap());
+        let keystream = self.crypto1.word(self.crypto1_uid() ^ nt, false);
+        self.session = Session::Nonce { sector, key_b, nt };
+
+        let nt = match nested {
+            true => nt ^ keystream,
+            false => nt,
+        };
+        Some(Frame::new(&nt.to_be_bytes()))
+    }
+
+    /// Check the answer of the reader ({nr}{ar}) and answer {at}
+    fn finish_authentication(
+        &mut self,
+        frame: &Frame,
+        sector: u8,
+        key_b: bool,
+        nt: u32,
+    ) -> Option<Frame> {
+        let bytes = frame.as_bytes();
+        let nr = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        let ar = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
+        self.crypto1.word(nr, true);
+        if ar ^ self.crypto1.word(0, false) != prng_successor(nt, 64) {
+            self.iso.deactivate();
+            return None;
+        }
+        let at = prng_successor(nt, 96) ^ self.crypto1.word(0, false);
+        self.session = Session::Authenticated { sector, key_b };
+        Some(Frame::new(&at.to_be_bytes()))
+    }
+
+    /// The authenticated key, if `block` is part of the authenticated sector
+    fn authenticated_key(&self, block: u8) -> Option<bool> {
+        match self.session {
+            Session::Authenticated { sector, key_b }
+                if (block as usize) < self.blocks && sector_of(block) == sector =>
+            {
+                Some(key_b)
+            }
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, block: u8) -> Option<Frame> {
+        let Some(key_b) = self.authenticated_key(block) else {
+            return self.nak(NAK_INVALID);
+        };
+        let sector = sector_of(block);
+        let mut data: [u8; 16] = self.memory[block as usize * 16..][..16].try_into().unwrap();
+
+        if block as usize == trailer_of(sector) {
+            let permissions = self.trailer_permissions(sector);
+            // key A can never be read
+            data[..6].fill(0);
+            if !self.allows(sector, permissions.access_read, key_b) {
+                data[6..10].fill(0);
+            }
+            if !self.allows(sector, permissions.key_b_read, key_b) {
+                data[10..].fill(0);
+            }
+        } else if !self.allows(sector, self.data_permissions(block).0, key_b) {
+            return self.nak(NAK_INVALID);
+        }
+        Some(Frame::with_crc_a(&data))
+    }
+
+    fn start_write(&mut self, block: u8) -> Option<Frame> {
+        let Some(key_b) = self.authenticated_key(block) else {
+            return self.nak(NAK_INVALID);
+        };
+        let sector = sector_of(block);
+        let allowed = if block as usize == trailer_of(sector) {
+            let permissions = self.trailer_permissions(sector);
+            [
+                permissions.key_a_write,
+                permissions.access_write,
+                permissions.key_b_write,
+            ]
+            .iter()
+            .any(|&permission| self.allows(sector, permission, key_b))
+        } else {
+            // the manufacturer block is read-only
+            block != 0 && self.allows(sector, self.data_permissions(block).1, key_b)
+        };
+
+        if !allowed {
+            return self.nak(NAK_INVALID);
+        }
+        self.write_block = Some(block);
+        Some(Frame::with_bits(&[ACK], 4))
+    }
+
+    /// Write the data of a WRITE command, the parts of a sector trailer
+    /// that may not be written are left unchanged
+    fn write(&mut self, block: u8, data: &[u8; 16]) {
+        let key_b = self.authenticated_key(block).unwrap_or(false);
+        let sector = sector_of(block);
+        let start = block as usize * 16;
+        if block as usize != trailer_of(sector) {
+            self.memory[start..start + 16].copy_from_slice(data);
+            return;
+        }
+
+        let permissions = self.trailer_permissions(sector);
+        for (range, permission) in [
+            (0..6, permissions.key_a_write),
+            (6..10, permissions.access_write),
+            (10..16, permissions.key_b_write),
+        ] {
+            if self.allows(sector, permission, key_b) {
+                self.memory[start + range.start..start + range.end].copy_from_slice(&data[range]);
+            }
+        }
+    }
+
+    /// Access conditions (C1 C2 C3) of a block, `None` if the access bits are inconsistent
+    fn access_conditions(&self, block: u8) -> Option<u8> {
+        let sector = sector_of(block);
+        let trailer = trailer_of(sector);
+        let group = match sector {
+            0..=31 => block as usize % 4,
+            _ => ((block as usize - 128) % 16 / 5).min(3),
+        };
+        let access = &self.memory[trailer * 16 + 6..trailer * 16 + 9];
+        let (c1, c2, c3) = (access[1] >> 4, access[2] & 0x0F, access[2] >> 4);
+        if access[0] & 0x0F != !c1 & 0x0F
+            || access[0] >> 4 != !c2 & 0x0F
+            || access[1] & 0x0F != !c3 & 0x0F
+        {
+            return None;
+        }
+        Some(((c1 >> group) & 1) << 2 | ((c2 >> group) & 1) << 1 | ((c3 >> group) & 1))
+    }
+
+    /// Read and write permissions of a data block
+    fn data_permissions(&self, block: u8) -> (Permission, Permission) {
+        match self.access_conditions(block) {
+            Some(0b000) => (Both, Both),
+            Some(0b010) | Some(0b001) => (Both, Never),
+            Some(0b100) | Some(0b110) => (Both, KeyB),
+            Some(0b011) => (KeyB, KeyB),
+            Some(0b101) => (KeyB, Never),
+            _ => (Never, Never),
+        }
+    }
+
+    fn trailer_permissions(&self, sector: u8) -> TrailerPermissions {
+        let [key_a_write, access_read, access_write, key_b_read, key_b_write] =
+            match self.access_conditions(trailer_of(sector) as u8) {
+                Some(0b000) => [KeyA, KeyA, Never, KeyA, KeyA],
+                Some(0b010) => [Never, KeyA, Never, KeyA, Never],
+                Some(0b100) => [KeyB, Both, Never, Never, KeyB],
+                Some(0b001) => [KeyA, KeyA, KeyA, KeyA, KeyA],
+                Some(0b011) => [KeyB, Both, KeyB, Never, KeyB],
+                Some(0b101) => [Never, Both, KeyB, Never, Never],
+                Some(_) => [Never, Both, Never, Never, Never],
+                None => [Never; 5],
+            };
+        TrailerPermissions {
+            key_a_write,
+            access_read,
+            access_write,
+            key_b_read,
+            key_b_write,
+        }
+    }
+
+    /// Does the permission allow the operation with the authenticated key.
+    /// If key B can be read, it can't be used to access the sector.
+    fn allows(&self, sector: u8, permission: Permission, key_b: bool) -> bool {
+        if key_b && self.key_b_readable(sector) {
+            return false;
+        }
+        permission.allows(key_b)
+    }
+
+    fn key_b_readable(&self, sector: u8) -> bool {
+        matches!(
+            self.access_conditions(trailer_of(sector) as u8),
+            Some(0b000) | Some(0b010) | Some(0b001)
+        )
+    }
+}
+
+impl Picc for Classic {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        if let Session::Nonce { sector, key_b, nt } = self.session {
+            self.session = Session::None;
+            if frame.bit_len() == 64 {
+                return self.finish_authentication(frame, sector, key_b, nt);
+            }
+            self.iso.deactivate();
+        }
+
+        let frame = match self.session {
+            Session::Authenticated { .. } => self.crypto1.crypt(frame),
+            _ => frame.clone(),
+        };
+        let response = match self.iso.transceive(&frame) {
+            Activation::Handled(response) => response,
+            Activation::Command => self.command(&frame),
+        };
+        // the answer is encrypted if the sector (still) is authenticated,
+        // not after the nonce of a new authentication
+        let response = match (self.session, response) {
+            (Session::Authenticated { .. }, Some(response)) => Some(self.crypto1.crypt(&response)),
+            (_, response) => response,
+        };
+        if !self.iso.is_active() {
+            self.session = Session::None;
+            self.write_block = None;
+        }
+        response
+    }
+
+    fn field_off(&mut self) {
+        self.iso.field_off();
+        self.session = Session::None;
+        self.write_block = None;
+    }
+}
+
+/// Sector containing a block
+fn sector_of(block: u8) -> u8 {
+    match block {
+        0..=127 => block / 4,
+        _ => 32 + (block - 128) / 16,
+    }
+}
+
+/// Block number of the sector trailer
+fn trailer_of(sector: u8) -> usize {
+    match sector {
+        0..=31 => sector as usize * 4 + 3,
+        _ => 128 + (sector as usize - 32) * 16 + 15,
+    }
+}
@@ -0,0 +1,93 @@
+//! MIFARE Crypto1 stream cipher, used by both sides of the simulated RF interface.
+//!
+//! The 48-bit LFSR is kept as its odd and even bits, as in the well-known
+//! crapto1 implementation, which makes the filter function cheap to evaluate.
+
+use super::Frame;
+
+/// Feedback taps of the LFSR, odd bits
+const LF_POLY_ODD: u32 = 0x29_CE5C;
+/// Feedback taps of the LFSR, even bits
+const LF_POLY_EVEN: u32 = 0x87_0804;
+
+/// State of the Crypto1 cipher
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crypto1 {
+    odd: u32,
+    even: u32,
+}
+
+impl Crypto1 {
+    /// Cipher loaded with a 6 byte key (key A or key B of a sector)
+    pub fn new(key: &[u8; 6]) -> Self {
+        let key = key.iter().fold(0u64, |k, &b| k << 8 | b as u64);
+        let mut crypto1 = Crypto1::default();
+        for i in (1..48).step_by(2).rev() {
+            crypto1.odd = crypto1.odd << 1 | (key >> ((i - 1) ^ 7)) as u32 & 1;
+            crypto1.even = crypto1.even << 1 | (key >> (i ^ 7)) as u32 & 1;
+        }
+        crypto1
+    }
+
+    /// Clock the LFSR once, shifting in `input` (XORed with the keystream if `encrypted`).
+    /// Returns the keystream bit.
+    pub fn bit(&mut self, input: u8, encrypted: bool) -> u8 {
+        let keystream = filter(self.odd);
+        let mut feedback = (input & 1) as u32;
+        if encrypted {
+            feedback ^= keystream as u32;
+        }
+        feedback ^= LF_POLY_ODD & self.odd;
+        feedback ^= LF_POLY_EVEN & self.even;
+        self.even = self.even << 1 | (feedback.count_ones() & 1);
+        core::mem::swap(&mut self.odd, &mut self.even);
+        keystream
+    }
+
+    /// Clock the LFSR 8 times, the bits of `input` are shifted in LSB first
+    pub fn byte(&mut self, input: u8, encrypted: bool) -> u8 {
+        (0..8).fold(0, |ks, i| ks | self.bit(input >> i, encrypted) << i)
+    }
+
+    /// Clock the LFSR 32 times, the bytes of `input` are shifted in MSB first (in the
+    /// order they are transmitted), the bits of each byte LSB first
+    pub fn word(&mut self, input: u32, encrypted: bool) -> u32 {
+        (0..32).fold(0, |ks, i| {
+            let bit = (input >> (i ^ 24)) as u8;
+            ks | (self.bit(bit, encrypted) as u32) << (i ^ 24)
+        })
+    }
+
+    /// Encrypt or decrypt a frame, bit by bit.
+    ///
+    /// The encrypted parity bits are not simulated, like all parity bits.
+    pub fn crypt(&mut self, frame: &Frame) -> Frame {
+        let mut crypted = frame.clone();
+        for i in 0..frame.bit_len() {
+            crypted.bytes[i / 8] ^= self.bit(0, false) << (i % 8);
+        }
+        crypted
+    }
+}
+
+/// Nonlinear filter function, producing the keystream from 20 of the odd bits of the LFSR
+fn filter(x: u32) -> u8 {
+    let mut f = (0xF_22C0 >> (x & 0xF)) & 16;
+    f |= (0x6_C9C0 >> ((x >> 4) & 0xF)) & 8;
+    f |= (0x3_C8B0 >> ((x >> 8) & 0xF)) & 4;
+    f |= (0x1_E458 >> ((x >> 12) & 0xF)) & 2;
+    f |= (0x0_D938 >> ((x >> 16) & 0xF)) & 1;
+    ((0xEC57_E80A_u32 >> f) & 1) as u8
+}
+
+/// The nonce `n` steps after `nonce`, generated by the 16-bit LFSR of the PICC.
+///
+/// During authentication, the reader answers the PICC nonce with its 64th successor,
+/// the PICC answers with the 96th successor.
+pub fn prng_successor(nonce: u32, n: u32) -> u32 {
+    let mut x = nonce.swap_bytes();
+    for _ in 0..n {
+        x = x >> 1 | ((x >> 16) ^ (x >> 18) ^ (x >> 19) ^ (x >> 21)) << 31;
+    }
+    x.swap_bytes()
+}
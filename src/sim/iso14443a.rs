@@ -0,0 +1,208 @@
+//! ISO/IEC 14443-3 type A activation of a simulated PICC: REQA, WUPA, anticollision,
+//! SELECT and HLTA, for UIDs of all three sizes.
+
+use heapless::Vec;
+
+use super::Frame;
+use crate::crc;
+
+const REQA: u8 = 0x26;
+const WUPA: u8 = 0x52;
+const HLTA: [u8; 2] = [0x50, 0x00];
+/// SEL of the three cascade levels
+const SEL: [u8; 3] = [0x93, 0x95, 0x97];
+/// NVB of the SELECT command: 7 valid bytes
+const NVB_SELECT: u8 = 0x70;
+const CASCADE_TAG: u8 = 0x88;
+/// Bit of the SAK indicating that the UID is not complete
+const SAK_CASCADE: u8 = 0x04;
+
+/// State of a PICC (ISO/IEC 14443-3, 6.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Powered, waiting for REQA or WUPA
+    Idle,
+    /// Woken up, taking part in the anticollision of a cascade level (0 to 2)
+    Ready { level: usize },
+    /// Selected, the PICC answers to its application commands
+    Active,
+    /// Halted, waiting for WUPA
+    Halt,
+}
+
+/// What [Iso14443a::transceive] did with a frame
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Activation {
+    /// The frame was handled (eg REQA, SELECT or HLTA), with this answer
+    Handled(Option<Frame>),
+    /// The PICC is ACTIVE and the frame is an application command
+    Command,
+}
+
+/// Activation state machine, shared by the PICC models
+#[derive(Debug, Clone)]
+pub struct Iso14443a {
+    uid: Vec<u8, 10>,
+    atqa: [u8; 2],
+    sak: u8,
+    state: State,
+    /// The PICC was woken up from HALT (READY* and ACTIVE* states)
+    halted: bool,
+}
+
+impl Iso14443a {
+    /// PICC with a UID of 4, 7 or 10 bytes.
+    ///
+    /// The UID size bits of the ATQA are set according to the length of `uid`.
+    ///
+    /// # Panics
+    ///
+    /// If the UID has a different length
+    pub fn new(uid: &[u8], atqa: [u8; 2], sak: u8) -> Self {
+        let size_bits = match uid.len() {
+            4 => 0x00,
+            7 => 0x40,
+            10 => 0x80,
+            n => panic!("invalid UID length {}", n),
+        };
+        Iso14443a {
+            uid: Vec::from_slice(uid).unwrap(),
+            atqa: [(atqa[0] & 0x3F) | size_bits, atqa[1]],
+            sak,
+            state: State::Idle,
+            halted: false,
+        }
+    }
+
+    pub fn uid(&self) -> &[u8] {
+        &self.uid
+    }
+
+    pub fn atqa(&self) -> [u8; 2] {
+        self.atqa
+    }
+
+    pub fn sak(&self) -> u8 {
+        self.sak
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == State::Active
+    }
+
+    /// Leave the ACTIVE state after an invalid command: back to IDLE,
+    /// or to HALT if the PICC was woken up from HALT
+    pub fn deactivate(&mut self) {
+        self.state = match self.halted {
+            true => State::Halt,
+            false => State::Idle,
+        };
+    }
+
+    /// The RF field was switched off
+    pub fn field_off(&mut self) {
+        self.state = State::Idle;
+        self.halted = false;
+    }
+
+    /// Handle a frame of the activation.
+    ///
+    /// In the ACTIVE state, HLTA is handled and other frames are left to the PICC model.
+    pub fn transceive(&mut self, frame: &Frame) -> Activation {
+        let bytes = frame.as_bytes();
+        if frame.bit_len() == 7 {
+            let answer = match (bytes[0], self.state) {
+                (REQA | WUPA, State::Idle) => {
+                    self.halted = false;
+                    true
+                }
+                (WUPA, State::Halt) => {
+                    self.halted = true;
+                    true
+                }
+                (REQA | WUPA, State::Ready { .. }) => true,
+                _ => false,
+            };
+            if answer {
+                self.state = State::Ready { level: 0 };
+                return Activation::Handled(Some(Frame::new(&self.atqa)));
+            }
+            if self.state == State::Active {
+                self.deactivate();
+            }
+            return Activation::Handled(None);
+        }
+
+        match self.state {
+            State::Ready { level } => Activation::Handled(self.anticollision(level, frame)),
+            State::Active => match frame.data_with_crc_a() {
+                Some(data) if data == HLTA => {
+                    self.state = State::Halt;
+                    Activation::Handled(None)
+                }
+                _ => Activation::Command,
+            },
+            State::Idle | State::Halt => Activation::Handled(None),
+        }
+    }
+
+    /// UID bytes of a cascade level (including the cascade tag) followed by the BCC
+    fn cascade_level(&self, level: usize) -> [u8; 5] {
+        let mut cl = [0u8; 5];
+        let complete = self.uid.len() == 4 + 3 * level;
+        match complete {
+            true => cl[..4].copy_from_slice(&self.uid[3 * level..3 * level + 4]),
+            false => {
+                cl[0] = CASCADE_TAG;
+                cl[1..4].copy_from_slice(&self.uid[3 * level..3 * level + 3]);
+            }
+        }
+        cl[4] = crc::bcc(&cl[..4]);
+        cl
+    }
+
+    fn anticollision(&mut self, level: usize, frame: &Frame) -> Option<Frame> {
+        let bytes = frame.as_bytes();
+        if bytes.len() < 2 || bytes[0] != SEL[level] {
+            self.deactivate();
+            return None;
+        }
+        let cl = self.cascade_level(level);
+        let cl_bit = |i: usize| (cl[i / 8] >> (i % 8)) & 1;
+
+        if bytes[1] == NVB_SELECT {
+            if frame.data_with_crc_a().and_then(|data| data.get(2..)) != Some(&cl[..]) {
+                self.deactivate();
+                return None;
+            }
+            let complete = self.uid.len() == 4 + 3 * level;
+            let sak = match complete {
+                true => {
+                    self.state = State::Active;
+                    self.sak
+                }
+                false => {
+                    self.state = State::Ready { level: level + 1 };
+                    SAK_CASCADE
+                }
+            };
+            return Some(Frame::with_crc_a(&[sak]));
+        }
+
+        // ANTICOLLISION: answer the bits of the UID following the known bits,
+        // if they match
+        let known = (bytes[1] >> 4).saturating_sub(2) as usize * 8 + (bytes[1] & 0b111) as usize;
+        if known >= 40 || frame.bit_len() != 16 + known {
+            return None;
+        }
+        if (0..known).any(|i| frame.bit(16 + i) != Some(cl_bit(i))) {
+            return None;
+        }
+        Some(Frame::from_bits((known..40).map(cl_bit)))
+    }
+}
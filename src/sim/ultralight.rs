@@ -0,0 +1,237 @@
+//! Simulated MIFARE Ultralight and NTAG21x PICCs.
+//!
+//! The memory is organized in pages of 4 bytes. READ, WRITE and COMPATIBILITY WRITE are
+//! supported by all models, GET_VERSION and FAST_READ by NTAG21x only. The static lock bits
+//! and the OTP bits of page 3 are enforced, the dynamic lock bits and the password
+//! protection of NTAG21x are not.
+
+use super::{Activation, Frame, Iso14443a, Picc, State};
+use crate::crc;
+
+const GET_VERSION: u8 = 0x60;
+const READ: u8 = 0x30;
+const FAST_READ: u8 = 0x3A;
+const WRITE: u8 = 0xA2;
+const COMPATIBILITY_WRITE: u8 = 0xA0;
+const ACK: u8 = 0xA;
+/// NAK: invalid argument, eg a page address out of range or a locked page
+const NAK_INVALID: u8 = 0x0;
+/// NAK: parity or CRC error
+const NAK_CRC: u8 = 0x1;
+
+/// Size of the largest memory (NTAG216, 231 pages)
+const MEMORY_SIZE: usize = 1024;
+
+/// Simulated MIFARE Ultralight or NTAG21x PICC
+#[derive(Debug, Clone)]
+pub struct Ultralight {
+    iso: Iso14443a,
+    memory: [u8; MEMORY_SIZE],
+    /// Number of 4 byte pages
+    pages: usize,
+    /// Answer to GET_VERSION, `None` if not supported
+    version: Option<[u8; 8]>,
+    /// Page of a COMPATIBILITY WRITE command that waits for its data
+    compatibility_write: Option<u8>,
+}
+
+impl Ultralight {
+    /// MIFARE Ultralight (MF0ICU1): 16 pages
+    pub fn new(uid: [u8; 7]) -> Self {
+        Self::with_memory(uid, 16, None, [0; 4])
+    }
+
+    /// NTAG213: 45 pages, 144 bytes of user memory
+    pub fn new_ntag213(uid: [u8; 7]) -> Self {
+        Self::ntag(uid, 45, 0x0F, 0x12)
+    }
+
+    /// NTAG215: 135 pages, 504 bytes of user memory
+    pub fn new_ntag215(uid: [u8; 7]) -> Self {
+        Self::ntag(uid, 135, 0x11, 0x3E)
+    }
+
+    /// NTAG216: 231 pages, 888 bytes of user memory
+    pub fn new_ntag216(uid: [u8; 7]) -> Self {
+        Self::ntag(uid, 231, 0x13, 0x6D)
+    }
+
+    /// NTAG21x, formatted as an empty NFC Forum Type 2 Tag
+    fn ntag(uid: [u8; 7], pages: usize, storage_size: u8, data_area: u8) -> Self {
+        let version = [0x00, 0x04, 0x04, 0x02, 0x01, 0x00, storage_size, 0x03];
+        // capability container: NDEF, version 1.0, size of the data area / 8, read/write
+        let cc = [0xE1, 0x10, data_area, 0x00];
+        let mut ntag = Self::with_memory(uid, pages, Some(version), cc);
+
+        // empty NDEF message TLV, followed by the terminator TLV
+        ntag.memory[16..20].copy_from_slice(&[0x03, 0x00, 0xFE, 0x00]);
+        // dynamic lock bytes, CFG0 (AUTH0 beyond the memory), CFG1, PWD and PACK
+        let config = (pages - 5) * 4;
+        ntag.memory[config..config + 20].copy_from_slice(&[
+            0x00, 0x00, 0x00, 0xBD, 0x04, 0x00, 0x00, 0xFF, 0x00, 0x05, 0x00, 0x00, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        ntag
+    }
+
+    fn with_memory(uid: [u8; 7], pages: usize, version: Option<[u8; 8]>, cc: [u8; 4]) -> Self {
+        let mut memory = [0; MEMORY_SIZE];
+        memory[..3].copy_from_slice(&uid[..3]);
+        memory[3] = crc::bcc(&[0x88, uid[0], uid[1], uid[2]]);
+        memory[4..8].copy_from_slice(&uid[3..]);
+        memory[8] = crc::bcc(&uid[3..]);
+        memory[9] = 0x48;
+        memory[12..16].copy_from_slice(&cc);
+        Ultralight {
+            iso: Iso14443a::new(&uid, [0x44, 0x00], 0x00),
+            memory,
+            pages,
+            version,
+            compatibility_write: None,
+        }
+    }
+
+    pub fn iso(&self) -> &Iso14443a {
+        &self.iso
+    }
+
+    pub fn state(&self) -> State {
+        self.iso.state()
+    }
+
+    /// The memory, page after page
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.pages * 4]
+    }
+
+    /// The memory, to set up data without lock bits
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory[..self.pages * 4]
+    }
+
+    fn command(&mut self, frame: &Frame) -> Option<Frame> {
+        let Some(data) = frame.data_with_crc_a() else {
+            return self.nak(NAK_CRC);
+        };
+        if let Some(page) = self.compatibility_write.take() {
+            // only the first 4 of the 16 bytes are written
+            return match data.len() {
+                16 => self.write(page, data[..4].try_into().unwrap()),
+                _ => self.nak(NAK_INVALID),
+            };
+        }
+
+        match *data {
+            [GET_VERSION] => match self.version {
+                Some(version) => Some(Frame::with_crc_a(&version)),
+                None => {
+                    // MIFARE Ultralight doesn't know the command and stays silent
+                    self.iso.deactivate();
+                    None
+                }
+            },
+            [READ, page] => self.read(page),
+            [FAST_READ, start, end] if self.version.is_some() => self.fast_read(start, end),
+            [WRITE, page, a, b, c, d] => self.write(page, [a, b, c, d]),
+            [COMPATIBILITY_WRITE, page] if self.writable(page) => {
+                self.compatibility_write = Some(page);
+                Some(Frame::with_bits(&[ACK], 4))
+            }
+            _ => self.nak(NAK_INVALID),
+        }
+    }
+
+    /// Answer a NAK and leave the ACTIVE state
+    fn nak(&mut self, nak: u8) -> Option<Frame> {
+        self.iso.deactivate();
+        Some(Frame::with_bits(&[nak], 4))
+    }
+
+    /// A page as it is read, the password and PACK of NTAG21x read as zero
+    fn page(&self, page: usize) -> [u8; 4] {
+        match self.version {
+            Some(_) if page >= self.pages - 2 => [0; 4],
+            _ => self.memory[page * 4..page * 4 + 4].try_into().unwrap(),
+        }
+    }
+
+    /// 4 pages, rolling over to page 0 at the end of the memory
+    fn read(&mut self, page: u8) -> Option<Frame> {
+        let page = page as usize;
+        if page >= self.pages {
+            return self.nak(NAK_INVALID);
+        }
+        let mut data = [0u8; 16];
+        for (i, chunk) in data.chunks_mut(4).enumerate() {
+            chunk.copy_from_slice(&self.page((page + i) % self.pages));
+        }
+        Some(Frame::with_crc_a(&data))
+    }
+
+    fn fast_read(&mut self, start: u8, end: u8) -> Option<Frame> {
+        let (start, end) = (start as usize, end as usize);
+        if start > end || end >= self.pages {
+            return self.nak(NAK_INVALID);
+        }
+        let mut data = [0u8; MEMORY_SIZE];
+        for page in start..=end {
+            data[(page - start) * 4..][..4].copy_from_slice(&self.page(page));
+        }
+        Some(Frame::with_crc_a(&data[..(end - start + 1) * 4]))
+    }
+
+    /// Is the page writable: not one of the UID pages, not beyond the memory,
+    /// and not locked by the static lock bits
+    fn writable(&self, page: u8) -> bool {
+        let lock = u16::from_le_bytes([self.memory[10], self.memory[11]]);
+        match page as usize {
+            0 | 1 => false,
+            2 => true,
+            // L-OTP
+            3 => lock & (1 << 3) == 0,
+            // L4 to L15
+            page @ 4..=15 => page < self.pages && lock & (1 << page) == 0,
+            page => page < self.pages,
+        }
+    }
+
+    fn write(&mut self, page: u8, data: [u8; 4]) -> Option<Frame> {
+        if !self.writable(page) {
+            return self.nak(NAK_INVALID);
+        }
+        let start = page as usize * 4;
+        match page {
+            // only the lock bytes can be written, and only set
+            2 => {
+                self.memory[10] |= data[2];
+                self.memory[11] |= data[3];
+            }
+            // one time programmable bits
+            3 => {
+                for (b, d) in self.memory[start..start + 4].iter_mut().zip(data) {
+                    *b |= d;
+                }
+            }
+            _ => self.memory[start..start + 4].copy_from_slice(&data),
+        }
+        Some(Frame::with_bits(&[ACK], 4))
+    }
+}
+
+impl Picc for Ultralight {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        let response = match self.iso.transceive(frame) {
+            Activation::Handled(response) => response,
+            Activation::Command => self.command(frame),
+        };
+        if !self.iso.is_active() {
+            self.compatibility_write = None;
+        }
+        response
+    }
+
+    fn field_off(&mut self) {
+        self.iso.field_off();
+        self.compatibility_write = None;
+    }
+}
@@ -0,0 +1,258 @@
+//! Parser for BER-TLV encoded data (ISO/IEC 8825-1), as used by
+//! ISO/IEC 7816-4 smart card applications (eg EMV).
+//!
+//! The parser does not allocate: every [Tlv] refers to the underlying buffer
+//! and the children of constructed data objects are parsed on demand,
+//! so the buffer can be walked as a tree.
+//!
+//! ```ignore
+//! for tlv in tlv::parse(&response) {
+//!     if let Some(label) = tlv.find(0x50) {
+//!         // application label
+//!     }
+//! }
+//! ```
+
+/// A single BER-TLV data object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tlv<'a> {
+    tag: u32,
+    constructed: bool,
+    value: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    /// The tag, with the tag bytes in big endian order (eg `0x9F38`)
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    /// The value field
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Does the value field contain other data objects
+    pub fn is_constructed(&self) -> bool {
+        self.constructed
+    }
+
+    /// The data objects contained in a constructed data object.
+    ///
+    /// This is empty for primitive data objects.
+    pub fn children(&self) -> Tlvs<'a> {
+        if self.constructed {
+            Tlvs::new(self.value)
+        } else {
+            Tlvs::new(&[])
+        }
+    }
+
+    /// Search the children (recursively, depth-first) for a data object with the given tag
+    pub fn find(&self, tag: u32) -> Option<Tlv<'a>> {
+        self.children().find_tag(tag)
+    }
+}
+
+/// Iterator over consecutive BER-TLV data objects.
+///
+/// Iteration stops at the end of the data, or at the first malformed data object.
+#[derive(Debug, Clone)]
+pub struct Tlvs<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Tlvs<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Tlvs { data }
+    }
+
+    /// Search (recursively, depth-first) for a data object with the given tag
+    pub fn find_tag(self, tag: u32) -> Option<Tlv<'a>> {
+        for tlv in self {
+            if tlv.tag == tag {
+                return Some(tlv);
+            }
+            if let Some(found) = tlv.find(tag) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for Tlvs<'a> {
+    type Item = Tlv<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // 0x00 and 0xFF bytes are allowed before, between and after data objects
+        while let [0x00 | 0xFF, rest @ ..] = self.data {
+            self.data = rest;
+        }
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let parsed = parse_tag(self.data).and_then(|(tag, rest)| {
+            let (len, rest) = parse_length(rest)?;
+            let value = rest.get(..len)?;
+            Some((tag, value, &rest[len..]))
+        });
+
+        match parsed {
+            Some((tag, value, rest)) => {
+                self.data = rest;
+                Some(Tlv {
+                    tag,
+                    constructed: first_tag_byte(tag) & 0x20 != 0,
+                    value,
+                })
+            }
+            None => {
+                self.data = &[];
+                None
+            }
+        }
+    }
+}
+
+/// Parse BER-TLV data objects
+pub fn parse(data: &[u8]) -> Tlvs<'_> {
+    Tlvs::new(data)
+}
+
+/// Parse a tag, returns the tag and the remaining data
+pub(crate) fn parse_tag(data: &[u8]) -> Option<(u32, &[u8])> {
+    let (&first, mut rest) = data.split_first()?;
+    let mut tag = first as u32;
+    if first & 0x1F == 0x1F {
+        // subsequent bytes follow, as long as bit 8 is set (at most 3 in total)
+        loop {
+            let (&byte, tail) = rest.split_first()?;
+            rest = tail;
+            tag = (tag << 8) | byte as u32;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if tag > 0xFF_FF {
+                return None;
+            }
+        }
+    }
+    Some((tag, rest))
+}
+
+/// Parse a length field, returns the length and the remaining data.
+/// The indefinite form is not supported.
+pub(crate) fn parse_length(data: &[u8]) -> Option<(usize, &[u8])> {
+    let (&first, rest) = data.split_first()?;
+    match first {
+        0x00..=0x7F => Some((first as usize, rest)),
+        0x81..=0x83 => {
+            let n = (first & 0x7F) as usize;
+            let bytes = rest.get(..n)?;
+            let len = bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+            Some((len, &rest[n..]))
+        }
+        _ => None,
+    }
+}
+
+fn first_tag_byte(tag: u32) -> u8 {
+    let mut tag = tag;
+    while tag > 0xFF {
+        tag >>= 8;
+    }
+    tag as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FCI of the PPSE of a payment card with one application
+    const FCI: [u8; 47] = [
+        0x6F, 0x2D, 0x84, 0x0E, 0x32, 0x50, 0x41, 0x59, 0x2E, 0x53, 0x59, 0x53, 0x2E, 0x44, 0x44,
+        0x46, 0x30, 0x31, 0xA5, 0x1B, 0xBF, 0x0C, 0x18, 0x61, 0x16, 0x4F, 0x07, 0xA0, 0x00, 0x00,
+        0x00, 0x04, 0x10, 0x10, 0x50, 0x08, 0x44, 0x45, 0x42, 0x49, 0x54, 0x20, 0x4D, 0x43, 0x87,
+        0x01, 0x01,
+    ];
+
+    #[test]
+    fn nested_data_objects() {
+        let fci = parse(&FCI).next().unwrap();
+        assert_eq!(fci.tag(), 0x6F);
+        assert!(fci.is_constructed());
+        assert_eq!(fci.value().len(), 0x2D);
+
+        let tags: heapless::Vec<u32, 4> = fci.children().map(|t| t.tag()).collect();
+        assert_eq!(tags, [0x84, 0xA5]);
+
+        let name = fci.find(0x84).unwrap();
+        assert!(!name.is_constructed());
+        assert_eq!(name.value(), b"2PAY.SYS.DDF01");
+        assert_eq!(name.children().next(), None);
+
+        // two byte tag, found depth-first
+        let template = fci.find(0xBF0C).unwrap();
+        assert!(template.is_constructed());
+        assert_eq!(template.find(0x50).unwrap().value(), b"DEBIT MC");
+        assert_eq!(parse(&FCI).find_tag(0x87).unwrap().value(), [0x01]);
+        assert_eq!(parse(&FCI).find_tag(0x9F38), None);
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(parse_tag(&[0x5A, 0x08]), Some((0x5A, &[0x08][..])));
+        assert_eq!(parse_tag(&[0x9F, 0x38, 0x03]), Some((0x9F38, &[0x03][..])));
+        assert_eq!(parse_tag(&[0xDF, 0x81, 0x01]), Some((0xDF8101, &[][..])));
+        // the subsequent byte is missing
+        assert_eq!(parse_tag(&[0x9F]), None);
+        // more than 3 tag bytes
+        assert_eq!(parse_tag(&[0xDF, 0x81, 0x81, 0x01]), None);
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(parse_length(&[0x7F]), Some((0x7F, &[][..])));
+        assert_eq!(parse_length(&[0x81, 0x80, 0xAA]), Some((0x80, &[0xAA][..])));
+        assert_eq!(parse_length(&[0x82, 0x01, 0x00]), Some((0x100, &[][..])));
+        assert_eq!(
+            parse_length(&[0x83, 0x01, 0x00, 0x00]),
+            Some((0x10000, &[][..]))
+        );
+        // indefinite form and lengths of more than 3 bytes
+        assert_eq!(parse_length(&[0x80]), None);
+        assert_eq!(parse_length(&[0x84, 0x00, 0x00, 0x00, 0x01]), None);
+        // the length bytes are missing
+        assert_eq!(parse_length(&[0x82, 0x01]), None);
+    }
+
+    #[test]
+    fn long_value() {
+        let mut data = [0u8; 3 + 200];
+        data[..3].copy_from_slice(&[0x53, 0x81, 200]);
+        data[3..].fill(0x42);
+        let tlv = parse(&data).next().unwrap();
+        assert_eq!(tlv.tag(), 0x53);
+        assert_eq!(tlv.value(), [0x42; 200]);
+    }
+
+    #[test]
+    fn padding_between_data_objects() {
+        let data = [0x00, 0x5A, 0x01, 0x12, 0xFF, 0xFF, 0x50, 0x00, 0x00];
+        let tags: heapless::Vec<(u32, &[u8]), 4> =
+            parse(&data).map(|t| (t.tag(), t.value())).collect();
+        assert_eq!(tags, [(0x5A, &[0x12][..]), (0x50, &[][..])]);
+    }
+
+    #[test]
+    fn malformed_data_object_ends_the_iteration() {
+        // the value of the second data object is truncated
+        let data = [0x5A, 0x01, 0x12, 0x50, 0x04, 0x41, 0x42];
+        let mut tlvs = parse(&data);
+        assert_eq!(tlvs.next().unwrap().value(), [0x12]);
+        assert_eq!(tlvs.next(), None);
+        assert_eq!(tlvs.next(), None);
+    }
+}
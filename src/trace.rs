@@ -0,0 +1,446 @@
+//! Recording and replay of the SPI traffic between the driver and the MFRC522.
+//!
+//! [Recorder] wraps the SPI bus and decodes every access of the driver (a register read or
+//! write, or a burst of them on `FIFODataReg`) into a [Transaction], which it hands to a
+//! [Sink]: a ring buffer keeping the last transactions, or (`std` feature) a `Vec` keeping
+//! all of them.
+//!
+//! [Replay] is an SPI bus playing a recorded trace back to the driver: register reads are
+//! answered with the recorded values, and every access is checked against the trace.
+//! A trace captured when a protocol bug shows up in the field becomes a deterministic
+//! regression test that runs without hardware.
+//!
+//! ```ignore
+//! let recorder = Recorder::new(spi, Deque::<Transaction, 256>::new());
+//! let mut mfrc522 = Mfrc522::new(recorder).init()?;
+//! // ... reproduce the bug ...
+//! let (recorder, _) = mfrc522.release();
+//! let (_, trace) = recorder.release();
+//!
+//! let trace: Vec<Transaction> = trace.into_iter().collect();
+//! let mut mfrc522 = Mfrc522::new(Replay::new(&trace)).init()?;
+//! ```
+//!
+//! With the `std` feature, a trace is saved and loaded as text, one transaction per line
+//! (see [save] and [load]):
+//!
+//! ```text
+//! W CommandReg 0C
+//! R ComIrqReg 30
+//! R FIFOLevelReg 02
+//! R FIFODataReg 44 00
+//! ```
+
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+use heapless::{Deque, Vec};
+
+use crate::register::Register;
+use crate::spi;
+
+/// Largest number of values of a transaction, the size of the FIFO
+pub const MAX_VALUES: usize = 64;
+
+/// Direction of a [Transaction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Access of the driver to one register, decoded from the SPI frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    access: Access,
+    address: u8,
+    values: Vec<u8, MAX_VALUES>,
+}
+
+impl Transaction {
+    /// Access to the register at `address` (6 bits).
+    ///
+    /// # Panics
+    ///
+    /// If the address is beyond 0x3F, or if there are more than [MAX_VALUES] values
+    pub fn new(access: Access, address: u8, values: &[u8]) -> Self {
+        assert!(address <= 0x3F, "invalid register address {:#04X}", address);
+        Transaction {
+            access,
+            address,
+            values: Vec::from_slice(values).expect("too many values"),
+        }
+    }
+
+    pub fn access(&self) -> Access {
+        self.access
+    }
+
+    /// The 6 bit address of the register
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// The register, `None` for a reserved address
+    pub fn register(&self) -> Option<Register> {
+        Register::from_address(self.address)
+    }
+
+    /// The values read or written, in order
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+}
+
+/// Text form of a transaction: `R` or `W`, the register name (or its address for a
+/// reserved one) and the values in hexadecimal, eg `W FIFODataReg 93 20`
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => 'R',
+            Access::Write => 'W',
+        };
+        match self.register() {
+            Some(register) => write!(f, "{} {:?}", access, register)?,
+            None => write!(f, "{} {:#04X}", access, self.address)?,
+        }
+        for value in &self.values {
+            write!(f, " {:02X}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The text is not a [Transaction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTransactionError;
+
+impl fmt::Display for ParseTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid SPI transaction")
+    }
+}
+
+impl FromStr for Transaction {
+    type Err = ParseTransactionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let access = match words.next() {
+            Some("R") => Access::Read,
+            Some("W") => Access::Write,
+            _ => return Err(ParseTransactionError),
+        };
+        let address = match words.next() {
+            Some(word) if word.starts_with("0x") => {
+                u8::from_str_radix(&word[2..], 16).map_err(|_| ParseTransactionError)?
+            }
+            Some(word) => register_by_name(word).ok_or(ParseTransactionError)? as u8,
+            None => return Err(ParseTransactionError),
+        };
+        if address > 0x3F {
+            return Err(ParseTransactionError);
+        }
+        let mut values = Vec::new();
+        for word in words {
+            let value = u8::from_str_radix(word, 16).map_err(|_| ParseTransactionError)?;
+            values.push(value).map_err(|_| ParseTransactionError)?;
+        }
+        Ok(Transaction {
+            access,
+            address,
+            values,
+        })
+    }
+}
+
+/// The register with this name (as in the datasheet and [Register])
+fn register_by_name(name: &str) -> Option<Register> {
+    (0..=0x3F)
+        .filter_map(Register::from_address)
+        .find(|register| {
+            let mut buffer = heapless::String::<32>::new();
+            fmt::Write::write_fmt(&mut buffer, format_args!("{:?}", register)).is_ok()
+                && buffer == name
+        })
+}
+
+/// Destination of the transactions of a [Recorder]
+pub trait Sink {
+    fn record(&mut self, transaction: Transaction);
+}
+
+/// Ring buffer of the last `N` transactions
+impl<const N: usize> Sink for Deque<Transaction, N> {
+    fn record(&mut self, transaction: Transaction) {
+        if self.is_full() {
+            self.pop_front();
+        }
+        let _ = self.push_back(transaction);
+    }
+}
+
+/// All transactions
+#[cfg(feature = "std")]
+impl Sink for std::vec::Vec<Transaction> {
+    fn record(&mut self, transaction: Transaction) {
+        self.push(transaction);
+    }
+}
+
+/// Address of the register from the first byte of an SPI frame
+fn address_of(byte: u8) -> u8 {
+    (byte >> 1) & 0x3F
+}
+
+fn is_read(byte: u8) -> bool {
+    byte & 0x80 != 0
+}
+
+/// Split the address bytes of a read transfer into runs of the same register.
+///
+/// Returns the address of each run with the indices of its address bytes; each value is
+/// shifted out one byte after its address.
+fn read_runs(addresses: &[u8]) -> impl Iterator<Item = (u8, Range<usize>)> + '_ {
+    let mut start = 0;
+    core::iter::from_fn(move || {
+        let &first = addresses.get(start)?;
+        let len = addresses[start..]
+            .iter()
+            .take(MAX_VALUES)
+            .take_while(|&&byte| byte == first)
+            .count();
+        start += len;
+        Some((address_of(first), start - len..start))
+    })
+}
+
+/// SPI bus recording the transactions of the driver.
+///
+/// Frames longer than the FIFO plus the address byte are cut off in the trace.
+pub struct Recorder<SPI, S> {
+    spi: SPI,
+    sink: S,
+}
+
+impl<SPI, S: Sink> Recorder<SPI, S> {
+    pub fn new(spi: SPI, sink: S) -> Self {
+        Recorder { spi, sink }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Release the SPI bus and the recorded transactions
+    pub fn release(self) -> (SPI, S) {
+        (self.spi, self.sink)
+    }
+}
+
+impl<SPI, S> spi::Transfer<u8> for Recorder<SPI, S>
+where
+    SPI: spi::Transfer<u8>,
+    S: Sink,
+{
+    type Error = SPI::Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], SPI::Error> {
+        // the transfer overwrites the address bytes
+        let mut sent = [0u8; MAX_VALUES + 1];
+        let len = words.len().min(sent.len());
+        sent[..len].copy_from_slice(&words[..len]);
+
+        let received = self.spi.transfer(words)?;
+        let Some(&first) = sent[..len].first() else {
+            return Ok(received);
+        };
+        if !is_read(first) {
+            let values = &sent[1..len];
+            self.sink
+                .record(Transaction::new(Access::Write, address_of(first), values));
+            return Ok(received);
+        }
+        for (address, run) in read_runs(&sent[..len - 1]) {
+            let values = &received[run.start + 1..run.end + 1];
+            self.sink
+                .record(Transaction::new(Access::Read, address, values));
+        }
+        Ok(received)
+    }
+}
+
+impl<SPI, S> spi::Write<u8> for Recorder<SPI, S>
+where
+    SPI: spi::Write<u8>,
+    S: Sink,
+{
+    type Error = SPI::Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), SPI::Error> {
+        self.spi.write(words)?;
+        // without a transfer, the values of a read are lost and not worth recording
+        if let Some((&first, values)) = words.split_first() {
+            if !is_read(first) {
+                for values in values.chunks(MAX_VALUES) {
+                    self.sink
+                        .record(Transaction::new(Access::Write, address_of(first), values));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The driver did not access the registers as recorded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the transaction in the trace
+    pub position: usize,
+    /// The recorded transaction, `None` past the end of the trace
+    pub expected: Option<Transaction>,
+    /// The access of the driver, the values of a read are zero
+    pub actual: Transaction,
+}
+
+/// SPI bus playing a recorded trace back to the driver
+#[derive(Debug, Clone)]
+pub struct Replay<'a> {
+    trace: &'a [Transaction],
+    position: usize,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(trace: &'a [Transaction]) -> Self {
+        Replay { trace, position: 0 }
+    }
+
+    /// Number of transactions played so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// All transactions of the trace have been played
+    pub fn is_finished(&self) -> bool {
+        self.position == self.trace.len()
+    }
+
+    /// Play the next transaction of the trace if it matches `actual`
+    fn play(&mut self, actual: &Transaction) -> Option<&'a Transaction> {
+        let expected = self.trace.get(self.position)?;
+        if !matches(expected, actual) {
+            return None;
+        }
+        self.position += 1;
+        Some(expected)
+    }
+
+    /// The driver did `actual` instead of the next transaction of the trace
+    fn divergence(&self, actual: Transaction) -> Divergence {
+        Divergence {
+            position: self.position,
+            expected: self.trace.get(self.position).cloned(),
+            actual,
+        }
+    }
+}
+
+/// The recorded transaction matches the access of the driver: same register and number of
+/// values, and for a write the same values
+fn matches(expected: &Transaction, actual: &Transaction) -> bool {
+    expected.access == actual.access
+        && expected.address == actual.address
+        && expected.values.len() == actual.values.len()
+        && (actual.access == Access::Read || expected.values == actual.values)
+}
+
+impl spi::Transfer<u8> for Replay<'_> {
+    type Error = Divergence;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Divergence> {
+        let Some(&first) = words.first() else {
+            return Ok(words);
+        };
+        if !is_read(first) {
+            for values in words[1..].chunks(MAX_VALUES) {
+                let actual = Transaction::new(Access::Write, address_of(first), values);
+                if self.play(&actual).is_none() {
+                    return Err(self.divergence(actual));
+                }
+            }
+            words.fill(0);
+            return Ok(words);
+        }
+
+        // like the recorder, frames longer than the FIFO plus the address byte are cut off
+        let len = words.len().min(MAX_VALUES + 1);
+        let mut received = [0u8; MAX_VALUES + 1];
+        for (address, run) in read_runs(&words[..len - 1]) {
+            let zeros = [0u8; MAX_VALUES];
+            let actual = Transaction::new(Access::Read, address, &zeros[..run.len()]);
+            let Some(recorded) = self.play(&actual) else {
+                return Err(self.divergence(actual));
+            };
+            received[run.start + 1..run.end + 1].copy_from_slice(recorded.values());
+        }
+        words[..len].copy_from_slice(&received[..len]);
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for Replay<'_> {
+    type Error = Divergence;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Divergence> {
+        if let Some((&first, values)) = words.split_first() {
+            if !is_read(first) {
+                for values in values.chunks(MAX_VALUES) {
+                    let actual = Transaction::new(Access::Write, address_of(first), values);
+                    if self.play(&actual).is_none() {
+                        return Err(self.divergence(actual));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Save a trace as text, one transaction per line
+#[cfg(feature = "std")]
+pub fn save<'a, W, I>(trace: I, mut writer: W) -> std::io::Result<()>
+where
+    W: std::io::Write,
+    I: IntoIterator<Item = &'a Transaction>,
+{
+    for transaction in trace {
+        writeln!(writer, "{}", transaction)?;
+    }
+    Ok(())
+}
+
+/// Load a trace saved by [save]. Empty lines and lines starting with `#` are skipped,
+/// to annotate a trace kept as a regression test.
+#[cfg(feature = "std")]
+pub fn load<R: std::io::BufRead>(reader: R) -> std::io::Result<std::vec::Vec<Transaction>> {
+    use std::io::{Error, ErrorKind};
+
+    let mut trace = std::vec::Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let transaction = line.parse().map_err(|e: ParseTransactionError| {
+            Error::new(ErrorKind::InvalidData, format!("line {}: {}", index + 1, e))
+        })?;
+        trace.push(transaction);
+    }
+    Ok(trace)
+}
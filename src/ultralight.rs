@@ -0,0 +1,199 @@
+//! Commands for the MIFARE Ultralight family.
+//!
+//! MIFARE Ultralight memory is organized in pages of 4 bytes.
+//! Reading is done with [ul_read](Card::ul_read), which returns 4 pages at once,
+//! or with [ul_fast_read](Card::ul_fast_read) for a range of pages (NTAG and Ultralight EV1).
+//!
+//! MIFARE Ultralight C adds 3DES mutual authentication, which is available
+//! with the `crypto` feature.
+
+#[cfg(feature = "crypto")]
+use rand_core::RngCore;
+
+#[cfg(feature = "crypto")]
+use crate::crypto::{self, Cipher, Key};
+use crate::error::{Context, Error, Nak, Operation};
+use crate::picc;
+use crate::{spi, Card, Initialized, Mfrc522, WithNssDelay, MAX_FRAME_SIZE};
+
+/// Size of a MIFARE Ultralight C key (2K3DES)
+pub const ULC_KEYSIZE: usize = 16;
+/// MIFARE Ultralight C 2K3DES key
+pub type UltralightCKey = [u8; ULC_KEYSIZE];
+
+/// First page of the MIFARE Ultralight C 3DES key
+const ULC_KEY_PAGE: u8 = 0x2C;
+/// Page containing AUTH0, the first page that is protected by authentication
+const ULC_AUTH0_PAGE: u8 = 0x2A;
+/// Page containing AUTH1, which determines if reads are protected by authentication
+const ULC_AUTH1_PAGE: u8 = 0x2B;
+
+/// Reads a range of pages (NTAG and MIFARE Ultralight EV1)
+const FAST_READ: u8 = 0x3A;
+
+/// Start of the mutual authentication, also used for subsequent frames
+#[cfg(feature = "crypto")]
+const ULC_AUTHENTICATE: u8 = 0x1A;
+#[cfg(feature = "crypto")]
+const ULC_ADDITIONAL_FRAME: u8 = 0xAF;
+
+impl<'a, E, SPI, NSS, D> Card<'a, SPI, NSS, D>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    Mfrc522<SPI, NSS, D, Initialized>: WithNssDelay,
+{
+    /// Reads 4 pages (16 bytes) from a MIFARE Ultralight PICC, starting at `page`.
+    ///
+    /// The CRC_A of the response is verified. Transient errors are retried according to the
+    /// [RetryPolicy](crate::retry::RetryPolicy).
+    pub fn ul_read(&mut self, page: u8) -> Result<[u8; 16], Error<E>> {
+        self.mfrc522.retry(&self.uid, None, |mfrc522| {
+            mfrc522.mf_read(page, true, Nak::ultralight)
+        })
+    }
+
+    /// Reads the pages `start` to `end` (inclusive) with a single FAST_READ command,
+    /// supported by NTAG and MIFARE Ultralight EV1 PICCs.
+    ///
+    /// At most 63 pages can be read at once. Returns the number of bytes stored in `rx`,
+    /// or [Error::NoRoom] if `end` is before `start` or the pages do not fit in `rx`.
+    pub fn ul_fast_read(&mut self, start: u8, end: u8, rx: &mut [u8]) -> Result<usize, Error<E>> {
+        if end < start {
+            return Err(Error::NoRoom);
+        }
+        let len = (end - start) as usize * 4 + 4;
+        if len > MAX_FRAME_SIZE - 2 || len > rx.len() {
+            return Err(Error::NoRoom);
+        }
+
+        let operation = Operation::Read(start);
+        let fifo_data = self
+            .mfrc522
+            .transceive_crc::<MAX_FRAME_SIZE>(&[FAST_READ, start, end], true)
+            .map_err(|e| e.with_operation(operation))?;
+        if fifo_data.is_ack_nak() {
+            fifo_data
+                .ack(Nak::ultralight)
+                .map_err(|e| e.with_operation(operation))?;
+        }
+        if fifo_data.valid_bytes != len || fifo_data.valid_bits != 0 {
+            return Err(Error::IncompleteFrame(Context::default()).with_operation(operation));
+        }
+
+        rx[..len].copy_from_slice(&fifo_data.buffer[..len]);
+        Ok(len)
+    }
+
+    /// Writes one 4 byte page to a MIFARE Ultralight PICC.
+    ///
+    /// Transient errors are retried according to the [RetryPolicy](crate::retry::RetryPolicy).
+    pub fn ul_write(&mut self, page: u8, data: [u8; 4]) -> Result<(), Error<E>> {
+        let mut tx = [picc::Command::UlWrite as u8, page, 0, 0, 0, 0];
+        tx[2..].copy_from_slice(&data);
+
+        self.mfrc522.retry(&self.uid, None, |mfrc522| {
+            mfrc522
+                .transceive_crc::<1>(&tx, false)
+                .and_then(|fifo_data| fifo_data.ack(Nak::ultralight))
+                .map_err(|e| e.with_operation(Operation::Write(page)))
+        })
+    }
+
+    /// Writes the 3DES key to a MIFARE Ultralight C PICC.
+    ///
+    /// The key pages cannot be read back, if access to the configuration pages is
+    /// protected, the PICC needs to be authenticated first.
+    pub fn ulc_write_key(&mut self, key: &UltralightCKey) -> Result<(), Error<E>> {
+        // Both halves of the key are stored with their bytes in reversed order
+        for idx in 0..4 {
+            let start = (idx / 2) * 8 + if idx % 2 == 0 { 4 } else { 0 };
+            let mut page = [0u8; 4];
+            for (i, byte) in page.iter_mut().enumerate() {
+                *byte = key[start + 3 - i];
+            }
+            self.ul_write(ULC_KEY_PAGE + idx as u8, page)?;
+        }
+        Ok(())
+    }
+
+    /// Configures AUTH0 of a MIFARE Ultralight C PICC:
+    /// all pages starting from `page` require authentication.
+    ///
+    /// Valid values are `0x03` to `0x30`, with `0x30` disabling authentication.
+    pub fn ulc_set_auth0(&mut self, page: u8) -> Result<(), Error<E>> {
+        self.ul_write(ULC_AUTH0_PAGE, [page, 0, 0, 0])
+    }
+
+    /// Configures AUTH1 of a MIFARE Ultralight C PICC:
+    /// if `protect_read` is false, only write access requires authentication.
+    pub fn ulc_set_auth1(&mut self, protect_read: bool) -> Result<(), Error<E>> {
+        let auth1 = if protect_read { 0x00 } else { 0x01 };
+        self.ul_write(ULC_AUTH1_PAGE, [auth1, 0, 0, 0])
+    }
+
+    /// 3DES mutual authentication with a MIFARE Ultralight C PICC.
+    ///
+    /// The random number generator is used for the random challenge RndA.
+    /// Returns [Error::Integrity] when the PICC could not prove it knows the key.
+    #[cfg(feature = "crypto")]
+    pub fn ulc_authenticate<R: RngCore>(
+        &mut self,
+        key: &UltralightCKey,
+        rng: &mut R,
+    ) -> Result<(), Error<E>> {
+        let cipher = Cipher::new(&Key::TDes2(*key));
+        let mut iv = [0u8; crypto::MAX_BLOCK_SIZE];
+
+        // The PICC responds with the encrypted random number RndB
+        let mut rnd_b = [0u8; 8];
+        self.ulc_transceive(&[ULC_AUTHENTICATE, 0x00], ULC_ADDITIONAL_FRAME, &mut rnd_b)?;
+        cipher.decrypt_cbc(&mut iv, &mut rnd_b);
+
+        // Respond with RndA + RndB (rotated left by 1 byte)
+        let mut rnd_a = [0u8; 8];
+        rng.fill_bytes(&mut rnd_a);
+        let mut tx = [0u8; 17];
+        tx[0] = ULC_ADDITIONAL_FRAME;
+        tx[1..9].copy_from_slice(&rnd_a);
+        crypto::rotate_left(&mut tx[9..17], &rnd_b);
+        cipher.encrypt_cbc(&mut iv, &mut tx[1..17]);
+
+        // The PICC proves it knows the key by returning RndA (rotated left by 1 byte)
+        let mut response = [0u8; 8];
+        self.ulc_transceive(&tx, 0x00, &mut response)?;
+        cipher.decrypt_cbc(&mut iv, &mut response);
+
+        let mut expected = [0u8; 8];
+        crypto::rotate_left(&mut expected, &rnd_a);
+        if response != expected {
+            return Err(Error::Integrity);
+        }
+        Ok(())
+    }
+
+    /// Exchange one frame of the authentication, the response consists of a
+    /// status byte followed by 8 bytes of encrypted data.
+    #[cfg(feature = "crypto")]
+    fn ulc_transceive(
+        &mut self,
+        data: &[u8],
+        status: u8,
+        rx: &mut [u8; 8],
+    ) -> Result<(), Error<E>> {
+        let fifo_data = self.mfrc522.transceive_crc::<11>(data, true)?;
+        // a 4-bit frame is a NAK
+        if fifo_data.is_ack_nak() {
+            fifo_data.ack(Nak::ultralight)?;
+        }
+        if fifo_data.valid_bytes != 9 || fifo_data.valid_bits != 0 {
+            return Err(Error::IncompleteFrame(Context::default()));
+        }
+
+        if fifo_data.buffer[0] != status {
+            return Err(Error::Nak(Nak::Unexpected, Context::default()));
+        }
+
+        rx.copy_from_slice(&fifo_data.buffer[1..9]);
+        Ok(())
+    }
+}
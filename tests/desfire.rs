@@ -0,0 +1,184 @@
+#![cfg(feature = "crypto")]
+
+use std::collections::VecDeque;
+
+use mfrc522::crypto::Key;
+use mfrc522::desfire::{CommMode, Desfire};
+use mfrc522::error::Error;
+use mfrc522::sim::{Activation, Frame, Iso14443a, Picc, Simulator};
+use mfrc522::Mfrc522;
+use rand_core::{impls, RngCore};
+
+const UID: [u8; 7] = [0x04, 0x32, 0x6A, 0x1A, 0x2B, 0x5C, 0x80];
+/// ATS of a MIFARE DESFire EV1: FSCI 5, 106 to 848 kbit/s, FWI 8
+const ATS: [u8; 6] = [0x06, 0x75, 0x77, 0x81, 0x02, 0x80];
+
+/// MIFARE DESFire that answers the APDUs of a script, recorded from the exchange with a
+/// PICC using the same random number RndB (`10 11 12 ..`)
+struct Scripted {
+    iso: Iso14443a,
+    script: VecDeque<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Scripted {
+    fn new(script: &[(&str, &str)]) -> Self {
+        Scripted {
+            iso: Iso14443a::new(&UID, [0x44, 0x03], 0x20),
+            script: script.iter().map(|(c, r)| (hex(c), hex(r))).collect(),
+        }
+    }
+}
+
+impl Picc for Scripted {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        if let Activation::Handled(response) = self.iso.transceive(frame) {
+            return response;
+        }
+        match frame.data_with_crc_a()? {
+            // RATS
+            [0xE0, _] => Some(Frame::with_crc_a(&ATS)),
+            // I-block: the response is sent with the same block number
+            [pcb, apdu @ ..] if pcb & 0xE2 == 0x02 => {
+                let (command, response) = self.script.pop_front().expect("end of the script");
+                assert_eq!(apdu, command, "unexpected APDU");
+                let mut block = vec![*pcb];
+                block.extend_from_slice(&response);
+                Some(Frame::with_crc_a(&block))
+            }
+            _ => None,
+        }
+    }
+
+    fn field_off(&mut self) {
+        self.iso.field_off();
+    }
+}
+
+/// Random number generator returning `A0 A1 A2 ..`, the RndA of the scripts
+struct Counter(u8);
+
+impl RngCore for Counter {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            *byte = self.0;
+            self.0 = self.0.wrapping_add(1);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Activate the PICC and run the body with it as MIFARE DESFire, the whole script has to be used
+macro_rules! run {
+    ($script:expr, |$desfire:ident| $body:block) => {{
+        let mut mfrc522 = Mfrc522::new(Simulator::new(Scripted::new($script)))
+            .init()
+            .unwrap();
+        let atqa = mfrc522.reqa().unwrap();
+        let mut card = mfrc522.select(&atqa).unwrap();
+        let mut $desfire = Desfire::new(card.iso_dep().unwrap());
+        $body
+        drop($desfire);
+        drop(card);
+
+        let (sim, _) = mfrc522.release();
+        assert!(sim.picc().script.is_empty());
+    }};
+}
+
+const AES: [(&str, &str); 3] = [
+    ("90AA0000010000", "358D5B59ADB65D04107676586F47344691AF"),
+    (
+        "90AF000020A325CABC0DB3996E50BE9AC86F3CD7C0CC81AF66A73DB05DD02A6538821562FA00",
+        "CC731F7B46E3C2AF1531012851ACD8C59100",
+    ),
+    // READ_DATA of 4 bytes of file 1, with CMAC
+    ("90BD0000070100000004000000", "CAFEBABE9C80A0BA17193DC59100"),
+];
+
+#[test]
+fn aes_authentication() {
+    run!(&AES, |desfire| {
+        desfire
+            .authenticate(0, &Key::Aes([0; 16]), &mut Counter(0xA0))
+            .unwrap();
+        assert!(desfire.is_authenticated());
+
+        let mut data = [0u8; 4];
+        let n = desfire.read_data(1, 0, &mut data, CommMode::Mac).unwrap();
+        assert_eq!(data[..n], [0xCA, 0xFE, 0xBA, 0xBE]);
+    })
+}
+
+#[test]
+fn legacy_authentication() {
+    let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+    let script = [
+        ("900A0000010000", "970BB3C1A7B10AE691AF"),
+        (
+            "90AF0000106DA1BB23376EB8E1778C117EAAB6858D00",
+            "054150B8BCE0D1239100",
+        ),
+        // READ_DATA of 4 bytes of file 2, enciphered
+        ("90BD0000070200000004000000", "784613E81CBAF2669100"),
+    ];
+    run!(&script, |desfire| {
+        desfire
+            .authenticate(0, &Key::TDes2(key), &mut Counter(0xA0))
+            .unwrap();
+
+        let mut data = [0u8; 4];
+        let n = desfire
+            .read_data(2, 0, &mut data, CommMode::Enciphered)
+            .unwrap();
+        assert_eq!(data[..n], [0xCA, 0xFE, 0xBA, 0xBE]);
+    })
+}
+
+#[test]
+fn iso_authentication() {
+    let key: [u8; 24] = core::array::from_fn(|i| i as u8);
+    let script = [
+        ("901A0000010000", "65ABD6AE1AD5852047200CCEBF7A396391AF"),
+        (
+            "90AF0000205E8060413449A2CE7EAA6FF3A1F4ABF93F3757DC799ABAA5FF05DA5BDD0342AF00",
+            "15367A6C3F4B7F1BA89C06F62A6B7A859100",
+        ),
+    ];
+    run!(&script, |desfire| {
+        desfire
+            .authenticate(0, &Key::TDes3(key), &mut Counter(0xA0))
+            .unwrap();
+        assert!(desfire.is_authenticated());
+    })
+}
+
+#[test]
+fn picc_without_the_key() {
+    // the PICC does not return RndA
+    let mut script = AES;
+    script[1].1 = "000102030405060708090A0B0C0D0E0F9100";
+    run!(&script[..2], |desfire| {
+        let result = desfire.authenticate(0, &Key::Aes([0; 16]), &mut Counter(0xA0));
+        assert!(matches!(result, Err(Error::Integrity)));
+        assert!(!desfire.is_authenticated());
+    })
+}
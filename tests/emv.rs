@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use mfrc522::emv::Emv;
+use mfrc522::error::Error;
+use mfrc522::sim::{Activation, Frame, Iso14443a, Picc, Simulator};
+use mfrc522::Mfrc522;
+
+const UID: [u8; 4] = [0x08, 0x9C, 0x41, 0x7E];
+/// ATS of a payment card: FSCI 8, FWI 7
+const ATS: [u8; 5] = [0x05, 0x78, 0x80, 0x70, 0x02];
+
+const SELECT_PPSE: (&str, &str) = (
+    "00A404000E325041592E5359532E444446303100",
+    // two applications and an application template without AID
+    "6F4D840E325041592E5359532E4444463031A53BBF0C3861184F07A0000000041010500A4D4153544552434152\
+     4487010161124F07A0000000031010500456495341870102610850064E4F204149449000",
+);
+
+/// EMV card that answers the APDUs of a script
+struct Scripted {
+    iso: Iso14443a,
+    script: VecDeque<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Scripted {
+    fn new(script: &[(&str, &str)]) -> Self {
+        Scripted {
+            iso: Iso14443a::new(&UID, [0x04, 0x00], 0x20),
+            script: script.iter().map(|(c, r)| (hex(c), hex(r))).collect(),
+        }
+    }
+}
+
+impl Picc for Scripted {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        if let Activation::Handled(response) = self.iso.transceive(frame) {
+            return response;
+        }
+        match frame.data_with_crc_a()? {
+            // RATS
+            [0xE0, _] => Some(Frame::with_crc_a(&ATS)),
+            // I-block: the response is sent with the same block number
+            [pcb, apdu @ ..] if pcb & 0xE2 == 0x02 => {
+                let (command, response) = self.script.pop_front().expect("end of the script");
+                assert_eq!(apdu, command, "unexpected APDU");
+                let mut block = vec![*pcb];
+                block.extend_from_slice(&response);
+                Some(Frame::with_crc_a(&block))
+            }
+            _ => None,
+        }
+    }
+
+    fn field_off(&mut self) {
+        self.iso.field_off();
+    }
+}
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Activate the PICC and run the body with it as EMV card, the whole script has to be used
+macro_rules! run {
+    ($script:expr, |$emv:ident| $body:block) => {{
+        let mut mfrc522 = Mfrc522::new(Simulator::new(Scripted::new($script)))
+            .init()
+            .unwrap();
+        let atqa = mfrc522.reqa().unwrap();
+        let mut card = mfrc522.select(&atqa).unwrap();
+        let mut $emv = Emv::new(card.iso_dep().unwrap());
+        $body
+        drop($emv);
+        drop(card);
+
+        let (sim, _) = mfrc522.release();
+        assert!(sim.picc().script.is_empty());
+    }};
+}
+
+#[test]
+fn ppse() {
+    run!(&[SELECT_PPSE], |emv| {
+        let applications = emv.select_ppse().unwrap();
+        assert_eq!(applications.len(), 2);
+        assert_eq!(
+            applications[0].aid(),
+            [0xA0, 0x00, 0x00, 0x00, 0x04, 0x10, 0x10]
+        );
+        assert_eq!(applications[0].label(), b"MASTERCARD");
+        assert_eq!(applications[0].priority(), Some(1));
+        assert_eq!(
+            applications[1].aid(),
+            [0xA0, 0x00, 0x00, 0x00, 0x03, 0x10, 0x10]
+        );
+        assert_eq!(applications[1].label(), b"VISA");
+        assert_eq!(applications[1].priority(), Some(2));
+    })
+}
+
+#[test]
+fn ppse_with_too_long_aid() {
+    let select_ppse = (
+        SELECT_PPSE.0,
+        // the AID of the first application is 17 bytes long
+        "6F45840E325041592E5359532E4444463031A533BF0C30611D4F11A0A1A2A3A4A5A6A7A8A9AAABACADAEAFB0\
+         50084C4F4E4720414944610F4F07A00000000310105004564953419000",
+    );
+    run!(&[select_ppse], |emv| {
+        let applications = emv.select_ppse().unwrap();
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].label(), b"VISA");
+    })
+}
+
+#[test]
+fn ppse_not_found() {
+    run!(&[(SELECT_PPSE.0, "6A82")], |emv| {
+        assert!(matches!(emv.select_ppse(), Err(Error::Status(0x6A82))));
+    })
+}
+
+#[test]
+fn read_application() {
+    let script = [
+        (
+            "00A4040007A000000003101000",
+            // FCI with the PDOL 9F66 (TTQ, 4 bytes) and 9F02 (amount, 6 bytes)
+            "6F1A8407A0000000031010A50F5004564953419F38069F66049F02069000",
+        ),
+        (
+            "80A800000C830A3600400000000000000000",
+            // response format 2, with the AFL: SFI 1, record 1
+            "770A820220009404080101009000",
+        ),
+        // the length of the record is only known after a first attempt
+        ("00B2010C00", "6C12"),
+        ("00B2010C12", "70105A0847617390010100105F24033012319000"),
+    ];
+    run!(&script, |emv| {
+        let mut buffer = [0u8; 256];
+        let data = emv
+            .read_application(&[0xA0, 0x00, 0x00, 0x00, 0x03, 0x10, 0x10], &mut buffer)
+            .unwrap();
+        let pan = data.clone().find_tag(0x5A).unwrap();
+        assert_eq!(
+            pan.value(),
+            [0x47, 0x61, 0x73, 0x90, 0x01, 0x01, 0x00, 0x10]
+        );
+        let expiry = data.clone().find_tag(0x5F24).unwrap();
+        assert_eq!(expiry.value(), [0x30, 0x12, 0x31]);
+        assert_eq!(data.find_tag(0x50).unwrap().value(), b"VISA");
+    })
+}
+
+#[test]
+fn long_pdol() {
+    // the PDOL asks for 128 bytes of terminal data, the template length takes two bytes
+    let command = format!("80A8000083838180{}00", "00".repeat(128));
+    let script = [(command.as_str(), "80060000080101009000")];
+    run!(&script, |emv| {
+        let mut rx = [0u8; 32];
+        let pdol = [0xDF, 0x01, 0x80];
+        let n = emv.get_processing_options(Some(&pdol), &mut rx).unwrap();
+        assert_eq!(rx[..n], [0x80, 0x06, 0x00, 0x00, 0x08, 0x01, 0x01, 0x00]);
+    })
+}
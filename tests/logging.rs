@@ -0,0 +1,68 @@
+#![cfg(feature = "log")]
+
+use std::sync::Mutex;
+
+use log::{LevelFilter, Metadata, Record};
+use mfrc522::raw::RawFrame;
+use mfrc522::sim::{Classic, Simulator};
+use mfrc522::Mfrc522;
+
+/// Logger keeping the messages of the driver
+struct Capture(Mutex<Vec<String>>);
+
+impl log::Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target().starts_with("mfrc522") {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+
+#[test]
+fn frames_are_logged() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    // the UIDs differ in bit 1 of the first byte
+    let field = [
+        Classic::new_1k(&[0x11, 0x22, 0x33, 0x44]),
+        Classic::new_1k(&[0x13, 0x22, 0x33, 0x44]),
+    ];
+    let mut mfrc522 = Mfrc522::new(Simulator::new(field)).init().unwrap();
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    assert!(card.authenticate(4, &[0x00; 6]).is_err());
+    // commands that are not known are logged with their first byte
+    let frame = RawFrame::new(&[0x40]).with_last_bits(7);
+    let _ = mfrc522.transceive_raw(&frame, &mut [0; 1]);
+
+    let log = LOGGER.0.lock().unwrap();
+    let expected = [
+        "PCD -> PICC ReqA: 26 (7 bits)",
+        "PICC -> PCD: 04 00 (16 bits)",
+        "PCD -> PICC SelCl1: 93 20 (16 bits)",
+        "ErrorReg 08: CollErr",
+        "collision at bit 2 of cascade level 1",
+        "PCD -> PICC SelCl1: 93 22 03 (18 bits)",
+        "selected UID 13 22 33 44 SAK 08",
+        "authenticate block 4 of UID 13 22 33 44 with key A",
+        "authentication of block 4 failed: wrong key",
+        "PCD -> PICC 0x40: 40 (7 bits)",
+    ];
+    for line in expected {
+        assert!(
+            log.iter().any(|l| l == line),
+            "{:?} not in {:#?}",
+            line,
+            log
+        );
+    }
+}
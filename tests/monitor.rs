@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mfrc522::monitor::{CardEvent, CardMonitor, DEFAULT_DEBOUNCE};
+use mfrc522::sim::{Frame, Picc, Simulator, Ultralight};
+use mfrc522::Mfrc522;
+
+const UID: [u8; 7] = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+const OTHER_UID: [u8; 7] = [0x04, 0x93, 0x0E, 0x61, 0x22, 0x5D, 0x81];
+
+/// The field of the reader, a PICC can be put in and taken out while the MFRC522 is in use
+#[derive(Clone, Default)]
+struct Field(Rc<RefCell<Option<Ultralight>>>);
+
+impl Field {
+    fn put(&self, uid: [u8; 7]) {
+        *self.0.borrow_mut() = Some(Ultralight::new(uid));
+    }
+
+    fn take(&self) -> Option<Ultralight> {
+        self.0.borrow_mut().take()
+    }
+}
+
+impl Picc for Field {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        self.0.borrow_mut().transceive(frame)
+    }
+
+    fn field_off(&mut self) {
+        self.0.borrow_mut().field_off();
+    }
+}
+
+macro_rules! mfrc522 {
+    ($field:expr) => {
+        Mfrc522::new(Simulator::new($field.clone())).init().unwrap()
+    };
+}
+
+fn arrived(event: Option<CardEvent>) -> Vec<u8> {
+    match event {
+        Some(CardEvent::Arrived(uid)) => uid.as_bytes().to_vec(),
+        event => panic!("expected an arrival, got {:?}", event),
+    }
+}
+
+fn removed(event: Option<CardEvent>) -> Vec<u8> {
+    match event {
+        Some(CardEvent::Removed(uid)) => uid.as_bytes().to_vec(),
+        event => panic!("expected a removal, got {:?}", event),
+    }
+}
+
+#[test]
+fn arrival_and_removal() {
+    let field = Field::default();
+    let mut mfrc522 = mfrc522!(field);
+    let mut monitor = CardMonitor::new();
+    assert_eq!(monitor.poll(&mut mfrc522).unwrap(), None);
+
+    field.put(UID);
+    assert_eq!(arrived(monitor.poll(&mut mfrc522).unwrap()), UID);
+    assert_eq!(monitor.current().unwrap().as_bytes(), &UID);
+    // the PICC is halted after each poll and woken up again by the next one
+    for _ in 0..3 {
+        assert_eq!(
+            monitor.poll(&mut mfrc522).unwrap(),
+            Some(CardEvent::StillPresent)
+        );
+    }
+
+    field.take();
+    for _ in 1..DEFAULT_DEBOUNCE {
+        assert_eq!(
+            monitor.poll(&mut mfrc522).unwrap(),
+            Some(CardEvent::StillPresent)
+        );
+    }
+    assert_eq!(removed(monitor.poll(&mut mfrc522).unwrap()), UID);
+    assert!(monitor.current().is_none());
+    assert_eq!(monitor.poll(&mut mfrc522).unwrap(), None);
+}
+
+#[test]
+fn short_interruption_is_debounced() {
+    let field = Field::default();
+    let mut mfrc522 = mfrc522!(field);
+    let mut monitor = CardMonitor::new();
+    field.put(UID);
+    arrived(monitor.poll(&mut mfrc522).unwrap());
+
+    // the misses have to be consecutive
+    for _ in 0..2 {
+        let picc = field.take();
+        for _ in 1..DEFAULT_DEBOUNCE {
+            assert_eq!(
+                monitor.poll(&mut mfrc522).unwrap(),
+                Some(CardEvent::StillPresent)
+            );
+        }
+        *field.0.borrow_mut() = picc;
+        assert_eq!(
+            monitor.poll(&mut mfrc522).unwrap(),
+            Some(CardEvent::StillPresent)
+        );
+    }
+    assert_eq!(monitor.current().unwrap().as_bytes(), &UID);
+}
+
+#[test]
+fn another_picc_takes_its_place() {
+    let field = Field::default();
+    let mut mfrc522 = mfrc522!(field);
+    let mut monitor = CardMonitor::new();
+    field.put(UID);
+    arrived(monitor.poll(&mut mfrc522).unwrap());
+
+    // the removal is reported without debouncing, the arrival on the next poll
+    field.put(OTHER_UID);
+    assert_eq!(removed(monitor.poll(&mut mfrc522).unwrap()), UID);
+    assert_eq!(arrived(monitor.poll(&mut mfrc522).unwrap()), OTHER_UID);
+}
+
+#[test]
+fn removal_without_debouncing() {
+    let field = Field::default();
+    let mut mfrc522 = mfrc522!(field);
+    // a debounce of 0 is the same as 1
+    for debounce in [0, 1] {
+        let mut monitor = CardMonitor::new().with_debounce(debounce);
+        field.put(UID);
+        arrived(monitor.poll(&mut mfrc522).unwrap());
+        field.take();
+        assert_eq!(removed(monitor.poll(&mut mfrc522).unwrap()), UID);
+    }
+}
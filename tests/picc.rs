@@ -0,0 +1,279 @@
+use mfrc522::error::{Error, Nak};
+use mfrc522::identify::CardType;
+use mfrc522::sim::{Classic, Crypto1, Frame, Simulator, State, Ultralight};
+use mfrc522::Mfrc522;
+
+const DEFAULT_KEY: [u8; 6] = [0xFF; 6];
+const UL_UID: [u8; 7] = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+
+#[test]
+fn crypto1_is_symmetric() {
+    let frame = Frame::with_crc_a(&[0x30, 0x04]);
+    let mut encrypt = Crypto1::new(&[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5]);
+    let mut decrypt = encrypt;
+    let encrypted = encrypt.crypt(&frame);
+    assert_ne!(encrypted, frame);
+    assert_eq!(decrypt.crypt(&encrypted), frame);
+}
+
+#[test]
+fn classic_1k_read_write() {
+    let uid = [0xDE, 0xAD, 0xBE, 0xEF];
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Classic::new_1k(&uid)))
+        .init()
+        .unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &uid);
+    assert_eq!(card.identify().unwrap(), CardType::MifareClassic1k);
+
+    let mut sector = card.authenticate(4, &DEFAULT_KEY).unwrap();
+    let data = [0x42; 16];
+    sector.write(5, data).unwrap();
+    assert_eq!(sector.read(5).unwrap(), data);
+
+    // key A can't be read, the access bits and key B can in the transport configuration
+    let trailer = sector.read(7).unwrap();
+    assert_eq!(trailer[..6], [0; 6]);
+    assert_eq!(
+        trailer[6..],
+        [0xFF, 0x07, 0x80, 0x69, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+    );
+
+    // nested authentication of another sector
+    let mut sector = sector.authenticate(63, &DEFAULT_KEY).unwrap();
+    assert!(matches!(sector.read(5), Err(Error::NotAuthenticated)));
+    assert_eq!(sector.read(60).unwrap(), [0; 16]);
+    sector.finish().unwrap();
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.picc().memory()[5 * 16..6 * 16], data);
+    assert_eq!(sim.picc().state(), State::Halt);
+    assert!(!sim.picc().is_authenticated());
+}
+
+#[test]
+fn classic_wrong_key() {
+    let mut classic = Classic::new_1k(&[0x01, 0x02, 0x03, 0x04]);
+    classic.memory_mut()[7 * 16..7 * 16 + 6].copy_from_slice(&[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5]);
+    let mut mfrc522 = Mfrc522::new(Simulator::new(classic)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    let Err(error) = card.authenticate(4, &DEFAULT_KEY) else {
+        panic!("authenticated with the wrong key");
+    };
+    assert!(matches!(error, Error::AuthFailed(_)));
+    assert_eq!(
+        error.map(|_| ()).to_string(),
+        "AUTH block 4: authentication failed, wrong key (ErrorReg 00, ComIrqReg 21)"
+    );
+
+    let atqa = mfrc522.wupa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    let mut sector = card
+        .authenticate(4, &[0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5])
+        .unwrap();
+    assert_eq!(sector.read(4).unwrap(), [0; 16]);
+}
+
+#[test]
+fn classic_access_conditions() {
+    let mut classic = Classic::new_1k(&[0x01, 0x02, 0x03, 0x04]);
+    // block 4: read and write with key B only (C1 C2 C3 = 011), trailer: transport configuration
+    classic.memory_mut()[7 * 16 + 6..7 * 16 + 9].copy_from_slice(&[0xEF, 0x06, 0x91]);
+    classic.memory_mut()[4 * 16] = 0x55;
+    let mut mfrc522 = Mfrc522::new(Simulator::new(classic)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut sector = mfrc522
+        .select(&atqa)
+        .unwrap()
+        .authenticate(4, &DEFAULT_KEY)
+        .unwrap();
+    assert_eq!(sector.read(5).unwrap(), [0; 16]);
+    let Err(error) = sector.read(4) else {
+        panic!("read with key A");
+    };
+    assert!(matches!(error, Error::Nak(Nak::InvalidOperation, _)));
+    assert_eq!(
+        error.map(|_| ()).to_string(),
+        "READ block 4: NAK from the PICC: invalid operation"
+    );
+    drop(sector);
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.picc().memory()[4 * 16], 0x55);
+}
+
+#[test]
+fn classic_4k_double_size_uid() {
+    let uid = [0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Classic::new_4k(&uid)))
+        .init()
+        .unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &uid);
+    assert_eq!(card.identify().unwrap(), CardType::MifareClassic4k);
+
+    // sector 32, the first one with 16 blocks
+    let mut sector = card.authenticate(140, &DEFAULT_KEY).unwrap();
+    assert_eq!(sector.blocks(), 128..=143);
+    sector.write(142, [0x17; 16]).unwrap();
+    assert_eq!(sector.read(142).unwrap(), [0x17; 16]);
+}
+
+#[test]
+fn classic_4k_last_sector() {
+    let uid = [0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Classic::new_4k(&uid)))
+        .init()
+        .unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+
+    // sector 39 ends at block 255
+    let mut sector = card.authenticate(240, &DEFAULT_KEY).unwrap();
+    assert_eq!(sector.blocks(), 240..=255);
+    assert_eq!(sector.trailer_block(), 255);
+    sector.write(240, [0x17; 16]).unwrap();
+    assert_eq!(sector.read(240).unwrap(), [0x17; 16]);
+
+    let trailer = [
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x80, 0x69, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        0xFF,
+    ];
+    sector.write(255, trailer).unwrap();
+    assert_eq!(sector.read(255).unwrap()[6..], trailer[6..]);
+    assert!(matches!(sector.read(239), Err(Error::NotAuthenticated)));
+    sector.finish().unwrap();
+}
+
+#[test]
+fn anticollision_of_two_picc() {
+    // the UIDs differ in bit 1 of the first byte
+    let uids = [[0x11, 0x22, 0x33, 0x44], [0x13, 0x22, 0x33, 0x44]];
+    let field = [Classic::new_1k(&uids[0]), Classic::new_1k(&uids[1])];
+    let mut mfrc522 = Mfrc522::new(Simulator::new(field)).init().unwrap();
+
+    // the PICC with a 1 at the collision is selected first
+    for uid in [uids[1], uids[0]] {
+        let atqa = mfrc522.reqa().unwrap();
+        let card = mfrc522.select(&atqa).unwrap();
+        assert_eq!(card.uid().as_bytes(), &uid);
+        card.finish().unwrap();
+    }
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+}
+
+#[test]
+fn classic_and_ntag_in_the_field() {
+    // both have a 7 byte UID, they differ in bit 0 of the second byte
+    let classic_uid = [0x04, 0x9C, 0x01, 0x02, 0x03, 0x04, 0x05];
+    let field = (
+        Classic::new_1k(&classic_uid),
+        Ultralight::new_ntag215(UL_UID),
+    );
+    let mut mfrc522 = Mfrc522::new(Simulator::new(field)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &UL_UID);
+    assert_eq!(card.identify().unwrap(), CardType::Ntag215);
+    card.finish().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &classic_uid);
+    let mut sector = card.authenticate(0, &DEFAULT_KEY).unwrap();
+    assert_eq!(sector.read(0).unwrap()[..7], classic_uid);
+}
+
+#[test]
+fn ntag213_memory() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new_ntag213(UL_UID)))
+        .init()
+        .unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.identify().unwrap(), CardType::Ntag213);
+
+    // capability container and empty NDEF message
+    let data = card.ul_read(3).unwrap();
+    assert_eq!(data[..8], [0xE1, 0x10, 0x12, 0x00, 0x03, 0x00, 0xFE, 0x00]);
+
+    card.ul_write(4, [0x03, 0x03, 0xD0, 0x00]).unwrap();
+    card.ul_write(5, [0x00, 0xFE, 0x00, 0x00]).unwrap();
+    let mut memory = [0u8; 180];
+    assert_eq!(card.ul_fast_read(0, 44, &mut memory).unwrap(), 180);
+    assert_eq!(memory[..3], UL_UID[..3]);
+    assert_eq!(memory[16..22], [0x03, 0x03, 0xD0, 0x00, 0x00, 0xFE]);
+    // the password reads as zero
+    assert_eq!(memory[172..176], [0; 4]);
+}
+
+#[test]
+fn ntag_fast_read_range() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new_ntag213(UL_UID)))
+        .init()
+        .unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    let mut memory = [0u8; 8];
+    assert!(matches!(
+        card.ul_fast_read(4, 3, &mut memory),
+        Err(Error::NoRoom)
+    ));
+    assert!(matches!(
+        card.ul_fast_read(0, 2, &mut memory),
+        Err(Error::NoRoom)
+    ));
+
+    // nothing was sent, the PICC is still selected
+    assert_eq!(card.ul_fast_read(3, 3, &mut memory).unwrap(), 4);
+    assert_eq!(memory[..4], [0xE1, 0x10, 0x12, 0x00]);
+}
+
+#[test]
+fn ultralight_lock_bits() {
+    let mut ultralight = Ultralight::new(UL_UID);
+    // lock page 4
+    ultralight.memory_mut()[10] = 1 << 4;
+    let mut mfrc522 = Mfrc522::new(Simulator::new(ultralight)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.identify().unwrap(), CardType::MifareUltralight);
+    card.ul_write(5, [1, 2, 3, 4]).unwrap();
+    let Err(error) = card.ul_write(4, [1, 2, 3, 4]) else {
+        panic!("wrote a locked page");
+    };
+    assert!(matches!(error, Error::Nak(Nak::InvalidOperation, _)));
+    assert_eq!(
+        error.map(|_| ()).to_string(),
+        "WRITE block 4: NAK from the PICC: invalid operation"
+    );
+    drop(card);
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.picc().memory()[16..24], [0, 0, 0, 0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn picc_taken_out_of_the_field() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Some(Ultralight::new(UL_UID))))
+        .init()
+        .unwrap();
+    assert!(mfrc522.reqa().is_ok());
+
+    let (mut sim, _) = mfrc522.release();
+    *sim.picc_mut() = None;
+    let mut mfrc522 = Mfrc522::new(sim).init().unwrap();
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+}
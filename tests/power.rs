@@ -0,0 +1,179 @@
+use std::convert::Infallible;
+
+use embedded_hal::blocking::spi;
+use heapless::Deque;
+use mfrc522::error::{Error, Operation};
+use mfrc522::power::LowPowerDetector;
+use mfrc522::rf::{RfConfig, TxDriver, TxPin};
+use mfrc522::sim::{Simulator, Ultralight};
+use mfrc522::trace::{Access, Recorder, Transaction};
+use mfrc522::{Mfrc522, Register};
+
+const UID: [u8; 7] = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+/// CommandReg: soft power-down mode
+const POWER_DOWN: u8 = 1 << 4;
+/// TxControlReg: Tx1RFEn and Tx2RFEn
+const TX_RF_EN: u8 = 0b11;
+
+#[test]
+fn no_communication_in_power_down() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new(UID)))
+        .init()
+        .unwrap();
+    assert!(!mfrc522.is_powered_down().unwrap());
+
+    mfrc522.power_down().unwrap();
+    assert!(mfrc522.is_powered_down().unwrap());
+    // the antenna is left on
+    assert!(mfrc522.is_antenna_on().unwrap());
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+
+    mfrc522.wake_up().unwrap();
+    assert!(!mfrc522.is_powered_down().unwrap());
+    assert!(mfrc522.reqa().is_ok());
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.register(Register::CommandReg as u8) & POWER_DOWN, 0);
+}
+
+#[test]
+fn wake_up_waits_for_the_oscillator() {
+    let recorder = Recorder::new(Simulator::new(()), Deque::<Transaction, 256>::new());
+    let mut mfrc522 = Mfrc522::new(recorder).init().unwrap();
+    mfrc522.power_down().unwrap();
+    mfrc522.wake_up().unwrap();
+
+    let (recorder, _) = mfrc522.release();
+    let (_, trace) = recorder.release();
+    let command: Vec<_> = trace
+        .iter()
+        .filter(|t| t.register() == Some(Register::CommandReg))
+        .collect();
+    let last_write = command
+        .iter()
+        .rposition(|t| t.access() == Access::Write)
+        .unwrap();
+    assert_eq!(command[last_write].values()[0] & POWER_DOWN, 0);
+
+    // PowerDown is polled until it reads 0
+    let polled: Vec<bool> = command[last_write + 1..]
+        .iter()
+        .map(|t| t.values()[0] & POWER_DOWN != 0)
+        .collect();
+    assert_eq!(polled, [true, true, true, false]);
+}
+
+#[test]
+fn ping_without_picc() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    let detector = LowPowerDetector::new(250);
+    assert_eq!(detector.ping(&mut mfrc522).unwrap(), None);
+    assert!(mfrc522.is_powered_down().unwrap());
+    assert!(!mfrc522.is_antenna_on().unwrap());
+
+    // the next ping wakes the MFRC522 up again
+    assert_eq!(detector.ping(&mut mfrc522).unwrap(), None);
+}
+
+#[test]
+fn ping_with_picc() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new(UID)))
+        .init()
+        .unwrap();
+    mfrc522.antenna_off().unwrap();
+    mfrc522.power_down().unwrap();
+
+    let detector = LowPowerDetector::new(250);
+    let atqa = detector.ping(&mut mfrc522).unwrap().unwrap();
+    assert!(!mfrc522.is_powered_down().unwrap());
+    assert!(mfrc522.is_antenna_on().unwrap());
+
+    let card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &UID);
+}
+
+#[test]
+fn antenna_on_keeps_disabled_drivers_off() {
+    let config = RfConfig {
+        tx2: TxDriver {
+            enabled: false,
+            ..RfConfig::default().tx2
+        },
+        ..RfConfig::default()
+    };
+    let mut mfrc522 = Mfrc522::new(Simulator::new(()))
+        .with_rf_config(config)
+        .init()
+        .unwrap();
+    mfrc522.antenna_off().unwrap();
+    assert!(!mfrc522.is_antenna_on().unwrap());
+    mfrc522.antenna_on().unwrap();
+    assert!(mfrc522.is_antenna_on().unwrap());
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.register(Register::TxControlReg as u8) & TX_RF_EN, 0b01);
+}
+
+#[test]
+fn antenna_stays_off_when_reconfigured() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    mfrc522.antenna_off().unwrap();
+    mfrc522.set_rf_config(RfConfig::default()).unwrap();
+    mfrc522
+        .set_tx_driver(TxPin::Tx1, RfConfig::default().tx1)
+        .unwrap();
+    assert!(!mfrc522.is_antenna_on().unwrap());
+
+    mfrc522.antenna_on().unwrap();
+    let (sim, _) = mfrc522.release();
+    // both drivers are switched on again, as configured
+    assert_eq!(sim.register(Register::TxControlReg as u8), 0x83);
+}
+
+/// Simulator of which the oscillator does not start again after soft power-down:
+/// CommandReg PowerDown keeps reading 1
+struct NoOscillator {
+    sim: Simulator<()>,
+    powered_down: bool,
+}
+
+impl spi::Transfer<u8> for NoOscillator {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let command = words[0] == Register::CommandReg.read_address();
+        self.sim.transfer(words)?;
+        if command && self.powered_down {
+            words[1] |= POWER_DOWN;
+        }
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for NoOscillator {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        if words[0] == Register::CommandReg.write_address() && words[1] & POWER_DOWN != 0 {
+            self.powered_down = true;
+        }
+        self.sim.write(words)
+    }
+}
+
+#[test]
+fn wake_up_timeout() {
+    let spi = NoOscillator {
+        sim: Simulator::new(()),
+        powered_down: false,
+    };
+    let mut mfrc522 = Mfrc522::new(spi).init().unwrap();
+    mfrc522.power_down().unwrap();
+
+    let error = mfrc522.wake_up().unwrap_err();
+    assert!(matches!(error, Error::Timeout(c) if c.operation == Some(Operation::WakeUp)));
+    assert_eq!(
+        error.map(|_| ()).to_string(),
+        "wake-up: the oscillator did not start"
+    );
+}
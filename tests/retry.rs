@@ -0,0 +1,110 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use mfrc522::error::{Context, Error};
+use mfrc522::retry::{RetryOn, RetryPolicy};
+use mfrc522::sim::{Classic, Frame, Picc, Simulator};
+use mfrc522::Mfrc522;
+
+const UID: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+const DEFAULT_KEY: [u8; 6] = [0xFF; 6];
+
+/// A PICC at the edge of the field: its answers get lost while `lost` is not 0
+struct EdgeOfField<P> {
+    picc: P,
+    lost: Rc<Cell<u8>>,
+}
+
+impl<P: Picc> Picc for EdgeOfField<P> {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        let answer = self.picc.transceive(frame);
+        match self.lost.get() {
+            0 => answer,
+            lost => {
+                self.lost.set(lost - 1);
+                None
+            }
+        }
+    }
+
+    fn field_off(&mut self) {
+        self.picc.field_off();
+    }
+}
+
+fn classic() -> (Simulator<EdgeOfField<Classic>>, Rc<Cell<u8>>) {
+    let mut classic = Classic::new_1k(&UID);
+    classic.memory_mut()[4 * 16..5 * 16].copy_from_slice(&[0x42; 16]);
+    let lost = Rc::new(Cell::new(0));
+    let picc = EdgeOfField {
+        picc: classic,
+        lost: lost.clone(),
+    };
+    (Simulator::new(picc), lost)
+}
+
+#[test]
+fn classic_read_is_retried() {
+    let (sim, lost) = classic();
+    let policy = RetryPolicy {
+        attempts: 3,
+        ..RetryPolicy::default()
+    };
+    let mut mfrc522 = Mfrc522::new(sim).with_retry_policy(policy).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    let mut sector = card.authenticate(4, &DEFAULT_KEY).unwrap();
+
+    // the PICC is selected and authenticated again, the session continues afterwards
+    lost.set(1);
+    assert_eq!(sector.read(4).unwrap(), [0x42; 16]);
+    sector.write(5, [0x17; 16]).unwrap();
+    assert_eq!(sector.read(5).unwrap(), [0x17; 16]);
+    sector.finish().unwrap();
+}
+
+#[test]
+fn no_retry_by_default() {
+    let (sim, lost) = classic();
+    let mut mfrc522 = Mfrc522::new(sim).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    let mut sector = card.authenticate(4, &DEFAULT_KEY).unwrap();
+
+    lost.set(1);
+    assert!(matches!(sector.read(4), Err(Error::Timeout(_))));
+}
+
+#[test]
+fn retries_are_limited() {
+    static BACKOFF: AtomicU8 = AtomicU8::new(0);
+
+    let (sim, lost) = classic();
+    let policy = RetryPolicy {
+        attempts: 3,
+        backoff: Some(|retry| {
+            assert_eq!(BACKOFF.fetch_add(1, Ordering::Relaxed) + 1, retry);
+        }),
+        ..RetryPolicy::default()
+    };
+    let mut mfrc522 = Mfrc522::new(sim).with_retry_policy(policy).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let card = mfrc522.select(&atqa).unwrap();
+    let mut sector = card.authenticate(4, &DEFAULT_KEY).unwrap();
+
+    // the PICC left the field
+    lost.set(u8::MAX);
+    assert!(matches!(sector.read(4), Err(Error::Timeout(_))));
+    assert_eq!(BACKOFF.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn collisions_are_not_retried_by_default() {
+    let retry_on = RetryOn::default();
+    assert!(!retry_on.matches(&Error::<()>::Collision(Context::default())));
+    assert!(retry_on.matches(&Error::<()>::Crc(Context::default())));
+}
@@ -0,0 +1,191 @@
+use std::convert::Infallible;
+
+use embedded_hal::blocking::spi;
+use mfrc522::rf::{BitRate, RfConfig, RxGain, TxDriver, TxPin};
+use mfrc522::sim::{Picc, Simulator, Ultralight};
+use mfrc522::{Mfrc522, Register};
+
+const UID: [u8; 7] = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+
+const RF_REGISTERS: [Register; 5] = [
+    Register::RFCfgReg,
+    Register::GsNReg,
+    Register::CWGsPReg,
+    Register::ModGsPReg,
+    Register::TxControlReg,
+];
+
+const MODE_REGISTERS: [Register; 3] = [
+    Register::TxModeReg,
+    Register::RxModeReg,
+    Register::ModWidthReg,
+];
+
+fn registers<P: Picc>(sim: &Simulator<P>, registers: &[Register]) -> Vec<u8> {
+    registers.iter().map(|&r| sim.register(r as u8)).collect()
+}
+
+#[test]
+fn default_rf_config() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    assert_eq!(mfrc522.rf_config().unwrap(), RfConfig::default());
+
+    // the reset values, with the antenna switched on
+    let (sim, _) = mfrc522.release();
+    assert_eq!(
+        registers(&sim, &RF_REGISTERS),
+        [0x48, 0x88, 0x20, 0x20, 0x83]
+    );
+}
+
+#[test]
+fn rf_config_is_applied_by_init() {
+    let config = RfConfig {
+        rx_gain: RxGain::Db48,
+        cw_gs_n: 0xF,
+        mod_gs_n: 0x4,
+        cw_gs_p: 0x3F,
+        mod_gs_p: 0x11,
+        tx2: TxDriver {
+            enabled: true,
+            invert_on: false,
+            invert_off: true,
+        },
+        ..RfConfig::default()
+    };
+    let mut mfrc522 = Mfrc522::new(Simulator::new(()))
+        .with_rf_config(config)
+        .init()
+        .unwrap();
+    assert_eq!(mfrc522.rf_config().unwrap(), config);
+
+    // the reserved bits of RFCfgReg keep their reset value
+    let (sim, _) = mfrc522.release();
+    assert_eq!(
+        registers(&sim, &RF_REGISTERS),
+        [0x78, 0xF4, 0x3F, 0x11, 0x23]
+    );
+}
+
+#[test]
+fn rf_settings() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    mfrc522.set_rx_gain(RxGain::Db18).unwrap();
+    assert_eq!(mfrc522.rx_gain().unwrap(), RxGain::Db18);
+    // the conductances are limited to the width of their register fields
+    mfrc522.set_gs_n(0x1F, 0x2).unwrap();
+    assert_eq!(mfrc522.gs_n().unwrap(), (0xF, 0x2));
+    mfrc522.set_gs_p(0x7F, 0x05).unwrap();
+    assert_eq!(mfrc522.gs_p().unwrap(), (0x3F, 0x05));
+    let tx1 = TxDriver {
+        enabled: false,
+        invert_on: true,
+        invert_off: false,
+    };
+    mfrc522.set_tx_driver(TxPin::Tx1, tx1).unwrap();
+    assert_eq!(mfrc522.tx_driver(TxPin::Tx1).unwrap(), tx1);
+    // TX2 still delivers the energy carrier
+    assert!(mfrc522.is_antenna_on().unwrap());
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(
+        registers(&sim, &RF_REGISTERS),
+        [0x08, 0xF2, 0x3F, 0x05, 0xC2]
+    );
+}
+
+/// Simulator reporting the reserved TxSpeed value 0b100
+struct ReservedTxSpeed(Simulator<()>);
+
+impl spi::Transfer<u8> for ReservedTxSpeed {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let tx_mode = words[0] == Register::TxModeReg.read_address();
+        self.0.transfer(words)?;
+        if tx_mode {
+            words[1] |= 0b100 << 4;
+        }
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for ReservedTxSpeed {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        self.0.write(words)
+    }
+}
+
+#[test]
+fn bit_rate_registers() {
+    for (bit_rate, tx_mode, mod_width) in [
+        (BitRate::Kbps106, 0x00, 0x26),
+        (BitRate::Kbps212, 0x10, 0x13),
+        (BitRate::Kbps424, 0x20, 0x09),
+        (BitRate::Kbps848, 0x30, 0x04),
+    ] {
+        let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+        mfrc522.set_bit_rate(bit_rate, BitRate::Kbps106).unwrap();
+        assert_eq!(
+            mfrc522.bit_rate().unwrap(),
+            (Some(bit_rate), Some(BitRate::Kbps106))
+        );
+
+        let (sim, _) = mfrc522.release();
+        assert_eq!(
+            registers(&sim, &MODE_REGISTERS),
+            [tx_mode, 0x00, mod_width],
+            "{:?}",
+            bit_rate
+        );
+    }
+}
+
+#[test]
+fn modulation_width_follows_the_transmit_bit_rate() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    mfrc522
+        .set_bit_rate(BitRate::Kbps106, BitRate::Kbps848)
+        .unwrap();
+    assert_eq!(
+        mfrc522.bit_rate().unwrap(),
+        (Some(BitRate::Kbps106), Some(BitRate::Kbps848))
+    );
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(registers(&sim, &MODE_REGISTERS), [0x00, 0x30, 0x26]);
+}
+
+#[test]
+fn polling_falls_back_to_106_kbps() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Ultralight::new(UID)))
+        .init()
+        .unwrap();
+    let both = (Some(BitRate::Kbps106), Some(BitRate::Kbps106));
+
+    mfrc522
+        .set_bit_rate(BitRate::Kbps848, BitRate::Kbps848)
+        .unwrap();
+    let atqa = mfrc522.reqa().unwrap();
+    assert_eq!(mfrc522.bit_rate().unwrap(), both);
+    mfrc522.select(&atqa).unwrap().finish().unwrap();
+
+    mfrc522
+        .set_bit_rate(BitRate::Kbps424, BitRate::Kbps212)
+        .unwrap();
+    assert!(mfrc522.wupa().is_ok());
+    assert_eq!(mfrc522.bit_rate().unwrap(), both);
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(registers(&sim, &MODE_REGISTERS), [0x00, 0x00, 0x26]);
+}
+
+#[test]
+fn reserved_bit_rate() {
+    let mut mfrc522 = Mfrc522::new(ReservedTxSpeed(Simulator::new(())))
+        .init()
+        .unwrap();
+    assert_eq!(mfrc522.bit_rate().unwrap(), (None, Some(BitRate::Kbps106)));
+}
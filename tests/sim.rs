@@ -0,0 +1,383 @@
+use std::convert::Infallible;
+
+use embedded_hal::blocking::spi;
+use mfrc522::chip::Chip;
+use mfrc522::error::{Error, Operation};
+use mfrc522::raw::RawFrame;
+use mfrc522::sim::{Frame, Picc, Simulator};
+use mfrc522::{CrcMode, Mfrc522, Register, Uid};
+
+const UID: [u8; 7] = [0x04, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Ready,
+    Active,
+    Halt,
+}
+
+/// MIFARE Ultralight like PICC with a 7 byte UID and 64 pages of memory
+struct TestPicc {
+    state: State,
+    memory: [u8; 256],
+    /// Corrupt the CRC_A of READ responses
+    bad_crc: bool,
+}
+
+impl TestPicc {
+    fn new() -> Self {
+        let mut memory = [0u8; 256];
+        for (i, b) in memory.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        TestPicc {
+            state: State::Idle,
+            memory,
+            bad_crc: false,
+        }
+    }
+
+    /// UID bytes of a cascade level, including the cascade tag, followed by the BCC
+    fn cascade_level(level: u8) -> [u8; 5] {
+        let mut cl = [0u8; 5];
+        match level {
+            0x93 => cl[..4].copy_from_slice(&[0x88, UID[0], UID[1], UID[2]]),
+            _ => cl[..4].copy_from_slice(&UID[3..7]),
+        }
+        cl[4] = cl[..4].iter().fold(0, |bcc, b| bcc ^ b);
+        cl
+    }
+}
+
+impl Picc for TestPicc {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        let bytes = frame.as_bytes();
+        if frame.last_bits() == 7 {
+            return match (bytes[0], self.state) {
+                (0x26, State::Idle) | (0x52, State::Idle | State::Halt) => {
+                    self.state = State::Ready;
+                    Some(Frame::new(&[0x44, 0x00]))
+                }
+                _ => None,
+            };
+        }
+
+        if self.state == State::Ready && matches!(bytes[0], 0x93 | 0x95) {
+            let cl = Self::cascade_level(bytes[0]);
+            if bytes.len() == 2 && bytes[1] == 0x20 {
+                return Some(Frame::new(&cl));
+            }
+            let data = frame.data_with_crc_a()?;
+            if data.len() == 7 && data[1] == 0x70 && data[2..] == cl {
+                return Some(match bytes[0] {
+                    0x93 => Frame::with_crc_a(&[0x04]),
+                    _ => {
+                        self.state = State::Active;
+                        Frame::with_crc_a(&[0x00])
+                    }
+                });
+            }
+            return None;
+        }
+
+        if self.state != State::Active {
+            return None;
+        }
+        let data = frame.data_with_crc_a()?;
+        match data {
+            [0x50, 0x00] => {
+                self.state = State::Halt;
+                None
+            }
+            [0x30, page] => {
+                let start = *page as usize * 4;
+                let mut response = Frame::with_crc_a(&self.memory[start..start + 16]);
+                if self.bad_crc {
+                    let mut bytes = [0u8; 18];
+                    bytes.copy_from_slice(response.as_bytes());
+                    bytes[17] ^= 0xFF;
+                    response = Frame::new(&bytes);
+                }
+                Some(response)
+            }
+            [0x3A, start, end] => {
+                let memory = &self.memory[*start as usize * 4..(*end as usize + 1) * 4];
+                Some(Frame::with_crc_a(memory))
+            }
+            [0xA2, page, value @ ..] if value.len() == 4 => {
+                let start = *page as usize * 4;
+                self.memory[start..start + 4].copy_from_slice(value);
+                Some(Frame::with_bits(&[0x0A], 4))
+            }
+            _ => Some(Frame::with_bits(&[0x00], 4)),
+        }
+    }
+
+    fn field_off(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+macro_rules! mfrc522 {
+    ($crc_mode:expr) => {
+        Mfrc522::new(Simulator::new(TestPicc::new()))
+            .with_crc_mode($crc_mode)
+            .init()
+            .unwrap()
+    };
+}
+
+#[test]
+fn init_and_version() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(()).with_version(0x91))
+        .init()
+        .unwrap();
+    assert_eq!(mfrc522.version().unwrap(), 0x91);
+}
+
+#[test]
+fn chip_info_and_self_test() {
+    for (version, chip) in [
+        (0x91, Chip::Mfrc522V1),
+        (0x92, Chip::Mfrc522V2),
+        (0x88, Chip::Fm17522),
+    ] {
+        let mut mfrc522 = Mfrc522::new(Simulator::new(()).with_version(version))
+            .init()
+            .unwrap();
+        assert_eq!(mfrc522.chip_info().unwrap(), chip);
+        assert!(mfrc522.self_test().unwrap(), "{:?}", chip);
+    }
+
+    let mut mfrc522 = Mfrc522::new(Simulator::new(()).with_version(0xB2))
+        .init()
+        .unwrap();
+    assert_eq!(mfrc522.chip_info().unwrap(), Chip::Counterfeit(0xB2));
+    assert!(matches!(mfrc522.self_test(), Err(Error::Unsupported)));
+}
+
+#[test]
+fn configuration_is_restored_after_self_test() {
+    let mut mfrc522 = mfrc522!(CrcMode::Hardware);
+    assert!(mfrc522.self_test().unwrap());
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.ul_read(4).unwrap()[0], 16);
+    drop(card);
+
+    let (sim, _) = mfrc522.release();
+    assert_eq!(sim.register(Register::AutoTestReg as u8), 0x40);
+    assert_eq!(sim.register(Register::WaterLevelReg as u8), 32);
+}
+
+#[test]
+fn internal_buffer_and_random_id() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    let mut data = [0u8; 25];
+    for (i, b) in data.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    mfrc522.mem_store(&data).unwrap();
+    assert_eq!(mfrc522.mem_load().unwrap(), data);
+
+    // the random number replaces the first 10 bytes only
+    let random = mfrc522.random_id().unwrap();
+    assert_ne!(random, mfrc522.random_id().unwrap());
+    assert_eq!(mfrc522.mem_load().unwrap()[10..], data[10..]);
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn rng() {
+    use rand_core::RngCore;
+
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    let mut nonce = [0u8; 16];
+    mfrc522.rng().fill_bytes(&mut nonce);
+    assert_ne!(nonce[..8], nonce[8..]);
+    assert_ne!(mfrc522.rng().next_u64(), mfrc522.rng().next_u64());
+}
+
+#[test]
+fn empty_field_times_out() {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(())).init().unwrap();
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+}
+
+#[test]
+fn select_double_size_uid() {
+    let mut mfrc522 = mfrc522!(CrcMode::default());
+    let atqa = mfrc522.reqa().unwrap();
+    assert_eq!(atqa.as_bytes(), &[0x44, 0x00]);
+
+    let card = mfrc522.select(&atqa).unwrap();
+    assert!(matches!(card.uid(), Uid::Double(_)));
+    assert_eq!(card.uid().as_bytes(), &UID);
+    assert_eq!(card.uid().sak().as_byte(), 0x00);
+}
+
+#[test]
+fn read_with_each_crc_mode() {
+    for crc_mode in [CrcMode::Coprocessor, CrcMode::Hardware, CrcMode::Software] {
+        let mut mfrc522 = mfrc522!(crc_mode);
+        let atqa = mfrc522.reqa().unwrap();
+        let mut card = mfrc522.select(&atqa).unwrap();
+
+        let data = card.ul_read(4).unwrap();
+        let expected: Vec<u8> = (16..32).collect();
+        assert_eq!(data.as_slice(), expected.as_slice(), "{:?}", crc_mode);
+
+        card.ul_write(4, [0xDE, 0xAD, 0xBE, 0xEF]).unwrap();
+        assert_eq!(card.ul_read(4).unwrap()[..4], [0xDE, 0xAD, 0xBE, 0xEF]);
+    }
+}
+
+#[test]
+fn wrong_crc_is_detected() {
+    let mut sim = Simulator::new(TestPicc::new());
+    sim.picc_mut().bad_crc = true;
+    let mut mfrc522 = Mfrc522::new(sim).init().unwrap();
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert!(matches!(card.ul_read(0), Err(Error::Crc(_))));
+    drop(card);
+
+    // the MFRC522 checking the CRC_A reports it in the ErrorReg
+    mfrc522.set_crc_mode(CrcMode::Hardware);
+    let atqa = mfrc522.wupa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    let error = card.ul_read(0).unwrap_err();
+    let context = error.context().unwrap();
+    assert_eq!(context.operation, Some(Operation::Read(0)));
+    assert_eq!(context.error_reg, 0x04);
+    assert_eq!(
+        error.to_string(),
+        "READ block 0: wrong CRC_A (ErrorReg 04: CRCErr, ComIrqReg 66)"
+    );
+}
+
+#[test]
+fn frames_longer_than_the_fifo() {
+    // the coprocessor gets the data of the CRC_A in several parts as well
+    for crc_mode in [CrcMode::Coprocessor, CrcMode::Hardware, CrcMode::Software] {
+        let mut mfrc522 = mfrc522!(crc_mode);
+        let atqa = mfrc522.reqa().unwrap();
+        let mut card = mfrc522.select(&atqa).unwrap();
+
+        let mut rx = [0u8; 240];
+        assert_eq!(card.ul_fast_read(0, 59, &mut rx).unwrap(), 240);
+        let expected: Vec<u8> = (0..240).map(|i| i as u8).collect();
+        assert_eq!(rx.as_slice(), expected.as_slice(), "{:?}", crc_mode);
+    }
+}
+
+#[test]
+fn halted_picc_only_answers_wupa() {
+    let mut mfrc522 = mfrc522!(CrcMode::default());
+    let atqa = mfrc522.reqa().unwrap();
+    mfrc522.select(&atqa).unwrap().finish().unwrap();
+
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+    let atqa = mfrc522.wupa().unwrap();
+    assert!(mfrc522.select(&atqa).is_ok());
+}
+
+#[test]
+fn antenna_off_resets_the_picc() {
+    let mut mfrc522 = mfrc522!(CrcMode::default());
+    let atqa = mfrc522.reqa().unwrap();
+    mfrc522.select(&atqa).unwrap().finish().unwrap();
+
+    mfrc522.antenna_off().unwrap();
+    assert!(matches!(mfrc522.reqa(), Err(Error::Timeout(_))));
+    mfrc522.antenna_on().unwrap();
+    assert!(mfrc522.reqa().is_ok());
+}
+
+#[test]
+fn raw_short_frame() {
+    let mut mfrc522 = mfrc522!(CrcMode::default());
+    let mut rx = [0u8; 2];
+    let response = mfrc522
+        .transceive_raw(&RawFrame::new(&[0x26]).with_last_bits(7), &mut rx)
+        .unwrap();
+    assert_eq!(response.valid_bytes(), 2);
+    assert_eq!(response.bit_len(), 16);
+    assert_eq!(rx, [0x44, 0x00]);
+}
+
+/// PICC answering every frame with 100 bytes, the last one is `last`
+struct LongAnswer {
+    last: u8,
+}
+
+impl Picc for LongAnswer {
+    fn transceive(&mut self, _: &Frame) -> Option<Frame> {
+        let mut bytes = [0u8; 100];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        bytes[99] = self.last;
+        Some(Frame::new(&bytes))
+    }
+}
+
+#[test]
+fn raw_collision_after_the_fifo_was_drained() {
+    let field = (LongAnswer { last: 0x0F }, LongAnswer { last: 0x1F });
+    let mut mfrc522 = Mfrc522::new(Simulator::new(field)).init().unwrap();
+    let mut rx = [0u8; 100];
+    let response = mfrc522
+        .transceive_raw(&RawFrame::new(&[0x40]), &mut rx)
+        .unwrap();
+
+    // the collision is beyond the range of CollPos
+    assert_eq!(response.collision(), None);
+    assert_eq!(response.valid_bytes(), 100);
+    for (i, &b) in rx[..99].iter().enumerate() {
+        assert_eq!(b, i as u8);
+    }
+}
+
+/// Simulator of which the CRC coprocessor never finishes
+struct NoCrc(Simulator<TestPicc>);
+
+impl spi::Transfer<u8> for NoCrc {
+    type Error = Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Infallible> {
+        let div_irq = words[0] == Register::DivIrqReg.read_address();
+        self.0.transfer(words)?;
+        if div_irq {
+            // CRCIRq
+            words[1] &= !(1 << 2);
+        }
+        Ok(words)
+    }
+}
+
+impl spi::Write<u8> for NoCrc {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        self.0.write(words)
+    }
+}
+
+#[test]
+fn crc_coprocessor_timeout() {
+    let mut mfrc522 = Mfrc522::new(NoCrc(Simulator::new(TestPicc::new())))
+        .init()
+        .unwrap();
+    let atqa = mfrc522.reqa().unwrap();
+    let Err(error) = mfrc522.select(&atqa) else {
+        panic!("selected without CRC_A");
+    };
+    assert!(matches!(error, Error::Timeout(c) if c.operation == Some(Operation::CalcCrc)));
+    assert_eq!(
+        error.to_string(),
+        "CalcCRC: the MFRC522 did not finish in time"
+    );
+}
@@ -0,0 +1,86 @@
+use heapless::Deque;
+use mfrc522::error::Error;
+use mfrc522::sim::{Simulator, Ultralight};
+use mfrc522::trace::{Access, Recorder, Replay, Transaction};
+use mfrc522::{Mfrc522, Register};
+
+const UID: [u8; 7] = [0x04, 0x51, 0x2C, 0x7A, 0x1B, 0x48, 0x80];
+
+/// Select the NTAG and read its capability container
+fn record() -> Vec<Transaction> {
+    let sim = Simulator::new(Ultralight::new_ntag213(UID));
+    let recorder = Recorder::new(sim, Deque::<Transaction, 1024>::new());
+    let mut mfrc522 = Mfrc522::new(recorder).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.ul_read(3).unwrap()[..4], [0xE1, 0x10, 0x12, 0x00]);
+    drop(card);
+
+    let (recorder, _) = mfrc522.release();
+    let (_, trace) = recorder.release();
+    assert!(!trace.is_full());
+    trace.into_iter().collect()
+}
+
+#[test]
+fn transactions_are_decoded() {
+    let trace = record();
+    // init starts with a soft reset
+    assert_eq!(trace[0].access(), Access::Write);
+    assert_eq!(trace[0].register(), Some(Register::CommandReg));
+    assert_eq!(trace[0].values(), [0x0F]);
+
+    // the answer to READ is read from the FIFO in one burst
+    assert!(trace.iter().any(|t| t.access() == Access::Read
+        && t.register() == Some(Register::FIFODataReg)
+        && t.values().len() == 18));
+}
+
+#[test]
+fn replay() {
+    let trace = record();
+    let mut mfrc522 = Mfrc522::new(Replay::new(&trace)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    assert_eq!(card.uid().as_bytes(), &UID);
+    assert_eq!(card.ul_read(3).unwrap()[..4], [0xE1, 0x10, 0x12, 0x00]);
+    drop(card);
+
+    let (replay, _) = mfrc522.release();
+    assert!(replay.is_finished());
+}
+
+#[test]
+fn replay_divergence() {
+    let trace = record();
+    let mut mfrc522 = Mfrc522::new(Replay::new(&trace)).init().unwrap();
+
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    // another page than recorded
+    match card.ul_read(4) {
+        Err(Error::Spi(divergence)) => {
+            let expected = divergence.expected.unwrap();
+            assert_eq!(expected.register(), Some(Register::FIFODataReg));
+            assert_eq!(expected.values()[1], 3);
+            assert_eq!(divergence.actual.values()[1], 4);
+        }
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn save_and_load() {
+    let trace = record();
+    let mut text = Vec::new();
+    mfrc522::trace::save(&trace, &mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.starts_with("W CommandReg 0F\n"));
+
+    let annotated = format!("# NTAG213, READ of page 3\n\n{}", text);
+    assert_eq!(mfrc522::trace::load(annotated.as_bytes()).unwrap(), trace);
+    assert!(mfrc522::trace::load("R NoSuchReg 00".as_bytes()).is_err());
+}
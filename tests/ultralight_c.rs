@@ -0,0 +1,134 @@
+#![cfg(feature = "crypto")]
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+
+use mfrc522::error::{Error, Nak};
+use mfrc522::sim::{Activation, Frame, Iso14443a, Picc, Simulator};
+use mfrc522::{Mfrc522, UltralightCKey};
+use rand_core::{impls, RngCore};
+
+const UID: [u8; 7] = [0x04, 0x1F, 0x5E, 0x2A, 0x93, 0x3C, 0x80];
+/// Default key of the MIFARE Ultralight C, "BREAKMEIFYOUCAN!"
+const DEFAULT_KEY: UltralightCKey = [
+    0x49, 0x45, 0x4D, 0x4B, 0x41, 0x45, 0x52, 0x42, 0x21, 0x4E, 0x41, 0x43, 0x55, 0x4F, 0x59, 0x46,
+];
+
+/// The authentication with the default key, recorded from the exchange with a PICC using
+/// the random number RndB `10 11 12 ..`, and RndA `A0 A1 A2 ..`
+const AUTHENTICATION: [(&str, &str); 2] = [
+    ("1A00", "AFABD7163461A1AFA7"),
+    ("AFBA4AC644ECB1C44D1BBD4D6D582A9A59", "00C678480AD0B39C1F"),
+];
+
+/// MIFARE Ultralight C that answers the commands of a script
+struct Scripted {
+    iso: Iso14443a,
+    script: VecDeque<(Vec<u8>, Frame)>,
+}
+
+impl Scripted {
+    fn new(script: &[(&str, &str)]) -> Self {
+        Scripted {
+            iso: Iso14443a::new(&UID, [0x44, 0x00], 0x00),
+            script: script
+                .iter()
+                .map(|(c, r)| {
+                    let response = match hex(r) {
+                        nak if nak.len() == 1 => Frame::with_bits(&nak, 4),
+                        response => Frame::with_crc_a(&response),
+                    };
+                    (hex(c), response)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Picc for Scripted {
+    fn transceive(&mut self, frame: &Frame) -> Option<Frame> {
+        if let Activation::Handled(response) = self.iso.transceive(frame) {
+            return response;
+        }
+        let (command, response) = self.script.pop_front().expect("end of the script");
+        assert_eq!(frame.data_with_crc_a()?, command, "unexpected command");
+        Some(response)
+    }
+
+    fn field_off(&mut self) {
+        self.iso.field_off();
+    }
+}
+
+/// Random number generator returning `A0 A1 A2 ..`
+struct Counter(u8);
+
+impl RngCore for Counter {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            *byte = self.0;
+            self.0 = self.0.wrapping_add(1);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Select the PICC and authenticate with `key`, the whole script has to be used
+fn authenticate(script: &[(&str, &str)], key: &UltralightCKey) -> Result<(), Error<Infallible>> {
+    let mut mfrc522 = Mfrc522::new(Simulator::new(Scripted::new(script)))
+        .init()
+        .unwrap();
+    let atqa = mfrc522.reqa().unwrap();
+    let mut card = mfrc522.select(&atqa).unwrap();
+    let result = card.ulc_authenticate(key, &mut Counter(0xA0));
+    drop(card);
+
+    let (sim, _) = mfrc522.release();
+    assert!(sim.picc().script.is_empty());
+    result
+}
+
+#[test]
+fn ulc_authentication() {
+    assert!(authenticate(&AUTHENTICATION, &DEFAULT_KEY).is_ok());
+}
+
+#[test]
+fn ulc_picc_without_the_key() {
+    // the PICC does not return RndA
+    let mut script = AUTHENTICATION;
+    script[1].1 = "000001020304050607";
+    assert!(matches!(
+        authenticate(&script, &DEFAULT_KEY),
+        Err(Error::Integrity)
+    ));
+}
+
+#[test]
+fn ulc_not_supported() {
+    // a MIFARE Ultralight answers the authentication with a NAK
+    let script = [("1A00", "00")];
+    assert!(matches!(
+        authenticate(&script, &DEFAULT_KEY),
+        Err(Error::Nak(Nak::InvalidOperation, _))
+    ));
+}